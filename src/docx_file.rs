@@ -1,10 +1,18 @@
+use quick_xml::events::{BytesStart, Event};
 use std::fs::File;
 use std::io::{BufReader, Read, Seek};
 use std::path::Path;
+use thiserror::Error;
 use zip::result::ZipError;
 use zip::ZipArchive;
+
+use crate::docx_part::DocxPartType;
+use crate::transformers::find_and_replace::read_paragraphs;
+use crate::transformers::layout::tokens;
+use crate::ParagraphStyles;
 #[cfg(feature = "serde")]
 use {
+  crate::transformers::sections::Sections,
   crate::{CantSerializeError, DocxTemplate, Placeholders, Replacements},
   serde::Serialize,
 };
@@ -62,11 +70,174 @@ impl DocxFile<()> {
   }
 }
 
+/// A `{placeholder}` met in a document, see [DocxFile::scan_placeholders].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundPlaceholder {
+  /// The placeholder's text, brackets included, like `{name}`.
+  pub text: String,
+  /// Inner path of the part, like `word/document.xml` or `word/header1.xml`.
+  pub part: String,
+  /// Index of the paragraph within the part, in the document order.
+  pub paragraph: usize,
+}
+
+#[allow(missing_docs)]
+#[derive(Error, Debug)]
+pub enum CantScanError {
+  /// Read issues with the zip archive.
+  #[error(transparent)]
+  ZipErr(#[from] ZipError),
+  /// Probably a malformed `.docx` file.
+  #[error(transparent)]
+  ReadXmlErr(#[from] quick_xml::Error),
+}
+
+impl<R: Read + Seek> DocxFile<R> {
+  /// Lists `{…}` tokens residing in the main document, headers, footers, footnotes, endnotes
+  /// and the glossary document, which holds building blocks like cover pages.
+  ///
+  /// Tokens split over several runs are found as well, as the text of each paragraph is
+  /// looked through as a whole. Escaped characters are decoded, `{a &amp; b}` becomes `{a & b}`.
+  ///
+  /// ```rust
+  /// # use docx_template::DocxFile;
+  /// let placeholders = DocxFile::from_path("examples/template/input.docx")?
+  ///   .scan_placeholders("{", "}")?;
+  ///
+  /// let in_header = placeholders.iter().find(|it| it.part == "word/header1.xml").unwrap();
+  /// assert_eq!((in_header.text.as_str(), in_header.paragraph), ("{key}", 0));
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn scan_placeholders(
+    &mut self,
+    open_bracket: &str,
+    close_bracket: &str,
+  ) -> Result<Vec<FoundPlaceholder>, CantScanError> {
+    let (open, close) = (open_bracket.as_bytes(), close_bracket.as_bytes());
+    let mut found = Vec::new();
+
+    for idx in 0..self.archive.len() {
+      let mut file = self.archive.by_index(idx)?;
+      let part_of_layout: DocxPartType = file.name().into();
+      if !part_of_layout.is_layout() {
+        continue;
+      }
+
+      let part = file.name().to_owned();
+      let mut buf = Vec::new();
+      Read::read_to_end(&mut file, &mut buf).map_err(ZipError::Io)?;
+
+      // text of paragraphs is read the way placeholders are replaced, as pieces split by text
+      // boxes, which are numbered by the starts of their paragraphs
+      let mut pieces = Vec::<(usize, Vec<u8>)>::new();
+      read_paragraphs(&buf, |start, spans, _| {
+        pieces.push((start, spans.iter().flat_map(|span| &buf[span.clone()]).copied().collect()));
+        Ok::<_, CantScanError>(())
+      })?;
+      let mut starts = pieces.iter().map(|(start, _)| *start).collect::<Vec<_>>();
+      starts.sort_unstable();
+      starts.dedup();
+      pieces.sort_by_key(|(start, _)| *start);
+
+      for (start, text) in pieces {
+        let paragraph = starts.binary_search(&start).unwrap_or_default();
+        for token in tokens(&text, open, close) {
+          let token = String::from_utf8_lossy(&text[token]);
+          let placeholder = quick_xml::escape::unescape(&token).unwrap_or(token.clone());
+          found.push(FoundPlaceholder {
+            text: placeholder.into_owned(),
+            part: part.clone(),
+            paragraph,
+          });
+        }
+      }
+    }
+
+    Ok(found)
+  }
+
+  /// Heading and list styles defined in the document, to be given to converted Markdown or HTML.
+  ///
+  /// Styles are matched by their names, like `heading 1` or `List Bullet`,
+  /// as identifiers are localized. Missing styles are replaced by direct formatting.
+  /// Numbering of the list styles is picked up too, nested lists use its deeper levels.
+  pub fn paragraph_styles(&mut self) -> Result<ParagraphStyles, CantScanError> {
+    let mut styles = ParagraphStyles::unstyled();
+    let mut file = match self.archive.by_name("word/styles.xml") {
+      Ok(file) => file,
+      Err(ZipError::FileNotFound) => return Ok(styles),
+      Err(err) => return Err(err.into()),
+    };
+
+    let mut buf = Vec::new();
+    Read::read_to_end(&mut file, &mut buf).map_err(ZipError::Io)?;
+
+    let mut reader = quick_xml::Reader::from_reader(buf.as_slice());
+    let mut style_id = None;
+    // whether the style being read is the one of numbered or bulleted list items
+    let mut list = None;
+    loop {
+      match reader.read_event()? {
+        Event::Start(tag) if tag.name().as_ref() == b"w:style" => {
+          let is_paragraph = attribute(&tag, b"w:type").as_deref() == Some("paragraph");
+          style_id = attribute(&tag, b"w:styleId").filter(|_| is_paragraph);
+        }
+        Event::Empty(tag) if tag.name().as_ref() == b"w:name" => {
+          let (Some(id), Some(name)) = (&style_id, attribute(&tag, b"w:val")) else { continue };
+          let name = name.to_lowercase();
+          list = ["list bullet", "list number"].iter().position(|it| *it == name);
+          let slot = match name.as_str() {
+            "list bullet" => &mut styles.bullet_list,
+            "list number" => &mut styles.numbered_list,
+            name => match name.strip_prefix("heading ").and_then(|it| it.parse::<usize>().ok()) {
+              Some(level @ 1..=6) => &mut styles.headings[level - 1],
+              _ => continue,
+            },
+          };
+          *slot = Some(id.clone());
+        }
+        Event::Empty(tag) if tag.name().as_ref() == b"w:numId" => {
+          let slot = match list {
+            Some(0) => &mut styles.bullet_numbering,
+            Some(_) => &mut styles.numbered_numbering,
+            None => continue,
+          };
+          *slot = attribute(&tag, b"w:val");
+        }
+        Event::End(tag) if tag.name().as_ref() == b"w:style" => {
+          style_id = None;
+          list = None;
+        }
+        Event::Eof => break,
+        _ => {}
+      }
+    }
+
+    Ok(styles)
+  }
+}
+
+fn attribute(tag: &BytesStart<'_>, name: &[u8]) -> Option<String> {
+  let value = tag.try_get_attribute(name).ok()??.unescape_value().ok()?;
+  Some(value.into_owned())
+}
+
 #[cfg(feature = "serde")]
 impl<R> DocxFile<R> {
   /// A shortcut method for converting the `.docx` file into a template having `{placeholders}`.
   ///
   /// Placeholders and replacements will be taken from the `data` argument during render stage.
+  /// Fields of nested objects are reachable by paths, like `{client.address.city}` or `{items[0].sku}`.
+  /// Values can be formatted by [filters](crate::Filters): `{price | number:2}`.
+  ///
+  /// Arrays are rendered by sections. Paragraphs between `{#items}` and `{/items}` markers,
+  /// or table rows starting with `{#items}` and ending with `{/items}`, are repeated per each
+  /// element of the `items` array. Inside, `{name}` placeholders refer to the element's fields.
+  /// Sections can be nested.
+  ///
+  /// Conditional sections `{?flag}…{/flag}` keep their content only if `flag` is truthy,
+  /// while `{^flag}…{/flag}` only if it's falsy (`null`, `false`, `0`, `""`, `[]` or `{}`).
+  /// They may enclose runs of a single paragraph, sibling paragraphs or table rows.
   ///
   /// ```rust
   /// # use std::fs::File;
@@ -114,10 +285,16 @@ impl<R> DocxFile<R> {
     close_bracket: &str,
     data: impl Serialize,
   ) -> Result<DocxTemplate<'static, R>, CantSerializeError> {
-    let data = serde_json::to_value(data)?;
-    let placeholders =
-      Placeholders::from_json_keys_with_brackets(open_bracket, close_bracket, &data);
-    let replacements = Replacements::from_json_object_fields(&data);
-    Ok(DocxTemplate::new(self, placeholders, replacements))
+    let sections = Sections {
+      open_bracket: open_bracket.to_owned(),
+      close_bracket: close_bracket.to_owned(),
+      data: serde_json::to_value(data)?,
+      filters: Default::default(),
+    };
+    // placeholders are discovered and filled in by sections
+    Ok(
+      DocxTemplate::new(self, Placeholders::default(), Replacements::default())
+        .with_sections(sections),
+    )
   }
 }
@@ -5,17 +5,68 @@ pub enum DocxPartType {
   Main,
   Header,
   Footer,
+  Footnotes,
+  Endnotes,
+  /// Building blocks, like Quick Parts and AutoText entries.
+  Glossary,
   Comments,
+  /// `word/commentsExtended.xml`, replies and resolved states of comments.
+  CommentsExtended,
+  /// `word/commentsIds.xml`, durable ids of comments.
+  CommentsIds,
+  /// `word/commentsExtensible.xml`, dates of comments.
+  CommentsExtensible,
+  /// `docProps/core.xml`, the title, the author, etc.
+  CoreProperties,
+  /// `docProps/custom.xml`, properties defined by the user.
+  CustomProperties,
+  /// `.rels` files listing targets of hyperlinks, images, headers, etc.
+  Relationships,
   #[default]
   Unknown,
 }
 
 #[allow(missing_docs)]
 impl DocxPartType {
-  #[allow(dead_code)]
   pub fn comments() -> &'static str {
     "word/comments.xml"
   }
+
+  pub fn comments_extended() -> &'static str {
+    "word/commentsExtended.xml"
+  }
+
+  pub fn comments_ids() -> &'static str {
+    "word/commentsIds.xml"
+  }
+
+  /// Returns true for parts holding the document's text, where placeholders are looked for.
+  pub fn is_layout(self) -> bool {
+    match self {
+      Self::Main
+      | Self::Header
+      | Self::Footer
+      | Self::Footnotes
+      | Self::Endnotes
+      | Self::Glossary => true,
+      Self::Comments
+      | Self::CommentsExtended
+      | Self::CommentsIds
+      | Self::CommentsExtensible
+      | Self::CoreProperties
+      | Self::CustomProperties
+      | Self::Relationships
+      | Self::Unknown => false,
+    }
+  }
+
+  /// Returns true for parts describing comments, rather than the commented text.
+  pub fn is_comments(self) -> bool {
+    matches!(
+      self,
+      Self::Comments | Self::CommentsExtended | Self::CommentsIds | Self::CommentsExtensible
+    )
+  }
 }
 
 impl<S: AsRef<str>> From<S> for DocxPartType {
@@ -23,10 +74,34 @@ impl<S: AsRef<str>> From<S> for DocxPartType {
     match path.as_ref() {
       "word/document.xml" => Self::Main,
       "word/comments.xml" => Self::Comments,
+      "word/commentsExtended.xml" => Self::CommentsExtended,
+      "word/commentsIds.xml" => Self::CommentsIds,
+      "word/commentsExtensible.xml" => Self::CommentsExtensible,
+      "word/footnotes.xml" => Self::Footnotes,
+      "word/endnotes.xml" => Self::Endnotes,
+      "word/glossary/document.xml" => Self::Glossary,
+      "docProps/core.xml" => Self::CoreProperties,
+      "docProps/custom.xml" => Self::CustomProperties,
       // it's more like "word/header[0-9]*.xml", but regex crate is too heavy here
       path if path.starts_with(r#"word/header"#) && path.ends_with(".xml") => Self::Header,
       path if path.starts_with(r#"word/footer"#) && path.ends_with(".xml") => Self::Footer,
+      path if path.ends_with(".rels") => Self::Relationships,
       _ => Self::Unknown,
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn recognizes_layout_parts() {
+    assert_eq!(DocxPartType::from("word/footnotes.xml"), DocxPartType::Footnotes);
+    assert_eq!(DocxPartType::from("word/endnotes.xml"), DocxPartType::Endnotes);
+    assert_eq!(DocxPartType::from("word/glossary/document.xml"), DocxPartType::Glossary);
+    assert!(DocxPartType::from("word/footer2.xml").is_layout());
+    assert!(!DocxPartType::from("word/_rels/footnotes.xml.rels").is_layout());
+    assert!(!DocxPartType::from("word/comments.xml").is_layout());
+  }
+}
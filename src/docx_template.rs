@@ -1,14 +1,37 @@
-use aho_corasick::BuildError;
+use aho_corasick::automaton::OverlappingState;
+use aho_corasick::{BuildError, Input};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::io::{Cursor, Read, Seek, Write};
 use thiserror::Error;
 use zip::read::ZipFile;
 use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
 
 use crate::docx_file::DocxFile;
 use crate::docx_part::DocxPartType;
-use crate::transformers::find_and_replace::{FindAndReplace, Placeholders, Replacements};
+use crate::image::{ImageFit, ImageFormat, ImageHeader};
+use crate::properties::{self, CoreProperty, DocumentProperties, PropertyValue};
+use crate::relationships::{image_targets, relationship_targets, resolve_target, Linker};
+use crate::render_report::{RenderMode, RenderReport};
+use crate::transformers::bookmarks::Bookmarks;
+use crate::transformers::checkboxes::Checkboxes;
+use crate::transformers::content_controls::{ContentControls, Fill};
+use crate::transformers::drawings::{self, Extent};
+use crate::transformers::erase_commented::comments::{self, Selection};
+use crate::transformers::erase_commented::{EraseCommented, StripComments};
+use crate::transformers::fields::{FieldKind, Fields};
+use crate::transformers::find_and_replace::{FindAndReplace, Placeholders, Replacements, Value};
+use crate::transformers::layout::Layout;
 use crate::zip_file_ext::ZipFileExt;
+#[cfg(feature = "serde")]
+use crate::{
+  transformers::sections::{Sections, Usage},
+  Filters,
+};
+
+const CONTENT_TYPES: &str = "[Content_Types].xml";
+const PACKAGE_RELS: &str = "_rels/.rels";
 
 #[derive(Debug, Clone)]
 /// Builder accumulating all the transformations over `.docx` file.
@@ -18,6 +41,38 @@ pub struct DocxTemplate<'a, R> {
   replacements: Option<Replacements<'a>>,
   inner_files_to_replace: HashMap<&'a str, &'a [u8]>,
   comments_to_delete: HashSet<&'a str>,
+  strip_comments: bool,
+  bookmarks: Vec<(String, Value)>,
+  keep_bookmarks: bool,
+  content_controls: Vec<(String, Fill)>,
+  checkboxes: Vec<(String, bool)>,
+  images_to_replace: Vec<ImageReplacement<'a>>,
+  replace_in_link_targets: bool,
+  fields: Option<Fields>,
+  properties: DocumentProperties,
+  #[cfg(feature = "serde")]
+  sections: Option<Sections>,
+  /// Filters of placeholders derived from json, sections have their own ones.
+  #[cfg(feature = "serde")]
+  filters: Filters,
+  mode: RenderMode,
+  warnings: RenderReport,
+}
+
+/// An image to be replaced, see [DocxTemplate::replace_image_by_alt_text].
+#[derive(Debug, Clone, Copy)]
+struct ImageReplacement<'a> {
+  image: ImageRef<'a>,
+  bytes: &'a [u8],
+  fit: ImageFit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageRef<'a> {
+  /// Alt text, title or name of the drawing.
+  AltText(&'a str),
+  /// Path of the media file inside the archive.
+  Path(&'a str),
 }
 
 #[allow(missing_docs)]
@@ -32,6 +87,15 @@ pub enum CantRenderError {
   /// Probably a malformed `.docx` file.
   #[error(transparent)]
   ReadXmlErr(#[from] quick_xml::Error),
+  /// A `{#section}` marker has no matching `{/section}` one, or vice versa.
+  #[error("section {0:?} is not closed properly")]
+  UnbalancedSectionErr(String),
+  /// A custom property is set to NaN or an infinite number, which can't be stored.
+  #[error("custom property {0:?} is not a finite number")]
+  NonFiniteNumberErr(String),
+  /// Some placeholders were left unfilled, or some data was never used, see [RenderMode::Strict].
+  #[error("{0}")]
+  StrictRenderErr(RenderReport),
 }
 
 impl<'a, R> DocxTemplate<'a, R> {
@@ -50,6 +114,21 @@ impl<'a, R> DocxTemplate<'a, R> {
       replacements: Some(replacements),
       inner_files_to_replace: Default::default(),
       comments_to_delete: Default::default(),
+      strip_comments: false,
+      bookmarks: Vec::new(),
+      keep_bookmarks: false,
+      content_controls: Vec::new(),
+      checkboxes: Vec::new(),
+      images_to_replace: Default::default(),
+      replace_in_link_targets: false,
+      fields: None,
+      properties: DocumentProperties::default(),
+      #[cfg(feature = "serde")]
+      sections: None,
+      #[cfg(feature = "serde")]
+      filters: Filters::default(),
+      mode: RenderMode::default(),
+      warnings: RenderReport::default(),
     }
   }
 }
@@ -87,7 +166,68 @@ impl<R> DocxTemplate<'_, R> {
       replacements: None,
       inner_files_to_replace: Default::default(),
       comments_to_delete: Default::default(),
+      strip_comments: false,
+      bookmarks: Vec::new(),
+      keep_bookmarks: false,
+      content_controls: Vec::new(),
+      checkboxes: Vec::new(),
+      images_to_replace: Default::default(),
+      replace_in_link_targets: false,
+      fields: None,
+      properties: DocumentProperties::default(),
+      #[cfg(feature = "serde")]
+      sections: None,
+      #[cfg(feature = "serde")]
+      filters: Filters::default(),
+      mode: RenderMode::default(),
+      warnings: RenderReport::default(),
+    }
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<R> DocxTemplate<'_, R> {
+  /// Fill in placeholders and expand `{#sections}` with the json data.
+  pub(crate) fn with_sections(mut self, sections: Sections) -> Self {
+    self.sections = Some(sections);
+    self
+  }
+
+  /// Add a custom filter applicable inside placeholders, like `{price | currency}`.
+  ///
+  /// Filters are applied to templates made by [DocxFile::into_template](crate::DocxFile::into_template),
+  /// or to the ones having [placeholders derived from json](Placeholders::from_json_keys_with_brackets).
+  ///
+  /// ```rust
+  /// # use docx_template::DocxFile;
+  /// use serde_json::json;
+  ///
+  /// DocxFile::from_path("examples/template/input.docx")?
+  ///   .into_template(json!({ "price": 13.37 }))?
+  ///   .register_filter("currency", |value, _args| json!(format!("${}", value)))
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn register_filter<F>(&mut self, name: impl Into<String>, filter: F) -> &mut Self
+  where
+    F: Fn(&serde_json::Value, &[serde_json::Value]) -> serde_json::Value + Send + Sync + 'static,
+  {
+    let filters = match self.sections {
+      Some(ref mut sections) => &mut sections.filters,
+      None => &mut self.filters,
+    };
+    filters.register(name, filter);
+    self
+  }
+
+  /// Replace the registry of filters applicable inside placeholders, see [Filters].
+  pub fn use_filters(&mut self, filters: Filters) -> &mut Self {
+    match self.sections {
+      Some(ref mut sections) => sections.filters = filters,
+      None => self.filters = filters,
     }
+    self
   }
 }
 
@@ -103,11 +243,11 @@ impl<'a, R: Read + Seek> DocxTemplate<'a, R> {
     self
   }
 
-  #[doc(hidden)]
-  // todo: a screenshot
-  /// Encountering a comment with `{placeholder}` content will _delete_ the whole commented block.
+  /// Encountering a comment with `{placeholder}` content will _delete_ the whole commented block,
+  /// along with the comment itself.
   ///
   /// To remove the image from a document, wrap it in a comment, and delete using this method.
+  /// The comment's text must be exactly the placeholder, surrounding spaces aside.
   ///
   /// ```rust
   /// # use std::fs::File;
@@ -127,11 +267,144 @@ impl<'a, R: Read + Seek> DocxTemplate<'a, R> {
     self
   }
 
+  /// Remove all the comments, keeping the commented text, so that reviewers' notes
+  /// never reach the resulting document.
+  ///
+  /// ```rust
+  /// # use docx_template::DocxFile;
+  /// # use serde_json::json;
+  ///
+  /// DocxFile::from_path("features/comments_and_bookmarks.docx")?
+  ///   .into_template(json!({}))?
+  ///   .strip_comments(true)
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn strip_comments(&mut self, enabled: bool) -> &mut Self {
+    self.strip_comments = enabled;
+    self
+  }
+
+  /// Replace everything between the start and the end of the bookmark with the value,
+  /// for templates marking fields with bookmarks rather than `{placeholders}`.
+  ///
+  /// Bookmark names are case-insensitive. Paragraphs the bookmark spans are merged into
+  /// the first one, the value gets the formatting of the bookmark's first run.
+  ///
+  /// ```rust
+  /// # use docx_template::{DocxFile, RenderMode, Value};
+  /// # use serde_json::json;
+  ///
+  /// // fails if any of the bookmarks is missing
+  /// DocxFile::from_path("features/bookmarks.docx")?
+  ///   .into_template(json!({}))?
+  ///   .replace_bookmark("ClientName", "Alphabet Inc.")
+  ///   .replace_bookmark("Notes", Value::from_markdown("**Paid** in full"))
+  ///   .render_mode(RenderMode::Strict)
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn replace_bookmark(&mut self, name: &str, value: impl Into<Value>) -> &mut Self {
+    self.bookmarks.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    self.bookmarks.push((name.to_owned(), value.into()));
+    self
+  }
+
+  /// Keep replaced bookmarks around the new content, so they can be found in the document.
+  pub fn keep_bookmarks(&mut self, enabled: bool) -> &mut Self {
+    self.keep_bookmarks = enabled;
+    self
+  }
+
+  /// Fill the content control having the tag or the title, the flag showing its placeholder
+  /// is cleared.
+  ///
+  /// Plain text, rich text, date picker, dropdown and combo box controls are supported.
+  /// Dates given as `YYYY-MM-DD` are set as the picker's date, dropdowns select the item
+  /// having the value or the display text.
+  ///
+  /// ```rust
+  /// # use docx_template::{DocxFile, RenderMode, Value};
+  /// # use serde_json::json;
+  ///
+  /// // fails if any of the controls is missing
+  /// DocxFile::from_path("features/controls.docx")?
+  ///   .into_template(json!({}))?
+  ///   .fill_content_control("client", "Alphabet Inc.")
+  ///   .fill_content_control("due", "2024-05-28")
+  ///   .render_mode(RenderMode::Strict)
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn fill_content_control(&mut self, name: &str, value: impl Into<Value>) -> &mut Self {
+    self.content_controls.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    self.content_controls.push((name.to_owned(), Fill::Value(value.into())));
+    self
+  }
+
+  /// Repeat the first item of the repeating section control per each of the `items`,
+  /// filling the item's inner controls by their tags or titles.
+  ///
+  /// ```rust
+  /// # use docx_template::{DocxFile, RenderMode, Value};
+  /// # use serde_json::json;
+  ///
+  /// // fails if the section is missing
+  /// DocxFile::from_path("features/controls.docx")?
+  ///   .into_template(json!({}))?
+  ///   .fill_repeating_section("goods", vec![
+  ///     vec![("name".to_owned(), Value::from("Tea")), ("price".to_owned(), Value::from("3"))],
+  ///     vec![("name".to_owned(), Value::from("Milk")), ("price".to_owned(), Value::from("2"))],
+  ///   ])
+  ///   .render_mode(RenderMode::Strict)
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn fill_repeating_section(
+    &mut self,
+    name: &str,
+    items: Vec<Vec<(String, Value)>>,
+  ) -> &mut Self {
+    self.content_controls.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    self.content_controls.push((name.to_owned(), Fill::Items(items)));
+    self
+  }
+
+  /// Tick or clear the checkbox, either a content control found by its tag or title,
+  /// or a legacy form field found by its name or a bookmark around it.
+  ///
+  /// A `{placeholder}` run right before or after a checkbox addresses it too, and is removed.
+  /// Boolean data fields of [DocxFile::into_template](crate::DocxFile::into_template) are put into checkboxes the same way.
+  ///
+  /// ```rust
+  /// # use docx_template::{DocxFile, RenderMode};
+  /// # use serde_json::json;
+  ///
+  /// // fails if any of the checkboxes is missing
+  /// DocxFile::from_path("features/checkboxes.docx")?
+  ///   .into_template(json!({ "paid": true }))?
+  ///   .check("agree", true)
+  ///   .render_mode(RenderMode::Strict)
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn check(&mut self, name: &str, checked: bool) -> &mut Self {
+    self.checkboxes.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    self.checkboxes.push((name.to_owned(), checked));
+    self
+  }
+
   /// Replace a file inside `.docx` archive.
   ///
   /// Method is quite handy for switching images in a document.
   /// The formatting options won't be changed, so it's wise to preserve
   /// the image's size (width × height), as well as the codec (png, jpg, ...).
+  /// Otherwise, see [DocxTemplate::replace_inner_file_fitting].
   ///
   /// ```rust
   /// # use std::fs::File;
@@ -148,14 +421,224 @@ impl<'a, R: Read + Seek> DocxTemplate<'a, R> {
     self.inner_files_to_replace.insert(inner_path, bytes);
     self
   }
+
+  /// Replace an image inside `.docx` archive, resizing frames of the drawings referencing it.
+  ///
+  /// The new size is computed from the image's header: width, height and resolution
+  /// of PNG, JPEG, GIF, BMP and TIFF images are read.
+  /// The content type is fixed if the format differs.
+  ///
+  /// ```rust
+  /// # use std::io::{Read, Seek};
+  /// # use docx_template::{DocxTemplate, CantRenderError, ImageFit};
+  /// # fn generate<R>(template: &mut DocxTemplate<R>) -> Result<(), CantRenderError> where R: Read + Seek {
+  /// let cat = include_bytes!("../examples/image-replacement/cat.jpg");
+  /// template.replace_inner_file_fitting("word/media/image1.jpg", cat, ImageFit::Contain).render()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn replace_inner_file_fitting(
+    &mut self,
+    inner_path: &'a str,
+    bytes: &'a [u8],
+    fit: ImageFit,
+  ) -> &mut Self {
+    self.inner_files_to_replace.insert(inner_path, bytes);
+    self.images_to_replace.push(ImageReplacement { image: ImageRef::Path(inner_path), bytes, fit });
+    self
+  }
+
+  /// Replace an image, which has the `alt_text` set as its alt text, title or name.
+  ///
+  /// Unlike [DocxTemplate::replace_inner_file], the path of the image inside the archive
+  /// is not required: it's found through relationships of the part the image resides in.
+  /// The content type is fixed if the format differs, the frame is left as is.
+  ///
+  /// ```rust
+  /// # use std::io::{Read, Seek};
+  /// # use docx_template::{DocxTemplate, CantRenderError};
+  /// # fn generate<R>(template: &mut DocxTemplate<R>) -> Result<(), CantRenderError> where R: Read + Seek {
+  /// let cat = include_bytes!("../examples/image-replacement/cat.jpg");
+  /// template.replace_image_by_alt_text("{logo}", cat).render()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn replace_image_by_alt_text(&mut self, alt_text: &'a str, bytes: &'a [u8]) -> &mut Self {
+    self.replace_image_by_alt_text_fitting(alt_text, bytes, ImageFit::KeepBox)
+  }
+
+  /// Replace an image found by its alt text, changing the frame's height to keep
+  /// the new image's aspect ratio. The width is left as is.
+  pub fn replace_image_by_alt_text_keeping_ratio(
+    &mut self,
+    alt_text: &'a str,
+    bytes: &'a [u8],
+  ) -> &mut Self {
+    self.replace_image_by_alt_text_fitting(alt_text, bytes, ImageFit::KeepWidth)
+  }
+
+  /// Replace an image found by its alt text, resizing its frame to the new image's proportions.
+  pub fn replace_image_by_alt_text_fitting(
+    &mut self,
+    alt_text: &'a str,
+    bytes: &'a [u8],
+    fit: ImageFit,
+  ) -> &mut Self {
+    let image = ImageRef::AltText(alt_text);
+    self.images_to_replace.push(ImageReplacement { image, bytes, fit });
+    self
+  }
+
+  /// Replace placeholders in targets of hyperlinks, like `https://portal/orders/{id}`.
+  ///
+  /// Targets are kept in `.rels` files, so they are left as is by default.
+  /// Values are put as plain text, without formatting.
+  ///
+  /// ```rust
+  /// # use docx_template::{DocxFile, RenderMode};
+  /// use serde_json::json;
+  ///
+  /// // fails if `order` is left unused, as it's met in the link target only
+  /// DocxFile::from_path("features/links.docx")?
+  ///   .into_template(json!({ "order": { "id": "kx-1042" }, "link": "KX-1042" }))?
+  ///   .replace_in_link_targets(true)
+  ///   .render_mode(RenderMode::Strict)
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn replace_in_link_targets(&mut self, enabled: bool) -> &mut Self {
+    self.replace_in_link_targets = enabled;
+    self
+  }
+
+  /// Fill in results of `MERGEFIELD` and `DOCPROPERTY` fields, like `{ MERGEFIELD CustomerName }`.
+  ///
+  /// The field's name is looked up as if it was a placeholder, `CustomerName` matches `{CustomerName}`.
+  /// `DOCPROPERTY` fields take custom properties set by [DocxTemplate::set_custom_property] first.
+  /// Plain text of the value is put, merge fields having no value are reported in strict mode.
+  /// Fields are left updatable, so Word may refresh them, see [DocxTemplate::unlink_fields].
+  ///
+  /// ```rust
+  /// # use docx_template::{DocxFile, RenderMode};
+  /// use serde_json::json;
+  ///
+  /// // fails if a merge field is left without a value
+  /// DocxFile::from_path("features/fields.docx")?
+  ///   .into_template(json!({ "Customer": "Alphabet Inc." }))?
+  ///   .fill_fields(true)
+  ///   .render_mode(RenderMode::Strict)
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn fill_fields(&mut self, enabled: bool) -> &mut Self {
+    self.fields = enabled.then(|| self.fields.unwrap_or_default());
+    self
+  }
+
+  /// Fill in `MERGEFIELD` and `DOCPROPERTY` fields, replacing them with plain results.
+  pub fn unlink_fields(&mut self, enabled: bool) -> &mut Self {
+    match self.fields {
+      Some(ref mut fields) => fields.unlink = enabled,
+      None if enabled => self.fields = Some(Fields { unlink: true }),
+      None => {}
+    }
+    self
+  }
+
+  /// Set a field shown in File → Info → Properties, like the title or the author.
+  ///
+  /// `docProps/core.xml` is created if the document has none.
+  ///
+  /// ```rust
+  /// # use docx_template::{CoreProperty, DocxFile};
+  /// use serde_json::json;
+  ///
+  /// DocxFile::from_path("examples/template/input.docx")?
+  ///   .into_template(json!({ "key": "value" }))?
+  ///   .set_core_property(CoreProperty::Title, "Service agreement")
+  ///   .set_core_property(CoreProperty::Creator, "Acme Inc.")
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn set_core_property(
+    &mut self,
+    property: CoreProperty,
+    value: impl Into<String>,
+  ) -> &mut Self {
+    self.properties.set_core(property, value.into());
+    self
+  }
+
+  /// Add or update a custom property, listed in File → Properties → Advanced Properties → Custom.
+  ///
+  /// `docProps/custom.xml` is created if the document has none. Names are case-insensitive.
+  ///
+  /// ```rust
+  /// # use docx_template::{DocxFile, PropertyValue};
+  /// use serde_json::json;
+  ///
+  /// DocxFile::from_path("examples/template/input.docx")?
+  ///   .into_template(json!({ "key": "value" }))?
+  ///   .set_custom_property("Customer", "Alphabet Inc.")
+  ///   .set_custom_property("Signed", PropertyValue::Date("2024-05-31T12:00:00Z".into()))
+  ///   .set_custom_property("Revision", 3)
+  ///   .render()?;
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn set_custom_property(
+    &mut self,
+    name: impl Into<String>,
+    value: impl Into<PropertyValue>,
+  ) -> &mut Self {
+    self.properties.set_custom(name.into(), value.into());
+    self
+  }
+
+  /// Check for `{tokens}` left in the document and data matching nothing while rendering.
+  ///
+  /// In [strict](RenderMode::Strict) mode render fails with [CantRenderError::StrictRenderErr],
+  /// in [lenient](RenderMode::Lenient) mode the same report is available through
+  /// [DocxTemplate::warnings]. Tokens are looked for between the template's brackets: the ones set
+  /// by [DocxFile::into_template_having_brackets](crate::DocxFile::into_template_having_brackets),
+  /// the ones of [Placeholders::with_brackets], or the ones shared by the placeholders,
+  /// `{` and `}` if the placeholders share none.
+  ///
+  /// ```rust
+  /// # use docx_template::{CantRenderError, DocxFile, RenderMode};
+  /// use serde_json::json;
+  ///
+  /// let result = DocxFile::from_path("examples/template/input.docx")?
+  ///   .into_template(json!({ "key": "value", "unknown": 42 }))?
+  ///   .render_mode(RenderMode::Strict)
+  ///   .render();
+  ///
+  /// let Err(CantRenderError::StrictRenderErr(report)) = result else { panic!() };
+  /// assert!(report.unresolved_placeholders.contains(&"{undefined_placholder}".to_owned()));
+  /// assert_eq!(report.unused_keys, ["unknown"]);
+  ///
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn render_mode(&mut self, mode: RenderMode) -> &mut Self {
+    self.mode = mode;
+    self
+  }
+
+  /// Issues found by the last render in [lenient](RenderMode::Lenient) mode.
+  pub fn warnings(&self) -> &RenderReport {
+    &self.warnings
+  }
 }
 
-impl<R: Read + Seek> DocxTemplate<'_, R> {
+impl<'a, R: Read + Seek> DocxTemplate<'a, R> {
   /// Render the template applying all the transformations set before.
   ///
   /// Returns a byte array, content of a `.docx` file.
   pub fn render(&mut self) -> Result<Vec<u8>, CantRenderError> {
-    self.render_to(Cursor::new(Vec::new())).map(Cursor::into_inner)
+    self.render_parts_to(Cursor::new(Vec::new())).map(Cursor::into_inner)
   }
 
   /// Render the template applying all the transformations set before.
@@ -165,75 +648,524 @@ impl<R: Read + Seek> DocxTemplate<'_, R> {
   /// Even though the function accepts generic parameter writer: `W` by value,
   /// you [may pass] a `&mut writer` reference if necessary.
   ///
+  /// In [strict](RenderMode::Strict) mode the document is rendered in memory first,
+  /// so nothing is written to the `writer` if the render fails the checks.
+  ///
   /// [may pass]: https://rust-lang.github.io/api-guidelines/interoperability.html#generic-readerwriter-functions-take-r-read-and-w-write-by-value-c-rw-value
-  pub fn render_to<W: Write + Seek>(&mut self, writer: W) -> Result<W, CantRenderError> {
+  pub fn render_to<W: Write + Seek>(&mut self, mut writer: W) -> Result<W, CantRenderError> {
+    if self.mode != RenderMode::Strict {
+      return self.render_parts_to(writer);
+    }
+    let rendered = self.render_parts_to(Cursor::new(Vec::new()))?.into_inner();
+    Write::write_all(&mut writer, &rendered).map_err(ZipError::Io)?;
+    Ok(writer)
+  }
+
+  /// Renders the template, writing parts as they are transformed.
+  fn render_parts_to<W: Write + Seek>(&mut self, writer: W) -> Result<W, CantRenderError> {
+    if let Some(name) = self.properties.non_finite_custom() {
+      return Err(CantRenderError::NonFiniteNumberErr(name.to_owned()));
+    }
     let mut result = zip::ZipWriter::new(writer);
 
-    let find_and_replace = self
-      .replacements
-      .clone()
-      .map(|replacements| FindAndReplace { placeholders: self.placeholders.clone(), replacements });
+    let find_and_replace =
+      self.replacements.clone().filter(|_| !self.placeholders.is_empty()).map(|replacements| {
+        FindAndReplace { placeholders: self.placeholders.clone(), replacements }
+      });
 
-    // let _comments = self._extract_comments();
+    let has_sections = self.has_sections();
+    let (open_bracket, close_bracket) = self.brackets();
+
+    let mut report = RenderReport::default();
+    let mut matched_patterns = vec![false; self.placeholders.len()];
+    let mut matched_bookmarks = HashSet::new();
+    let mut matched_controls = HashSet::new();
+    let mut matched_checkboxes = HashSet::new();
+    #[cfg(feature = "serde")]
+    let mut usage = Usage::default();
+
+    let relationships = self.replacements.iter().flat_map(Replacements::relationships);
+    let controls = self.content_controls.iter().flat_map(|(_, fill)| match fill {
+      Fill::Value(value) => vec![value],
+      Fill::Items(items) => items.iter().flatten().map(|(_, value)| value).collect(),
+    });
+    let relationships = relationships
+      .chain(self.bookmarks.iter().flat_map(|(_, value)| &value.relationships))
+      .chain(controls.flat_map(|value| &value.relationships))
+      .cloned();
+    let mut linker = Linker::new(relationships);
+    let file_names = self.file.archive.file_names().map(str::to_owned).collect::<HashSet<_>>();
+    let mut replaced_media = HashMap::<String, &'a [u8]>::new();
+
+    let erased = self.erased_comments()?;
+    if self.strip_comments {
+      let comments = file_names.iter().filter(|it| DocxPartType::from(it).is_comments());
+      comments.for_each(|it| linker.remove_part(it));
+    }
+
+    // `.rels` files, `[Content_Types].xml` and media of images found by alt texts are written
+    // last, as parts met later in the archive may add relationships, content types or media
+    let mut deferred = Vec::new();
+    let media = self.linked_media(&file_names)?;
 
     for idx in 0..self.file.archive.len() {
       let mut f: ZipFile<R> = self.file.archive.by_index(idx)?;
+      let name = f.name().to_owned();
+      let options = f.to_options();
 
-      if let Some(&buffer) = self.inner_files_to_replace.get(f.name()) {
-        // declare a file
-        result.start_file(f.name(), f.to_options())?;
-        // pipe passed bytes
+      if linker.is_removed(&name) {
+        continue;
+      }
+      if let Some(&buffer) = self.inner_files_to_replace.get(name.as_str()) {
+        drop(f);
+        // declare a file, then pipe the bytes
+        result.start_file(name, options)?;
         Write::write_all(&mut result, buffer).map_err(ZipError::Io)?;
-
         continue;
       }
 
-      let part_of_layout: DocxPartType = f.name().into();
-
+      let part_of_layout: DocxPartType = name.as_str().into();
       match part_of_layout {
-        DocxPartType::Comments | DocxPartType::Unknown => {
-          // copy-paste compressed bytes directly to the resulting archive
-          result.raw_copy_file(f)?
+        DocxPartType::Relationships => {
+          deferred.push(idx);
+          continue;
+        }
+        DocxPartType::Unknown => {
+          match name == CONTENT_TYPES || media.contains(&name) {
+            true => deferred.push(idx),
+            // copy-paste compressed bytes directly to the resulting archive
+            false => result.raw_copy_file(f)?,
+          }
+          continue;
+        }
+        DocxPartType::Comments
+        | DocxPartType::CommentsExtended
+        | DocxPartType::CommentsIds
+        | DocxPartType::CommentsExtensible => {
+          if erased.ids.is_empty() {
+            result.raw_copy_file(f)?;
+            continue;
+          }
+
+          let mut buf = Vec::new();
+          Read::read_to_end(&mut f, &mut buf).map_err(ZipError::Io)?;
+          drop(f);
+
+          result.start_file(name, options)?;
+          Write::write_all(&mut result, &erased.remove_from(part_of_layout, &buf)?)
+            .map_err(ZipError::Io)?;
+          continue;
+        }
+        DocxPartType::CoreProperties | DocxPartType::CustomProperties => {
+          let is_core = part_of_layout == DocxPartType::CoreProperties;
+          if !(is_core && self.properties.has_core() || !is_core && self.properties.has_custom()) {
+            result.raw_copy_file(f)?;
+            continue;
+          }
+
+          let mut buf = Vec::new();
+          Read::read_to_end(&mut f, &mut buf).map_err(ZipError::Io)?;
+          drop(f);
+
+          buf = match is_core {
+            true => self.properties.patch_core(Some(&buf))?,
+            false => self.properties.patch_custom(Some(&buf))?,
+          };
+          result.start_file(name, options)?;
+          Write::write_all(&mut result, &buf).map_err(ZipError::Io)?;
+          continue;
         }
-        DocxPartType::Main | DocxPartType::Header | DocxPartType::Footer => {
-          if let Some(ref find_and_replace) = find_and_replace {
-            // declare a file
-            result.start_file(f.name(), f.to_options())?;
-
-            // read the file into a buffer, transform it, write to the resulting archive
-            let mut buf = String::new();
-            Read::read_to_string(&mut f, &mut buf).map_err(ZipError::Io)?;
-
-            find_and_replace
-              .transform_stream(buf.as_bytes(), &mut result)
-              .map_err(CantRenderError::from)?;
-          } else {
+        DocxPartType::Main
+        | DocxPartType::Header
+        | DocxPartType::Footer
+        | DocxPartType::Footnotes
+        | DocxPartType::Endnotes
+        | DocxPartType::Glossary => {
+          if find_and_replace.is_none()
+            && !has_sections
+            && self.mode == RenderMode::Silent
+            && self.images_to_replace.is_empty()
+            && erased.ids.is_empty()
+            && !self.strip_comments
+            && self.bookmarks.is_empty()
+            && self.content_controls.is_empty()
+            && self.checkboxes.is_empty()
+          {
             result.raw_copy_file(f)?;
+            continue;
+          }
+        }
+      }
+
+      // read the file into a buffer, transform it
+      let mut buf = Vec::new();
+      Read::read_to_end(&mut f, &mut buf).map_err(ZipError::Io)?;
+      drop(f);
+
+      if !erased.ids.is_empty() {
+        let erase_commented = EraseCommented { ids: &erased.ids };
+        buf = erase_commented.transform_stream(&buf, Vec::new()).map_err(CantRenderError::from)?;
+      }
+      if self.strip_comments {
+        buf = StripComments.transform_stream(&buf, Vec::new()).map_err(CantRenderError::from)?;
+      }
+
+      if !self.checkboxes.is_empty() {
+        let checkboxes = Checkboxes { open_bracket: &open_bracket, close_bracket: &close_bracket };
+        let lookup = |name: &str| {
+          let checkbox = self.checkboxes.iter().find(|(key, _)| key.eq_ignore_ascii_case(name));
+          if let Some((key, checked)) = checkbox {
+            matched_checkboxes.insert(key.clone());
+            return Some(*checked);
+          }
+          None
+        };
+        buf =
+          checkboxes.transform_stream(&buf, Vec::new(), lookup).map_err(CantRenderError::from)?;
+      }
+
+      #[cfg(feature = "serde")]
+      if let Some(ref sections) = self.sections {
+        buf =
+          sections.transform_stream(&buf, Vec::new(), &mut usage).map_err(CantRenderError::from)?;
+      }
+
+      if let Some(ref find_and_replace) = find_and_replace {
+        if self.mode != RenderMode::Silent {
+          match_patterns(&self.placeholders, &buf, &mut matched_patterns)?;
+        }
+        #[cfg(feature = "serde")]
+        let resolved = find_and_replace.with_paths_met(&buf, &self.filters)?;
+        #[cfg(feature = "serde")]
+        let find_and_replace = resolved.as_ref().unwrap_or(find_and_replace);
+        buf = find_and_replace.transform_stream(&buf, Vec::new()).map_err(CantRenderError::from)?;
+      }
+
+      if let Some(fields) = self.fields {
+        let lookup = |kind, name: &str| {
+          if kind == FieldKind::DocProperty {
+            if let Some(value) = self.properties.custom(name) {
+              return Some(Value::from_text(&value.text()));
+            }
+          }
+          #[cfg(feature = "serde")]
+          if let Some(ref sections) = self.sections {
+            return sections.value(name, &mut usage);
+          }
+          let find_and_replace = find_and_replace.as_ref()?;
+          let pattern = format!("{open_bracket}{name}{close_bracket}");
+          let idx = self.placeholders.patterns.iter().position(|it| *it == pattern)?;
+          matched_patterns[idx] = true;
+          Some(find_and_replace.replacements[idx].clone())
+        };
+        buf = fields
+          .transform_stream(&buf, Vec::new(), lookup, &mut report.unresolved_placeholders)
+          .map_err(CantRenderError::from)?;
+      }
+
+      if !self.bookmarks.is_empty() {
+        let bookmarks = Bookmarks { values: &self.bookmarks, keep: self.keep_bookmarks };
+        buf = bookmarks
+          .transform_stream(&buf, Vec::new(), &mut matched_bookmarks)
+          .map_err(CantRenderError::from)?;
+      }
+
+      if !self.content_controls.is_empty() {
+        let controls = ContentControls { values: &self.content_controls };
+        buf = controls
+          .transform_stream(&buf, Vec::new(), &mut matched_controls)
+          .map_err(CantRenderError::from)?;
+      }
+
+      if self.mode != RenderMode::Silent {
+        report.scan_unresolved(&buf, &open_bracket, &close_bracket)?;
+      }
+
+      let relationships = match linker.is_empty() && self.images_to_replace.is_empty() {
+        true => HashMap::new(),
+        false => self.relationships(&Linker::rels_path(&name))?,
+      };
+      if !linker.is_empty() {
+        buf = linker.link(&name, buf, &relationships, &file_names);
+      }
+      if !self.images_to_replace.is_empty() {
+        buf = self.replace_images(&name, buf, &relationships, &mut replaced_media, &mut linker)?;
+      }
+
+      result.start_file(name, options)?;
+      Write::write_all(&mut result, &buf).map_err(ZipError::Io)?;
+    }
+
+    // parts of properties missing in the template are listed in the package's relationships
+    let mut new_parts = Vec::new();
+    if self.properties.has_core() && !file_names.contains(properties::CORE_PATH) {
+      new_parts.push((properties::CORE_PATH, self.properties.patch_core(None)?));
+    }
+    if self.properties.has_custom() && !file_names.contains(properties::CUSTOM_PATH) {
+      new_parts.push((properties::CUSTOM_PATH, self.properties.patch_custom(None)?));
+    }
+    for &(path, _) in &new_parts {
+      let (kind, content_type) = match path {
+        properties::CORE_PATH => (properties::CORE_TYPE, properties::CORE_CONTENT_TYPE),
+        _ => (properties::CUSTOM_TYPE, properties::CUSTOM_CONTENT_TYPE),
+      };
+      let relationships = self.relationships(PACKAGE_RELS)?;
+      linker.add_part(PACKAGE_RELS, kind, path, &relationships);
+      linker.override_content_type(path, content_type);
+    }
+
+    for idx in deferred {
+      let mut f: ZipFile<R> = self.file.archive.by_index(idx)?;
+      let name = f.name().to_owned();
+      let options = f.to_options();
+
+      let has_link_targets = self.replace_in_link_targets
+        && (find_and_replace.is_some() || has_sections)
+        && DocxPartType::from(&name) == DocxPartType::Relationships;
+      let patched = if let Some(&buffer) = replaced_media.get(&name) {
+        Cow::Borrowed(buffer)
+      } else if has_link_targets
+        || linker.touches(&name)
+        || name == CONTENT_TYPES && linker.touches_content_types()
+      {
+        let mut buf = Vec::new();
+        Read::read_to_end(&mut f, &mut buf).map_err(ZipError::Io)?;
+
+        #[cfg(feature = "serde")]
+        if let Some(sections) = self.sections.as_ref().filter(|_| has_link_targets) {
+          buf =
+            sections.transform_relationships(&buf, &mut usage).map_err(CantRenderError::from)?;
+        }
+        if let Some(find_and_replace) = find_and_replace.as_ref().filter(|_| has_link_targets) {
+          if self.mode != RenderMode::Silent {
+            for target in relationship_targets(&buf).values() {
+              match_patterns_in_text(&self.placeholders, target.as_bytes(), &mut matched_patterns);
+            }
           }
+          buf = find_and_replace.transform_relationships(&buf).map_err(CantRenderError::from)?;
         }
+        match name.as_str() {
+          CONTENT_TYPES => Cow::Owned(linker.patch_content_types(&buf)),
+          rels_path if linker.touches(rels_path) => {
+            Cow::Owned(linker.patch_rels(rels_path, Some(&buf)))
+          }
+          _ => Cow::Owned(buf),
+        }
+      } else {
+        // copy-paste compressed bytes directly to the resulting archive
+        result.raw_copy_file(f)?;
+        continue;
+      };
+
+      // declare a file, then pipe the bytes
+      result.start_file(name, options)?;
+      Write::write_all(&mut result, &patched).map_err(ZipError::Io)?;
+    }
+
+    if find_and_replace.is_some() {
+      let unmatched = matched_patterns.iter().zip(self.placeholders.patterns.iter());
+      let unmatched = unmatched.filter(|(&is_matched, _)| !is_matched);
+      let keys = unmatched.map(|(_, pattern)| {
+        let key = pattern.strip_prefix(open_bracket.as_str());
+        let key = key.and_then(|it| it.strip_suffix(close_bracket.as_str()));
+        key.unwrap_or(pattern).to_owned()
+      });
+      report.unused_keys.extend(keys);
+    }
+    let unmatched = self.bookmarks.iter().map(|(name, _)| name);
+    report.unused_keys.extend(unmatched.filter(|it| !matched_bookmarks.contains(*it)).cloned());
+    let unmatched = self.content_controls.iter().map(|(name, _)| name);
+    report.unused_keys.extend(unmatched.filter(|it| !matched_controls.contains(*it)).cloned());
+    let unmatched = self.checkboxes.iter().map(|(name, _)| name);
+    report.unused_keys.extend(unmatched.filter(|it| !matched_checkboxes.contains(*it)).cloned());
+    #[cfg(feature = "serde")]
+    if let Some(ref sections) = self.sections {
+      report.unused_keys.extend(sections.unused_keys(&usage));
+    }
+
+    match self.mode {
+      RenderMode::Silent => {}
+      RenderMode::Lenient => self.warnings = report,
+      RenderMode::Strict if !report.is_empty() => {
+        return Err(CantRenderError::StrictRenderErr(report));
       }
+      RenderMode::Strict => {}
+    }
+
+    for rels_path in linker.new_rels_files(&file_names) {
+      result.start_file(rels_path, SimpleFileOptions::default())?;
+      Write::write_all(&mut result, &linker.patch_rels(rels_path, None)).map_err(ZipError::Io)?;
+    }
+    for (path, bytes) in linker.media_files() {
+      result.start_file(path, SimpleFileOptions::default())?;
+      Write::write_all(&mut result, bytes).map_err(ZipError::Io)?;
+    }
+    for (path, bytes) in new_parts {
+      result.start_file(path, SimpleFileOptions::default())?;
+      Write::write_all(&mut result, &bytes).map_err(ZipError::Io)?;
     }
 
     Ok(result.finish()?)
   }
 
-  #[cfg(feature = "docx-rust")]
-  fn _extract_comments(&mut self) -> HashMap<String, isize> {
-    let mut part = match self.file.archive.by_name(DocxPartType::comments()) {
-      Ok(part) => part,
-      Err(_) => return Default::default(),
+  /// Targets listed in the `.rels` file by relationship ids, if the file exists.
+  fn relationships(&mut self, rels_path: &str) -> Result<HashMap<String, String>, CantRenderError> {
+    let buf = self.read_part(rels_path)?;
+    Ok(buf.map(|buf| relationship_targets(&buf)).unwrap_or_default())
+  }
+
+  /// Images of layout parts, which ones replaced by alt texts may point at.
+  fn linked_media(
+    &mut self,
+    file_names: &HashSet<String>,
+  ) -> Result<HashSet<String>, CantRenderError> {
+    let mut media = HashSet::new();
+    if !self.images_to_replace.iter().any(|it| matches!(it.image, ImageRef::AltText(_))) {
+      return Ok(media);
+    }
+    for part in file_names.iter().filter(|it| DocxPartType::from(it).is_layout()) {
+      let Some(xml) = self.read_part(&Linker::rels_path(part))? else { continue };
+      media.extend(image_targets(&xml).into_iter().map(|it| resolve_target(part, &it)));
+    }
+    Ok(media)
+  }
+
+  /// Bytes of the archive's file, if it exists.
+  fn read_part(&mut self, path: &str) -> Result<Option<Vec<u8>>, CantRenderError> {
+    let mut file = match self.file.archive.by_name(path) {
+      Ok(file) => file,
+      Err(ZipError::FileNotFound) => return Ok(None),
+      Err(err) => return Err(err.into()),
     };
+    let mut buf = Vec::new();
+    Read::read_to_end(&mut file, &mut buf).map_err(ZipError::Io)?;
+    Ok(Some(buf))
+  }
+
+  /// Comments whose text is one of [DocxTemplate::remove_commented_block] placeholders,
+  /// along with their replies.
+  fn erased_comments(&mut self) -> Result<Selection, CantRenderError> {
+    let mut erased = Selection::default();
+    if self.comments_to_delete.is_empty() {
+      return Ok(erased);
+    }
+    let Some(xml) = self.read_part(DocxPartType::comments())? else { return Ok(erased) };
+
+    let comments = comments::read(&xml)?;
+    for comment in &comments {
+      if self.comments_to_delete.contains(comment.text.trim()) {
+        erased.add(comment);
+      }
+    }
+    if let Some(xml) = self.read_part(DocxPartType::comments_extended())? {
+      erased.add_replies(&comments, &xml)?;
+    }
+    if let Some(xml) = self.read_part(DocxPartType::comments_ids())? {
+      let (tag, key, durable_id) = (b"w16cid:commentId", b"w16cid:paraId", b"w16cid:durableId");
+      erased.durable_ids = comments::lookup(&xml, tag, key, &erased.paragraph_ids, durable_id)?;
+    }
+    Ok(erased)
+  }
+
+  /// Finds images by their alt texts or paths, marks their media files to be replaced,
+  /// and resizes the frames if required.
+  fn replace_images(
+    &self,
+    part: &str,
+    xml: Vec<u8>,
+    relationships: &HashMap<String, String>,
+    replaced_media: &mut HashMap<String, &'a [u8]>,
+    linker: &mut Linker,
+  ) -> Result<Vec<u8>, CantRenderError> {
+    let mut extents = Vec::new();
+
+    for drawing in drawings::read(&xml)? {
+      let Some(target) = drawing.embed.as_ref().and_then(|id| relationships.get(id)) else {
+        continue;
+      };
+      let path = resolve_target(part, target);
+      let Some(image) = self.images_to_replace.iter().find(|it| match it.image {
+        ImageRef::AltText(text) => drawing.is_labeled(text),
+        ImageRef::Path(inner_path) => inner_path == path,
+      }) else {
+        continue;
+      };
 
-    let mut buf = String::with_capacity(part.size() as usize);
-    Read::read_to_string(&mut part, &mut buf).ok();
+      let extension = path.rsplit_once('.').map(|(_, extension)| extension).unwrap_or_default();
+      let format = ImageFormat::detect(image.bytes);
+      if let Some(format) = format.filter(|&it| ImageFormat::from_extension(extension) != Some(it))
+      {
+        linker.override_content_type(&path, format.content_type());
+      }
+      if let ImageRef::AltText(_) = image.image {
+        replaced_media.insert(path, image.bytes);
+      }
+
+      if let Some((size, header)) = drawing.size().zip(ImageHeader::read(image.bytes)) {
+        let (cx, cy) = image.fit.apply(size, &header);
+        if (cx, cy) != size {
+          extents.extend(drawing.extents.into_iter().map(|it| Extent { cx, cy, ..it }));
+        }
+      }
+    }
+
+    Ok(match extents.is_empty() {
+      true => xml,
+      false => drawings::resize(&xml, extents),
+    })
+  }
+
+  /// Brackets of `{tokens}` which are reported if left unfilled.
+  fn brackets(&self) -> (String, String) {
+    #[cfg(feature = "serde")]
+    if let Some(ref sections) = self.sections {
+      return (sections.open_bracket.clone(), sections.close_bracket.clone());
+    }
+    match self.placeholders.brackets {
+      Some(ref brackets) => brackets.as_ref().clone(),
+      None => ("{".to_owned(), "}".to_owned()),
+    }
+  }
+
+  fn has_sections(&self) -> bool {
+    #[cfg(feature = "serde")]
+    return self.sections.is_some();
+    #[cfg(not(feature = "serde"))]
+    return false;
+  }
+}
+
+/// Marks placeholders met in paragraphs of the part.
+fn match_patterns(
+  placeholders: &Placeholders,
+  xml: &[u8],
+  matched: &mut [bool],
+) -> Result<(), CantRenderError> {
+  for paragraph in Layout::read(xml)?.paragraphs {
+    match_patterns_in_text(placeholders, &paragraph.text(xml), matched);
+  }
+  Ok(())
+}
+
+/// Marks placeholders met in the text, overlapping ones included.
+fn match_patterns_in_text(placeholders: &Placeholders, text: &[u8], matched: &mut [bool]) {
+  let input = Input::new(text);
+  let mut state = OverlappingState::start();
+  while placeholders.automaton.try_find_overlapping(&input, &mut state).is_ok() {
+    let Some(found) = state.get_match() else { break };
+    matched[found.pattern().as_usize()] = true;
+  }
+}
 
-    use hard_xml::{XmlRead, XmlWrite};
-    let Ok(def) = docx_rust::document::Comments::from_str(&buf) else { panic!() };
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-    def
-      .comments
-      .into_iter()
-      .filter_map(|com| com.content.to_string().ok().zip(com.id))
-      .collect::<HashMap<_, _>>()
+  #[test]
+  fn matches_overlapping_placeholders() {
+    let placeholders = Placeholders::from_iter(["{a}", "{a}{b}", "{b}", "{c}"]);
+    let mut matched = vec![false; placeholders.len()];
+    match_patterns_in_text(&placeholders, b"x {a}{b} y", &mut matched);
+    assert_eq!(matched, [true, true, true, false]);
   }
 }
@@ -15,7 +15,7 @@ impl<'a, 'b> From<&'a mut Formatter<'b>> for IntoIoAdapter<'a, 'b> {
 impl io::Write for IntoIoAdapter<'_, '_> {
   fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
     let str = from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-    self.formatter.write_str(str).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    self.formatter.write_str(str).map_err(io::Error::other)?;
     Ok(buf.len())
   }
 
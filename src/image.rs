@@ -0,0 +1,380 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// English Metric Units per pixel, at 96 DPI.
+pub const EMU_PER_PIXEL: u64 = 9525;
+
+const EMU_PER_INCH: f64 = 914400.0;
+
+/// An id for a new `<wp:docPr>`, which has to be unique within the document.
+/// Word numbers drawings from 1, so new ones start far above.
+pub fn next_drawing_id() -> u64 {
+  static COUNTER: AtomicU64 = AtomicU64::new(0x10000);
+  COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
+/// How a replaced image is fitted into the frame of the old one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFit {
+  /// The frame is left as is, so the image is stretched if proportions differ.
+  #[default]
+  KeepBox,
+  /// The frame's width is kept, the height follows the image's aspect ratio.
+  KeepWidth,
+  /// The frame's height is kept, the width follows the image's aspect ratio.
+  KeepHeight,
+  /// The image is scaled down to fit into the frame, keeping its aspect ratio.
+  Contain,
+}
+
+impl ImageFit {
+  /// Size of the new frame in EMUs, given the old one and the image's header.
+  pub(crate) fn apply(self, (cx, cy): (u64, u64), image: &ImageHeader) -> (u64, u64) {
+    let (width, height) = image.size();
+    let (width, height) = (width.max(1), height.max(1));
+    let keep_width = (cx, cx * height / width);
+    let keep_height = (cy * width / height, cy);
+    match self {
+      Self::KeepBox => (cx, cy),
+      Self::KeepWidth => keep_width,
+      Self::KeepHeight => keep_height,
+      // the image is wider than the frame
+      Self::Contain if width * cy > height * cx => keep_width,
+      Self::Contain => keep_height,
+    }
+  }
+}
+
+/// Formats of images Word is able to display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+  Png,
+  Jpeg,
+  Gif,
+  Bmp,
+  Tiff,
+}
+
+impl ImageFormat {
+  /// Recognizes the format by the file's signature.
+  pub fn detect(bytes: &[u8]) -> Option<Self> {
+    match bytes {
+      [0x89, b'P', b'N', b'G', ..] => Some(Self::Png),
+      [0xFF, 0xD8, 0xFF, ..] => Some(Self::Jpeg),
+      [b'G', b'I', b'F', b'8', ..] => Some(Self::Gif),
+      [b'B', b'M', ..] if is_bitmap(bytes) => Some(Self::Bmp),
+      [b'I', b'I', 42, 0, ..] | [b'M', b'M', 0, 42, ..] => Some(Self::Tiff),
+      _ => None,
+    }
+  }
+
+  /// Guesses the format by the file's extension.
+  pub fn from_extension(extension: &str) -> Option<Self> {
+    match extension.to_ascii_lowercase().as_str() {
+      "png" => Some(Self::Png),
+      "jpg" | "jpeg" | "jpe" => Some(Self::Jpeg),
+      "gif" => Some(Self::Gif),
+      "bmp" => Some(Self::Bmp),
+      "tif" | "tiff" => Some(Self::Tiff),
+      _ => None,
+    }
+  }
+
+  pub fn extension(self) -> &'static str {
+    match self {
+      Self::Png => "png",
+      Self::Jpeg => "jpeg",
+      Self::Gif => "gif",
+      Self::Bmp => "bmp",
+      Self::Tiff => "tiff",
+    }
+  }
+
+  pub fn content_type(self) -> &'static str {
+    match self {
+      Self::Png => "image/png",
+      Self::Jpeg => "image/jpeg",
+      Self::Gif => "image/gif",
+      Self::Bmp => "image/bmp",
+      Self::Tiff => "image/tiff",
+    }
+  }
+}
+
+/// Size and resolution of an image, read from its header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageHeader {
+  /// Width in pixels.
+  pub width: u32,
+  /// Height in pixels.
+  pub height: u32,
+  /// Horizontal and vertical resolution, 96 DPI unless stated by the image.
+  pub dpi: (f64, f64),
+}
+
+impl ImageHeader {
+  /// Reads the header of a PNG, JPEG, GIF, BMP or TIFF image.
+  pub fn read(bytes: &[u8]) -> Option<Self> {
+    let u16_le = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u16_be = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u32_le = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let u32_be = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let i32_le = |at: usize| Some(i32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+
+    let mut header = match ImageFormat::detect(bytes)? {
+      ImageFormat::Png => {
+        // IHDR chunk goes first, pHYs one comes before the image data
+        let mut header = Self::new(u32_be(16)?, u32_be(20)?);
+        let mut at = 8;
+        while let (Some(length), Some(kind)) = (u32_be(at), bytes.get(at + 4..at + 8)) {
+          match kind {
+            b"pHYs" if bytes.get(at + 16) == Some(&1) => {
+              let (x, y) = (u32_be(at + 8)?, u32_be(at + 12)?);
+              header.dpi = (f64::from(x) * 0.0254, f64::from(y) * 0.0254);
+            }
+            b"IDAT" | b"IEND" => break,
+            _ => {}
+          }
+          at += 12 + length as usize;
+        }
+        header
+      }
+      ImageFormat::Gif => Self::new(u32::from(u16_le(6)?), u32::from(u16_le(8)?)),
+      // OS/2 bitmaps keep the size in shorts, and have no resolution
+      ImageFormat::Bmp if u32_le(14)? == 12 => {
+        Self::new(u32::from(u16_le(18)?), u32::from(u16_le(20)?))
+      }
+      ImageFormat::Bmp => {
+        // height is negative for top-down bitmaps
+        let mut header = Self::new(i32_le(18)?.unsigned_abs(), i32_le(22)?.unsigned_abs());
+        if u32_le(14)? >= 40 {
+          let (x, y) = (i32_le(38)?, i32_le(42)?);
+          header.dpi = (f64::from(x) * 0.0254, f64::from(y) * 0.0254);
+        }
+        header
+      }
+      ImageFormat::Jpeg => {
+        // segments are walked until a start of frame one, JFIF one may state the density
+        let mut dpi = None;
+        let mut at = 2;
+        loop {
+          while *bytes.get(at)? == 0xFF && *bytes.get(at + 1)? == 0xFF {
+            at += 1;
+          }
+          let marker = *bytes.get(at + 1)?;
+          if marker == 0xE0 && bytes.get(at + 4..at + 9) == Some(b"JFIF\0") {
+            let (x, y) = (f64::from(u16_be(at + 12)?), f64::from(u16_be(at + 14)?));
+            dpi = match bytes.get(at + 11) {
+              Some(1) => Some((x, y)),
+              Some(2) => Some((x * 2.54, y * 2.54)),
+              _ => None,
+            };
+          }
+          if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let mut header = Self::new(u32::from(u16_be(at + 7)?), u32::from(u16_be(at + 5)?));
+            header.dpi = dpi.unwrap_or(header.dpi);
+            break header;
+          }
+          at += 2 + usize::from(u16_be(at + 2)?);
+        }
+      }
+      ImageFormat::Tiff => {
+        // tags of the first image file directory, shorts and longs are kept in the entries,
+        // resolutions are rationals found by offsets
+        let big_endian = bytes[0] == b'M';
+        let u16_at = |at| if big_endian { u16_be(at) } else { u16_le(at) };
+        let u32_at = |at| if big_endian { u32_be(at) } else { u32_le(at) };
+        let number = |entry: usize| match u16_at(entry + 2)? {
+          3 => Some(u32::from(u16_at(entry + 8)?)),
+          4 => u32_at(entry + 8),
+          _ => None,
+        };
+        let rational = |entry: usize| {
+          let at = u32_at(entry + 8)? as usize;
+          Some(f64::from(u32_at(at)?) / f64::from(u32_at(at + 4)?.max(1)))
+        };
+
+        let directory = u32_at(4)? as usize;
+        let (mut width, mut height, mut dpi, mut unit) = (None, None, (0.0, 0.0), 2);
+        for idx in 0..usize::from(u16_at(directory)?) {
+          let entry = directory + 2 + 12 * idx;
+          match u16_at(entry)? {
+            256 => width = number(entry),
+            257 => height = number(entry),
+            282 => dpi.0 = rational(entry).unwrap_or_default(),
+            283 => dpi.1 = rational(entry).unwrap_or_default(),
+            296 => unit = number(entry).unwrap_or(unit),
+            _ => {}
+          }
+        }
+        let mut header = Self::new(width?, height?);
+        // resolution is per inch, per centimeter, or not stated
+        let scale = match unit {
+          2 => 1.0,
+          3 => 2.54,
+          _ => 0.0,
+        };
+        header.dpi = (dpi.0 * scale, dpi.1 * scale);
+        header
+      }
+    };
+
+    if header.dpi.0 < 1.0 || header.dpi.1 < 1.0 {
+      header.dpi = (96.0, 96.0);
+    }
+    Some(header)
+  }
+
+  fn new(width: u32, height: u32) -> Self {
+    Self { width, height, dpi: (96.0, 96.0) }
+  }
+
+  /// Physical width and height in EMUs.
+  pub fn size(&self) -> (u64, u64) {
+    let emu = |pixels: u32, dpi: f64| (f64::from(pixels) / dpi * EMU_PER_INCH).round() as u64;
+    (emu(self.width, self.dpi.0), emu(self.height, self.dpi.1))
+  }
+}
+
+/// Checks the file header of a bitmap: the stated file size, the reserved fields, and the offset
+/// of pixels, which follow the info header.
+fn is_bitmap(bytes: &[u8]) -> bool {
+  let u32_le = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+  let (Some(size), Some(reserved), Some(offset), Some(info)) =
+    (u32_le(2), u32_le(6), u32_le(10), u32_le(14))
+  else {
+    return false;
+  };
+  let info_sizes = [12, 40, 52, 56, 64, 108, 124];
+  reserved == 0
+    && info_sizes.contains(&info)
+    && 14 + info <= offset
+    && offset <= size
+    && size as usize <= bytes.len()
+}
+
+/// An inline picture referencing the image by the relationship id, sized in EMUs.
+pub fn drawing(rel_id: &str, doc_pr_id: u64, cx: u64, cy: u64) -> String {
+  format!(
+    r#"<w:drawing><wp:inline distT="0" distB="0" distL="0" distR="0" xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing"><wp:extent cx="{cx}" cy="{cy}"/><wp:effectExtent l="0" t="0" r="0" b="0"/><wp:docPr id="{doc_pr_id}" name="Picture {doc_pr_id}"/><wp:cNvGraphicFramePr><a:graphicFrameLocks xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" noChangeAspect="1"/></wp:cNvGraphicFramePr><a:graphic xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:pic xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:nvPicPr><pic:cNvPr id="0" name="Picture {doc_pr_id}"/><pic:cNvPicPr/></pic:nvPicPr><pic:blipFill><a:blip r:embed="{rel_id}" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill><pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="{cx}" cy="{cy}"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></pic:spPr></pic:pic></a:graphicData></a:graphic></wp:inline></w:drawing>"#
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_formats() {
+    assert_eq!(ImageFormat::detect(b"\x89PNG\r\n\x1a\n"), Some(ImageFormat::Png));
+    assert_eq!(ImageFormat::detect(b"\xFF\xD8\xFF\xE0"), Some(ImageFormat::Jpeg));
+    assert_eq!(ImageFormat::detect(b"GIF89a"), Some(ImageFormat::Gif));
+    assert_eq!(ImageFormat::detect(b"<svg"), None);
+    assert_eq!(ImageFormat::detect(b"II*\0\x08\0\0\0"), Some(ImageFormat::Tiff));
+  }
+
+  /// A 24-bit bitmap header with no pixels, whose info header has the `info` size.
+  fn bitmap(info: u32) -> Vec<u8> {
+    let offset = 14 + info;
+    let mut bytes = [&b"BM"[..], &offset.to_le_bytes(), &[0; 4], &offset.to_le_bytes()].concat();
+    bytes.extend_from_slice(&info.to_le_bytes());
+    bytes.resize(offset as usize, 0);
+    bytes
+  }
+
+  #[test]
+  fn checks_bitmap_headers() {
+    let mut bmp = bitmap(40);
+    assert_eq!(ImageFormat::detect(&bmp), Some(ImageFormat::Bmp));
+    assert_eq!(ImageFormat::detect(b"BM is not a bitmap, just a text"), None);
+
+    // pixels past the end of the file
+    bmp[10..14].copy_from_slice(&100_u32.to_le_bytes());
+    assert_eq!(ImageFormat::detect(&bmp), None);
+    // the file is shorter than stated
+    let mut bmp = bitmap(40);
+    bmp[2..6].copy_from_slice(&100_u32.to_le_bytes());
+    assert_eq!(ImageFormat::detect(&bmp), None);
+    // pixels overlap the info header
+    let mut bmp = bitmap(40);
+    bmp[10..14].copy_from_slice(&40_u32.to_le_bytes());
+    assert_eq!(ImageFormat::detect(&bmp), None);
+  }
+
+  #[test]
+  fn reads_headers() {
+    let cat = ImageHeader::read(include_bytes!("../examples/image-replacement/cat.jpg")).unwrap();
+    assert_eq!((cat.width, cat.height), (320, 240));
+
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x01\x2c\0\0\0\x64\x08\x02\0\0\0CRC!\
+      \0\0\0\x09pHYs\0\0\x17\x70\0\0\x17\x70\x01CRC!\0\0\0\0IEND";
+    let png = ImageHeader::read(png).unwrap();
+    assert_eq!((png.width, png.height), (300, 100));
+    assert_eq!(png.dpi.0.round(), 152.0);
+    assert_eq!(png.size(), (1800000, 600000));
+
+    let gif = ImageHeader::read(b"GIF89a\x20\x00\x10\x00").unwrap();
+    assert_eq!((gif.width, gif.height, gif.dpi), (32, 16, (96.0, 96.0)));
+
+    let mut bmp = bitmap(40);
+    bmp[18..22].copy_from_slice(&64_i32.to_le_bytes());
+    bmp[22..26].copy_from_slice(&(-48_i32).to_le_bytes());
+    bmp[38..46].copy_from_slice(&[&5906_i32.to_le_bytes()[..], &5906_i32.to_le_bytes()].concat());
+    let bmp = ImageHeader::read(&bmp).unwrap();
+    assert_eq!((bmp.width, bmp.height, bmp.dpi.0.round()), (64, 48, 150.0));
+
+    let mut os2 = bitmap(12);
+    os2[18..22].copy_from_slice(&[64, 0, 48, 0]);
+    let os2 = ImageHeader::read(&os2).unwrap();
+    assert_eq!((os2.width, os2.height, os2.dpi), (64, 48, (96.0, 96.0)));
+  }
+
+  #[test]
+  fn reads_tiff_headers() {
+    // width as a short, height as a long, resolution of 118 pixels per centimeter
+    let entries: [(u16, u16, u32); 5] =
+      [(256, 3, 300), (257, 4, 100), (282, 5, 74), (283, 5, 82), (296, 3, 3)];
+    let tiff = |big_endian: bool| {
+      let u16_bytes = |it: u16| if big_endian { it.to_be_bytes() } else { it.to_le_bytes() };
+      let u32_bytes = |it: u32| if big_endian { it.to_be_bytes() } else { it.to_le_bytes() };
+      let mut bytes = [&b"II*\0"[..], &u32_bytes(8), &u16_bytes(5)].concat();
+      if big_endian {
+        bytes[..4].copy_from_slice(b"MM\0*");
+      }
+      for (tag, kind, value) in entries {
+        bytes.extend([u16_bytes(tag), u16_bytes(kind)].concat());
+        bytes.extend(u32_bytes(1));
+        match kind {
+          3 => bytes.extend([u16_bytes(value as u16), [0, 0]].concat()),
+          _ => bytes.extend(u32_bytes(value)),
+        }
+      }
+      bytes.extend(u32_bytes(0));
+      for _ in 0..2 {
+        bytes.extend([u32_bytes(118), u32_bytes(1)].concat());
+      }
+      bytes
+    };
+
+    for big_endian in [false, true] {
+      let header = ImageHeader::read(&tiff(big_endian)).unwrap();
+      assert_eq!((header.width, header.height), (300, 100));
+      assert_eq!(header.dpi.0.round(), 300.0);
+    }
+    assert_eq!(ImageHeader::read(b"II*\0\x08\0\0\0\0\0"), None);
+  }
+
+  #[test]
+  fn fits_into_the_frame() {
+    let frame = (2000, 1000);
+    let square = ImageHeader::new(100, 100);
+    assert_eq!(ImageFit::KeepBox.apply(frame, &square), (2000, 1000));
+    assert_eq!(ImageFit::KeepWidth.apply(frame, &square), (2000, 2000));
+    assert_eq!(ImageFit::KeepHeight.apply(frame, &square), (1000, 1000));
+    assert_eq!(ImageFit::Contain.apply(frame, &square), (1000, 1000));
+    assert_eq!(ImageFit::Contain.apply(frame, &ImageHeader::new(400, 100)), (2000, 500));
+
+    // pixels twice as tall as wide
+    let stretched = ImageHeader { dpi: (96.0, 48.0), ..square };
+    assert_eq!(ImageFit::KeepWidth.apply(frame, &stretched), (2000, 4000));
+  }
+}
@@ -1,3 +1,5 @@
+//! Replace `{placeholders}` and manage content inside `.docx` files.
+
 #![warn(missing_docs)]
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(rustdoc::private_intra_doc_links)]
@@ -6,27 +8,43 @@
 mod docx_file;
 mod docx_part;
 mod docx_template;
+#[cfg(any(feature = "docx-rs", feature = "docx-rust"))]
 mod fmt_to_io_adapter;
+mod image;
 mod iter_tools;
 #[cfg(any(feature = "docx-rs", feature = "docx-rust"))]
 mod markup_node;
+mod properties;
+mod relationships;
+mod render_report;
 pub(crate) mod transformers;
 mod zip_file_ext;
 
 #[doc(inline)]
-pub use docx_file::DocxFile;
+pub use docx_file::{CantScanError, DocxFile, FoundPlaceholder};
 #[doc(inline)]
 pub use docx_template::{CantRenderError, DocxTemplate};
 #[doc(inline)]
+pub use image::ImageFit;
+#[doc(inline)]
 #[cfg(feature = "docx-rs")]
 pub use markup_node::docx_rs::DocxRsMarkupNode;
 #[doc(inline)]
 #[cfg(feature = "docx-rust")]
 pub use markup_node::docx_rust::DocxRustMarkupNode;
+#[doc(inline)]
+pub use properties::{CoreProperty, PropertyValue};
+#[doc(inline)]
+pub use render_report::{RenderMode, RenderReport};
+#[doc(inline)]
+#[cfg(feature = "serde")]
+pub use transformers::find_and_replace::Filters;
 #[doc(hidden)]
 pub use transformers::find_and_replace::FindAndReplace;
 #[doc(inline)]
-pub use transformers::find_and_replace::{Placeholders, Replacements, Value};
+pub use transformers::find_and_replace::{
+  ParagraphStyles, Placeholders, Replacements, RichText, Span, Value,
+};
 
 #[cfg(feature = "serde")]
 #[allow(missing_docs)]
@@ -0,0 +1,370 @@
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+
+pub const CORE_PATH: &str = "docProps/core.xml";
+pub const CUSTOM_PATH: &str = "docProps/custom.xml";
+
+pub const CORE_TYPE: &str =
+  "http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties";
+pub const CUSTOM_TYPE: &str =
+  "http://schemas.openxmlformats.org/officeDocument/2006/relationships/custom-properties";
+
+pub const CORE_CONTENT_TYPE: &str = "application/vnd.openxmlformats-package.core-properties+xml";
+pub const CUSTOM_CONTENT_TYPE: &str =
+  "application/vnd.openxmlformats-officedocument.custom-properties+xml";
+
+/// Format id Word gives to all the custom properties.
+const CUSTOM_FMTID: &str = "{D5CDD505-2E9C-101B-9397-08002B2CF9AE}";
+
+const NAMESPACES: &[(&str, &str)] = &[
+  ("cp", "http://schemas.openxmlformats.org/package/2006/metadata/core-properties"),
+  ("dc", "http://purl.org/dc/elements/1.1/"),
+  ("dcterms", "http://purl.org/dc/terms/"),
+  ("dcmitype", "http://purl.org/dc/dcmitype/"),
+  ("xsi", "http://www.w3.org/2001/XMLSchema-instance"),
+  ("vt", "http://schemas.openxmlformats.org/officeDocument/2006/docPropsVTypes"),
+];
+
+/// A field of `docProps/core.xml`, shown by Word in File → Info → Properties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CoreProperty {
+  #[allow(missing_docs)]
+  Title,
+  #[allow(missing_docs)]
+  Subject,
+  /// The author.
+  Creator,
+  /// Tags, separated by semicolons or commas.
+  Keywords,
+  /// Comments.
+  Description,
+  #[allow(missing_docs)]
+  LastModifiedBy,
+  /// Date and time in W3C format, like `2024-05-31T12:00:00Z`.
+  Created,
+  /// Date and time in W3C format, like `2024-05-31T12:00:00Z`.
+  Modified,
+}
+
+impl CoreProperty {
+  fn tag(self) -> &'static str {
+    match self {
+      Self::Title => "dc:title",
+      Self::Subject => "dc:subject",
+      Self::Creator => "dc:creator",
+      Self::Keywords => "cp:keywords",
+      Self::Description => "dc:description",
+      Self::LastModifiedBy => "cp:lastModifiedBy",
+      Self::Created => "dcterms:created",
+      Self::Modified => "dcterms:modified",
+    }
+  }
+
+  fn markup(self, value: &str) -> String {
+    let (tag, value) = (self.tag(), escape(value));
+    match self {
+      Self::Created | Self::Modified => {
+        format!(r#"<{tag} xsi:type="dcterms:W3CDTF">{value}</{tag}>"#)
+      }
+      _ => format!("<{tag}>{value}</{tag}>"),
+    }
+  }
+}
+
+/// A value of a custom property, listed in File → Properties → Advanced Properties → Custom.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+  /// `vt:lpwstr`.
+  Text(String),
+  /// `vt:i4`.
+  Integer(i32),
+  /// `vt:r8`, NaN and infinite numbers fail the render.
+  Number(f64),
+  /// Yes or no, `vt:bool`.
+  Bool(bool),
+  /// Date and time in W3C format, like `2024-05-31T12:00:00Z`.
+  Date(String),
+}
+
+impl PropertyValue {
+  /// The value as Word shows it in `DOCPROPERTY` fields.
+  pub fn text(&self) -> String {
+    match self {
+      Self::Text(text) | Self::Date(text) => text.clone(),
+      Self::Integer(number) => number.to_string(),
+      Self::Number(number) => number.to_string(),
+      Self::Bool(true) => "Y".to_owned(),
+      Self::Bool(false) => "N".to_owned(),
+    }
+  }
+
+  fn markup(&self) -> String {
+    match self {
+      Self::Text(text) => format!("<vt:lpwstr>{}</vt:lpwstr>", escape(text.as_str())),
+      Self::Integer(number) => format!("<vt:i4>{number}</vt:i4>"),
+      Self::Number(number) => format!("<vt:r8>{number}</vt:r8>"),
+      Self::Bool(bool) => format!("<vt:bool>{bool}</vt:bool>"),
+      Self::Date(date) => format!("<vt:filetime>{}</vt:filetime>", escape(date.as_str())),
+    }
+  }
+}
+
+impl From<&str> for PropertyValue {
+  fn from(text: &str) -> Self {
+    Self::Text(text.to_owned())
+  }
+}
+
+impl From<String> for PropertyValue {
+  fn from(text: String) -> Self {
+    Self::Text(text)
+  }
+}
+
+impl From<i32> for PropertyValue {
+  fn from(number: i32) -> Self {
+    Self::Integer(number)
+  }
+}
+
+impl From<f64> for PropertyValue {
+  fn from(number: f64) -> Self {
+    Self::Number(number)
+  }
+}
+
+impl From<bool> for PropertyValue {
+  fn from(bool: bool) -> Self {
+    Self::Bool(bool)
+  }
+}
+
+/// Core and custom properties to be set while rendering.
+#[derive(Debug, Default, Clone)]
+pub struct DocumentProperties {
+  core: Vec<(CoreProperty, String)>,
+  custom: Vec<(String, PropertyValue)>,
+}
+
+impl DocumentProperties {
+  pub fn set_core(&mut self, property: CoreProperty, value: String) {
+    match self.core.iter_mut().find(|(it, _)| *it == property) {
+      Some((_, old)) => *old = value,
+      None => self.core.push((property, value)),
+    }
+  }
+
+  pub fn set_custom(&mut self, name: String, value: PropertyValue) {
+    match self.custom.iter_mut().find(|(it, _)| it.eq_ignore_ascii_case(&name)) {
+      Some((_, old)) => *old = value,
+      None => self.custom.push((name, value)),
+    }
+  }
+
+  /// Value of the custom property, names are case-insensitive.
+  pub fn custom(&self, name: &str) -> Option<&PropertyValue> {
+    let (_, value) = self.custom.iter().find(|(it, _)| it.eq_ignore_ascii_case(name))?;
+    Some(value)
+  }
+
+  pub fn has_core(&self) -> bool {
+    !self.core.is_empty()
+  }
+
+  pub fn has_custom(&self) -> bool {
+    !self.custom.is_empty()
+  }
+
+  /// Name of a custom property holding NaN or an infinite number, if any.
+  pub fn non_finite_custom(&self) -> Option<&str> {
+    let mut custom = self.custom.iter();
+    let (name, _) =
+      custom.find(|(_, value)| matches!(value, PropertyValue::Number(it) if !it.is_finite()))?;
+    Some(name)
+  }
+
+  /// Sets the fields of `docProps/core.xml`, or creates the part if `xml` is `None`.
+  pub fn patch_core(&self, xml: Option<&[u8]>) -> Result<Vec<u8>, quick_xml::Error> {
+    let xml = xml.unwrap_or(
+      b"<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+      <cp:coreProperties xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\">\
+      </cp:coreProperties>",
+    );
+    let prefixes = self.core.iter().map(|(property, _)| property.tag().split(':').next());
+    let mut prefixes = prefixes.flatten().collect::<Vec<_>>();
+    if self.core.iter().any(|(it, _)| matches!(it, CoreProperty::Created | CoreProperty::Modified))
+    {
+      prefixes.push("xsi");
+    }
+
+    let (mut output, end, met) = patch(xml, &prefixes, |tag, _| {
+      let idx = self.core.iter().position(|(it, _)| it.tag().as_bytes() == tag)?;
+      let (property, value) = &self.core[idx];
+      Some((idx, property.markup(value)))
+    })?;
+
+    let missing = self.core.iter().enumerate().filter(|(idx, _)| !met.contains(idx));
+    let missing = missing.map(|(_, (property, value))| property.markup(value));
+    output.splice(end..end, missing.collect::<String>().into_bytes());
+    Ok(output)
+  }
+
+  /// Adds or updates `docProps/custom.xml` properties, or creates the part if `xml` is `None`.
+  pub fn patch_custom(&self, xml: Option<&[u8]>) -> Result<Vec<u8>, quick_xml::Error> {
+    let xml = xml.unwrap_or(
+      b"<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+      <Properties xmlns=\"http://schemas.openxmlformats.org/officeDocument/2006/custom-properties\">\
+      </Properties>",
+    );
+    let mut max_pid = 1;
+
+    let (mut output, end, met) = patch(xml, &["vt"], |name, tag| {
+      if name != b"property" {
+        return None;
+      }
+      let attribute = |name| -> Option<String> {
+        tag.try_get_attribute(name).ok()??.unescape_value().ok().map(|it| it.into_owned())
+      };
+      let pid = attribute("pid").and_then(|it| it.parse::<u32>().ok()).unwrap_or_default();
+      max_pid = max_pid.max(pid);
+      let property_name = attribute("name")?;
+      let idx = self.custom.iter().position(|(it, _)| it.eq_ignore_ascii_case(&property_name))?;
+      Some((idx, custom_markup(pid, &property_name, &self.custom[idx].1)))
+    })?;
+
+    // pids of new properties follow the existing ones, Word starts them from 2
+    let missing = self.custom.iter().enumerate().filter(|(idx, _)| !met.contains(idx));
+    let missing =
+      missing.zip(max_pid + 1..).map(|((_, (name, value)), pid)| custom_markup(pid, name, value));
+    output.splice(end..end, missing.collect::<String>().into_bytes());
+    Ok(output)
+  }
+}
+
+fn custom_markup(pid: u32, name: &str, value: &PropertyValue) -> String {
+  format!(
+    r#"<property fmtid="{CUSTOM_FMTID}" pid="{pid}" name="{}">{}</property>"#,
+    escape(name),
+    value.markup()
+  )
+}
+
+/// Pipes the `xml` through, replacing children of the root element for which `replace`
+/// returns an index of the property and its markup. Returns the output, the position
+/// of the root's closing tag in it, and indices of the properties met.
+///
+/// Namespaces of the `prefixes` are declared on the root element, if missing.
+fn patch(
+  xml: &[u8],
+  prefixes: &[&str],
+  mut replace: impl FnMut(&[u8], &BytesStart) -> Option<(usize, String)>,
+) -> Result<(Vec<u8>, usize, Vec<usize>), quick_xml::Error> {
+  let mut reader = quick_xml::Reader::from_reader(xml);
+  let mut output = Vec::with_capacity(xml.len());
+  let (mut reported, mut depth, mut end) = (0, 0, None);
+  let mut met = Vec::new();
+
+  loop {
+    let position = reader.buffer_position() as usize;
+    let event = reader.read_event()?;
+    match event {
+      Event::Start(ref tag) | Event::Empty(ref tag) if depth == 0 => {
+        let start = reader.buffer_position() as usize;
+        let closing = if matches!(event, Event::Empty(_)) { 2 } else { 1 };
+        output.extend_from_slice(&xml[reported..start - closing]);
+        let root = String::from_utf8_lossy(&xml[position..start]);
+        for &prefix in prefixes {
+          let Some((_, namespace)) = NAMESPACES.iter().find(|(it, _)| *it == prefix) else {
+            continue;
+          };
+          if !root.contains(&format!("xmlns:{prefix}=")) {
+            output.extend_from_slice(format!(r#" xmlns:{prefix}="{namespace}""#).as_bytes());
+          }
+        }
+        reported = start - closing;
+        if let Event::Start(_) = event {
+          depth += 1;
+          continue;
+        }
+        // a childless root is reopened, so the properties can be put inside
+        let name = String::from_utf8_lossy(tag.name().as_ref()).into_owned();
+        output.extend_from_slice(b">");
+        end = Some(output.len());
+        output.extend_from_slice(format!("</{name}>").as_bytes());
+        reported = start;
+      }
+      Event::Start(ref tag) | Event::Empty(ref tag) if depth == 1 => {
+        let Some((idx, markup)) = replace(tag.name().as_ref(), tag) else {
+          if let Event::Start(_) = event {
+            depth += 1;
+          }
+          continue;
+        };
+        if let Event::Start(_) = event {
+          reader.read_to_end(tag.name())?;
+        }
+        output.extend_from_slice(&xml[reported..position]);
+        output.extend_from_slice(markup.as_bytes());
+        reported = reader.buffer_position() as usize;
+        met.push(idx);
+      }
+      Event::Start(_) => depth += 1,
+      Event::End(_) => {
+        depth -= 1;
+        if depth == 0 {
+          output.extend_from_slice(&xml[reported..position]);
+          reported = position;
+          end = Some(output.len());
+        }
+      }
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+
+  output.extend_from_slice(&xml[reported..]);
+  let end = end.unwrap_or(output.len());
+  Ok((output, end, met))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn patches_core_properties() {
+    let mut properties = DocumentProperties::default();
+    properties.set_core(CoreProperty::Title, "Terms & conditions".into());
+    properties.set_core(CoreProperty::Modified, "2024-05-31T12:00:00Z".into());
+    properties.set_core(CoreProperty::Creator, "Acme".into());
+
+    let xml = br#"<cp:coreProperties xmlns:cp="cp" xmlns:dc="dc"><dc:title/><dc:creator>Jane</dc:creator><cp:revision>2</cp:revision></cp:coreProperties>"#;
+    let patched = String::from_utf8(properties.patch_core(Some(xml)).unwrap()).unwrap();
+    insta::assert_snapshot!(patched, @r###"
+      <cp:coreProperties xmlns:cp="cp" xmlns:dc="dc" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><dc:title>Terms &amp; conditions</dc:title><dc:creator>Acme</dc:creator><cp:revision>2</cp:revision><dcterms:modified xsi:type="dcterms:W3CDTF">2024-05-31T12:00:00Z</dcterms:modified></cp:coreProperties>
+    "###);
+  }
+
+  #[test]
+  fn patches_custom_properties() {
+    let mut properties = DocumentProperties::default();
+    properties.set_custom("customer".into(), "Alphabet".into());
+    properties.set_custom("Revision".into(), 3.into());
+
+    let created = String::from_utf8(properties.patch_custom(None).unwrap()).unwrap();
+    insta::assert_snapshot!(created, @r###"
+      <?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+      <Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/custom-properties" xmlns:vt="http://schemas.openxmlformats.org/officeDocument/2006/docPropsVTypes"><property fmtid="{D5CDD505-2E9C-101B-9397-08002B2CF9AE}" pid="2" name="customer"><vt:lpwstr>Alphabet</vt:lpwstr></property><property fmtid="{D5CDD505-2E9C-101B-9397-08002B2CF9AE}" pid="3" name="Revision"><vt:i4>3</vt:i4></property></Properties>
+    "###);
+
+    let xml = format!(
+      r#"<Properties xmlns="custom" xmlns:vt="vt">{}</Properties>"#,
+      custom_markup(5, "Customer", &PropertyValue::Bool(false)),
+    );
+    let patched =
+      String::from_utf8(properties.patch_custom(Some(xml.as_bytes())).unwrap()).unwrap();
+    insta::assert_snapshot!(patched, @r###"
+      <Properties xmlns="custom" xmlns:vt="vt"><property fmtid="{D5CDD505-2E9C-101B-9397-08002B2CF9AE}" pid="5" name="Customer"><vt:lpwstr>Alphabet</vt:lpwstr></property><property fmtid="{D5CDD505-2E9C-101B-9397-08002B2CF9AE}" pid="6" name="Revision"><vt:i4>3</vt:i4></property></Properties>
+    "###);
+  }
+}
@@ -0,0 +1,508 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+
+pub const HYPERLINK_TYPE: &str =
+  "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+pub const IMAGE_TYPE: &str =
+  "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+
+/// Marks a relationship id inside markup of a value. Control characters are not allowed
+/// in XML, so the marker can't clash with the document's content.
+const MARKER: u8 = 0x01;
+
+/// A reference from a part of the document to a resource, like an external hyperlink or an image.
+///
+/// Values carry relationships along with the markup, where `r:id` attributes hold [markers](Relationship::marker).
+/// The real ids are allocated per part while rendering.
+#[derive(Debug, Clone)]
+pub struct Relationship {
+  id: u64,
+  pub target: Target,
+}
+
+#[derive(Debug, Clone)]
+pub enum Target {
+  /// A link to an external resource, `TargetMode="External"`.
+  Hyperlink(String),
+  /// A file to be put into `word/media/`.
+  Media { extension: String, content_type: String, bytes: Arc<[u8]> },
+}
+
+impl Relationship {
+  pub fn new(target: Target) -> Self {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    Self { id: COUNTER.fetch_add(1, Ordering::Relaxed), target }
+  }
+
+  /// A text to be put into the markup instead of `r:id` value.
+  pub fn marker(&self) -> String {
+    let marker = char::from(MARKER);
+    format!("{marker}{}{marker}", self.id)
+  }
+
+  fn kind(&self) -> &'static str {
+    match self.target {
+      Target::Hyperlink(_) => HYPERLINK_TYPE,
+      Target::Media { .. } => IMAGE_TYPE,
+    }
+  }
+}
+
+/// Opening `<w:hyperlink>` tag. Links to `#bookmarks` are anchors,
+/// others are external ones, which require a relationship.
+pub fn hyperlink_tag(href: &str) -> (String, Option<Relationship>) {
+  if let Some(bookmark) = href.strip_prefix('#') {
+    return (format!(r#"<w:hyperlink w:anchor="{}">"#, escape(bookmark)), None);
+  }
+  let relationship = Relationship::new(Target::Hyperlink(href.to_owned()));
+  (format!(r#"<w:hyperlink r:id="{}">"#, relationship.marker()), Some(relationship))
+}
+
+/// Replaces markers of relationships with real ids, accumulating entries
+/// of `.rels` files, media files and content types to be written.
+#[derive(Debug, Default)]
+pub struct Linker {
+  relationships: HashMap<u64, Relationship>,
+  /// Ids allocated per `.rels` file, by relationship.
+  allocated: HashMap<String, HashMap<u64, String>>,
+  /// New `<Relationship>` entries per `.rels` file.
+  entries: BTreeMap<String, String>,
+  /// Paths of new media files, by relationship.
+  media: BTreeMap<u64, String>,
+  /// Content types of new media files, by extension.
+  content_types: BTreeMap<String, String>,
+  /// Content types of existing files, whose format was changed, by path.
+  overrides: BTreeMap<String, String>,
+  /// Paths of parts dropped from the archive, along with their `.rels` files.
+  removed: BTreeSet<String>,
+}
+
+impl Linker {
+  pub fn new(relationships: impl IntoIterator<Item = Relationship>) -> Self {
+    let relationships = relationships.into_iter().map(|it| (it.id, it)).collect();
+    Self { relationships, ..Default::default() }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.relationships.is_empty()
+  }
+
+  /// Path of the `.rels` file describing relationships of the `part`,
+  /// `word/document.xml` → `word/_rels/document.xml.rels`.
+  pub fn rels_path(part: &str) -> String {
+    let (dir, file) = part.rsplit_once('/').unwrap_or(("", part));
+    match dir {
+      "" => format!("_rels/{file}.rels"),
+      dir => format!("{dir}/_rels/{file}.rels"),
+    }
+  }
+
+  /// Replaces markers inside the `part`'s markup.
+  ///
+  /// `existing_ids` are relationships already listed in the part's `.rels` file,
+  /// `existing_files` are paths of the archive's files.
+  pub fn link(
+    &mut self,
+    part: &str,
+    xml: Vec<u8>,
+    existing_ids: &HashMap<String, String>,
+    existing_files: &HashSet<String>,
+  ) -> Vec<u8> {
+    if !xml.contains(&MARKER) {
+      return xml;
+    }
+
+    let rels_path = Self::rels_path(part);
+    let mut output = Vec::with_capacity(xml.len());
+    let mut pieces = xml.split(|&byte| byte == MARKER);
+
+    // markers split the markup into: text, id, text, id, ..., text
+    output.extend(pieces.next().unwrap_or_default());
+    while let (Some(id), Some(text)) = (pieces.next(), pieces.next()) {
+      let id = std::str::from_utf8(id).ok().and_then(|it| it.parse::<u64>().ok());
+      if let Some(rel_id) =
+        id.and_then(|id| self.allocate(part, &rels_path, id, existing_ids, existing_files))
+      {
+        output.extend_from_slice(rel_id.as_bytes());
+      }
+      output.extend_from_slice(text);
+    }
+
+    output
+  }
+
+  fn allocate(
+    &mut self,
+    part: &str,
+    rels_path: &str,
+    id: u64,
+    existing_ids: &HashMap<String, String>,
+    existing_files: &HashSet<String>,
+  ) -> Option<String> {
+    let relationship = self.relationships.get(&id)?.clone();
+    let allocated = self.allocated.entry(rels_path.to_owned()).or_default();
+    if let Some(rel_id) = allocated.get(&id) {
+      return Some(rel_id.clone());
+    }
+
+    let listed = self.entries.get(rels_path).map_or("", String::as_str);
+    let taken = |candidate: &String| {
+      existing_ids.contains_key(candidate)
+        || allocated.values().any(|it| it == candidate)
+        || listed.contains(&format!(r#"Id="{candidate}""#))
+    };
+    let rel_id = (1..).map(|n| format!("rId{n}")).find(|it| !taken(it))?;
+    allocated.insert(id, rel_id.clone());
+
+    let (target, mode) = match relationship.target {
+      Target::Hyperlink(ref url) => (url.clone(), r#" TargetMode="External""#),
+      Target::Media { ref extension, ref content_type, .. } => {
+        let path = self.media_path(id, extension, existing_files);
+        self.content_types.insert(extension.to_lowercase(), content_type.clone());
+        (relative_target(part, &path), "")
+      }
+    };
+
+    let entry = format!(
+      r#"<Relationship Id="{rel_id}" Type="{}" Target="{}"{mode}/>"#,
+      relationship.kind(),
+      escape(target.as_str()),
+    );
+    self.entries.entry(rels_path.to_owned()).or_default().push_str(&entry);
+    Some(rel_id)
+  }
+
+  /// Lists a new part in the `.rels` file, like `docProps/custom.xml` in `_rels/.rels`.
+  pub fn add_part(
+    &mut self,
+    rels_path: &str,
+    kind: &str,
+    target: &str,
+    existing_ids: &HashMap<String, String>,
+  ) {
+    let allocated = self.allocated.get(rels_path);
+    let listed = self.entries.get(rels_path).map_or("", String::as_str);
+    let taken = |candidate: &String| {
+      existing_ids.contains_key(candidate)
+        || allocated.is_some_and(|it| it.values().any(|it| it == candidate))
+        || listed.contains(&format!(r#"Id="{candidate}""#))
+    };
+    let Some(rel_id) = (1..).map(|n| format!("rId{n}")).find(|it| !taken(it)) else { return };
+
+    let entry =
+      format!(r#"<Relationship Id="{rel_id}" Type="{kind}" Target="{}"/>"#, escape(target));
+    self.entries.entry(rels_path.to_owned()).or_default().push_str(&entry);
+  }
+
+  /// Drops the part from the archive, unlisting it from `.rels` files and `[Content_Types].xml`.
+  pub fn remove_part(&mut self, path: &str) {
+    self.removed.insert(path.to_owned());
+    self.removed.insert(Self::rels_path(path));
+  }
+
+  /// Returns true if the file is dropped from the archive.
+  pub fn is_removed(&self, path: &str) -> bool {
+    self.removed.contains(path)
+  }
+
+  /// A free `word/media/imageN.ext` path, shared between parts referencing the same file.
+  fn media_path(&mut self, id: u64, extension: &str, existing_files: &HashSet<String>) -> String {
+    if let Some(path) = self.media.get(&id) {
+      return path.clone();
+    }
+    let taken =
+      |path: &String| existing_files.contains(path) || self.media.values().any(|it| it == path);
+    let path = (1..).map(|n| format!("word/media/image{n}.{extension}")).find(|it| !taken(it));
+    let path = path.unwrap_or_default();
+    self.media.insert(id, path.clone());
+    path
+  }
+
+  /// Returns true if the `.rels` file has to be changed.
+  pub fn touches(&self, rels_path: &str) -> bool {
+    self.entries.contains_key(rels_path) || !self.removed.is_empty() && rels_path.ends_with(".rels")
+  }
+
+  /// Adds new entries to the `.rels` file, or creates it. Entries of removed parts are dropped.
+  pub fn patch_rels(&self, rels_path: &str, xml: Option<&[u8]>) -> Vec<u8> {
+    let entries = self.entries.get(rels_path).map_or("", String::as_str);
+    match xml {
+      Some(xml) => {
+        let xml = match self.removed.is_empty() {
+          true => xml.to_vec(),
+          false => self.unlist_removed(rels_path, xml),
+        };
+        insert_before(&xml, b"</Relationships>", entries.as_bytes())
+      }
+      None => format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+        <Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+        {entries}</Relationships>"
+      )
+      .into_bytes(),
+    }
+  }
+
+  /// Drops `<Relationship>` entries targeting removed parts.
+  fn unlist_removed(&self, rels_path: &str, xml: &[u8]) -> Vec<u8> {
+    let part = Self::part_path(rels_path);
+    let mut reader = quick_xml::Reader::from_reader(xml);
+    let mut output = Vec::with_capacity(xml.len());
+    let mut reported = 0;
+    loop {
+      let position = reader.buffer_position() as usize;
+      match reader.read_event() {
+        Ok(Event::Empty(tag)) if tag.name().as_ref() == b"Relationship" => {
+          let external = tag.try_get_attribute("TargetMode").ok().flatten();
+          if external.is_some_and(|it| it.value.as_ref() == b"External") {
+            continue;
+          }
+          let target = tag.try_get_attribute("Target").ok().flatten();
+          let target = target.and_then(|it| it.unescape_value().ok().map(|it| it.into_owned()));
+          if target.is_some_and(|it| self.removed.contains(&resolve_target(&part, &it))) {
+            output.extend_from_slice(&xml[reported..position]);
+            reported = reader.buffer_position() as usize;
+          }
+        }
+        Ok(Event::Eof) | Err(_) => break,
+        _ => {}
+      }
+    }
+    output.extend_from_slice(&xml[reported..]);
+    output
+  }
+
+  /// Path of the part described by the `.rels` file, the opposite of [Linker::rels_path].
+  /// The package's `_rels/.rels` gives an empty path, so that targets resolve from the root.
+  fn part_path(rels_path: &str) -> String {
+    let rels_path = rels_path.strip_suffix(".rels").unwrap_or(rels_path);
+    let (dir, file) = rels_path.rsplit_once('/').unwrap_or(("", rels_path));
+    let dir = dir.strip_suffix("_rels").unwrap_or(dir);
+    format!("{dir}{file}")
+  }
+
+  /// Sets the content type of an existing file, like an image replaced by one of another format.
+  pub fn override_content_type(&mut self, path: &str, content_type: &str) {
+    self.overrides.insert(path.to_owned(), content_type.to_owned());
+  }
+
+  /// Returns true if `[Content_Types].xml` may have to be changed.
+  pub fn touches_content_types(&self) -> bool {
+    !self.media.is_empty() || !self.overrides.is_empty() || !self.removed.is_empty()
+  }
+
+  /// Adds defaults for extensions of new media files to `[Content_Types].xml`, and overrides.
+  /// Overrides of removed parts are dropped.
+  pub fn patch_content_types(&self, xml: &[u8]) -> Vec<u8> {
+    let mut xml = xml.to_vec();
+    for path in &self.removed {
+      xml = remove_override(&xml, &escape(format!("/{path}")));
+    }
+    let text = String::from_utf8_lossy(&xml).to_lowercase();
+    let mut entries = String::new();
+    for (extension, content_type) in &self.content_types {
+      if !text.contains(&format!(r#"extension="{extension}""#)) {
+        entries += &format!(r#"<Default Extension="{extension}" ContentType="{content_type}"/>"#);
+      }
+    }
+    for (path, content_type) in &self.overrides {
+      let part_name = escape(format!("/{path}")).into_owned();
+      xml = remove_override(&xml, &part_name);
+      entries += &format!(r#"<Override PartName="{part_name}" ContentType="{content_type}"/>"#);
+    }
+    insert_before(&xml, b"</Types>", entries.as_bytes())
+  }
+
+  /// `.rels` files to be created, as their parts had no relationships before.
+  pub fn new_rels_files<'s>(
+    &'s self,
+    existing_files: &'s HashSet<String>,
+  ) -> impl Iterator<Item = &'s str> + 's {
+    self.entries.keys().map(String::as_str).filter(|it| !existing_files.contains(*it))
+  }
+
+  /// New media files along with their content.
+  pub fn media_files(&self) -> impl Iterator<Item = (&str, &[u8])> + '_ {
+    self.media.iter().filter_map(|(id, path)| match self.relationships.get(id)?.target {
+      Target::Media { ref bytes, .. } => Some((path.as_str(), bytes.as_ref())),
+      Target::Hyperlink(_) => None,
+    })
+  }
+}
+
+/// Makes the `path` relative to the directory of the `part`.
+fn relative_target(part: &str, path: &str) -> String {
+  let dir = part.rsplit_once('/').map_or("", |(dir, _)| dir);
+  match path.strip_prefix(dir).and_then(|it| it.strip_prefix('/')) {
+    Some(relative) if !dir.is_empty() => relative.to_owned(),
+    _ => format!("/{path}"),
+  }
+}
+
+/// Resolves a target of the `part`'s relationship into a path inside the archive.
+pub fn resolve_target(part: &str, target: &str) -> String {
+  if let Some(absolute) = target.strip_prefix('/') {
+    return absolute.to_owned();
+  }
+  let mut segments = part.split('/').collect::<Vec<_>>();
+  segments.pop();
+  for segment in target.split('/') {
+    match segment {
+      "." | "" => {}
+      ".." => {
+        segments.pop();
+      }
+      segment => segments.push(segment),
+    }
+  }
+  segments.join("/")
+}
+
+/// Removes the `<Override>` entry of the part from `[Content_Types].xml`, if present.
+fn remove_override(xml: &[u8], part_name: &str) -> Vec<u8> {
+  let needle = format!(r#"PartName="{part_name}""#);
+  let text = String::from_utf8_lossy(xml);
+  let Some(attribute) = text.find(&needle) else { return xml.to_vec() };
+  let start = text[..attribute].rfind('<').unwrap_or(attribute);
+  let end = text[attribute..].find('>').map_or(text.len(), |it| attribute + it + 1);
+  [&xml[..start], &xml[end..]].concat()
+}
+
+fn insert_before(xml: &[u8], closing_tag: &[u8], content: &[u8]) -> Vec<u8> {
+  let position = xml.windows(closing_tag.len()).rposition(|window| window == closing_tag);
+  let position = position.unwrap_or(xml.len());
+  [&xml[..position], content, &xml[position..]].concat()
+}
+
+/// Targets of relationships listed in a `.rels` file, by ids.
+pub fn relationship_targets(xml: &[u8]) -> HashMap<String, String> {
+  let relationships = read_relationships(xml).into_iter();
+  relationships.map(|(id, _, target)| (id, target)).collect()
+}
+
+/// Targets of image relationships listed in a `.rels` file.
+pub fn image_targets(xml: &[u8]) -> Vec<String> {
+  let relationships = read_relationships(xml).into_iter();
+  relationships.filter(|(_, kind, _)| kind == IMAGE_TYPE).map(|(_, _, target)| target).collect()
+}
+
+/// Ids, types and targets of relationships listed in a `.rels` file.
+fn read_relationships(xml: &[u8]) -> Vec<(String, String, String)> {
+  let mut reader = quick_xml::Reader::from_reader(xml);
+  let mut relationships = Vec::new();
+  while let Ok(event) = reader.read_event() {
+    match event {
+      quick_xml::events::Event::Empty(tag) | quick_xml::events::Event::Start(tag) => {
+        let attribute = |name| match tag.try_get_attribute(name) {
+          Ok(Some(attribute)) => attribute.unescape_value().ok().map(|it| it.into_owned()),
+          _ => None,
+        };
+        if let Some(id) = attribute("Id") {
+          let kind = attribute("Type").unwrap_or_default();
+          relationships.push((id, kind, attribute("Target").unwrap_or_default()));
+        }
+      }
+      quick_xml::events::Event::Eof => break,
+      _ => {}
+    }
+  }
+  relationships
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn allocates_ids_per_part() {
+    let link = Relationship::new(Target::Hyperlink("https://example.com/?a&b".into()));
+    let image = Relationship::new(Target::Media {
+      extension: "png".into(),
+      content_type: "image/png".into(),
+      bytes: Arc::from(&b"png"[..]),
+    });
+    let xml = format!(
+      r#"<a r:id="{}"/><b r:id="{}"/><a r:id="{}"/>"#,
+      link.marker(),
+      image.marker(),
+      link.marker()
+    );
+
+    let mut linker = Linker::new([link, image]);
+    let ids = HashMap::from([("rId1".to_owned(), "styles.xml".to_owned())]);
+    let files = HashSet::from(["word/media/image1.png".to_owned()]);
+    let linked = linker.link("word/document.xml", xml.into_bytes(), &ids, &files);
+
+    assert_eq!(
+      String::from_utf8(linked).unwrap(),
+      r#"<a r:id="rId2"/><b r:id="rId3"/><a r:id="rId2"/>"#
+    );
+    assert_eq!(
+      String::from_utf8(
+        linker.patch_rels("word/_rels/document.xml.rels", Some(b"<Relationships></Relationships>"))
+      )
+      .unwrap(),
+      format!(
+        r#"<Relationships><Relationship Id="rId2" Type="{HYPERLINK_TYPE}" Target="https://example.com/?a&amp;b" TargetMode="External"/><Relationship Id="rId3" Type="{IMAGE_TYPE}" Target="media/image2.png"/></Relationships>"#
+      )
+    );
+    assert!(linker.touches_content_types());
+    assert_eq!(linker.media_files().collect::<Vec<_>>(), [("word/media/image2.png", &b"png"[..])]);
+    assert_eq!(
+      linker.patch_content_types(br#"<Types><Default Extension="xml" ContentType="application/xml"/></Types>"#),
+      br#"<Types><Default Extension="xml" ContentType="application/xml"/><Default Extension="png" ContentType="image/png"/></Types>"#
+    );
+  }
+
+  #[test]
+  fn overrides_content_types() {
+    let mut linker = Linker::default();
+    linker.override_content_type("word/media/image1.jpg", "image/png");
+    assert_eq!(
+      linker.patch_content_types(
+        br#"<Types><Override PartName="/word/media/image1.jpg" ContentType="image/jpeg"/></Types>"#
+      ),
+      br#"<Types><Override PartName="/word/media/image1.jpg" ContentType="image/png"/></Types>"#
+    );
+  }
+
+  #[test]
+  fn removes_parts() {
+    let mut linker = Linker::default();
+    linker.remove_part("word/comments.xml");
+    assert!(linker.is_removed("word/_rels/comments.xml.rels"));
+    assert!(linker.touches("word/_rels/document.xml.rels"));
+    assert_eq!(
+      linker.patch_rels(
+        "word/_rels/document.xml.rels",
+        Some(br#"<Relationships><Relationship Id="rId1" Target="styles.xml"/><Relationship Id="rId2" Target="comments.xml"/></Relationships>"#)
+      ),
+      br#"<Relationships><Relationship Id="rId1" Target="styles.xml"/></Relationships>"#
+    );
+    assert_eq!(
+      linker.patch_content_types(
+        br#"<Types><Override PartName="/word/comments.xml" ContentType="application/xml"/></Types>"#
+      ),
+      br#"<Types></Types>"#
+    );
+  }
+
+  #[test]
+  fn reads_image_targets() {
+    let xml = format!(
+      r#"<Relationships><Relationship Id="rId1" Type="{IMAGE_TYPE}" Target="media/a.png"/><Relationship Id="rId2" Type="{HYPERLINK_TYPE}" Target="https://example.com" TargetMode="External"/></Relationships>"#
+    );
+    assert_eq!(image_targets(xml.as_bytes()), ["media/a.png"]);
+  }
+
+  #[test]
+  fn resolves_targets() {
+    assert_eq!(resolve_target("word/document.xml", "media/image1.png"), "word/media/image1.png");
+    assert_eq!(resolve_target("word/header1.xml", "../media/a.png"), "media/a.png");
+    assert_eq!(resolve_target("word/document.xml", "/word/media/b.png"), "word/media/b.png");
+  }
+}
@@ -0,0 +1,68 @@
+use std::fmt::{Display, Formatter};
+
+use crate::transformers::layout::{tokens, Layout};
+
+/// Defines how a template reacts on placeholders left unfilled and data never used.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+  /// No checks are made.
+  #[default]
+  Silent,
+  /// Issues are collected into [warnings](crate::DocxTemplate::warnings), render succeeds.
+  Lenient,
+  /// Render fails with [StrictRenderErr](crate::CantRenderError::StrictRenderErr) if any issue is found.
+  Strict,
+}
+
+/// Issues met while rendering a template in [lenient](RenderMode::Lenient)
+/// or [strict](RenderMode::Strict) mode.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RenderReport {
+  /// Bracketed tokens left in the document, like `{clinetName}`,
+  /// and merge fields having no value, like `MERGEFIELD ClinetName`.
+  pub unresolved_placeholders: Vec<String>,
+  /// Data keys which matched nothing in the document, like `client.phone`. Placeholders are
+  /// reported without brackets, bookmarks and controls by their names.
+  pub unused_keys: Vec<String>,
+}
+
+impl RenderReport {
+  /// Returns true if there are no issues.
+  pub fn is_empty(&self) -> bool {
+    self.unresolved_placeholders.is_empty() && self.unused_keys.is_empty()
+  }
+
+  /// Collects `{…}` tokens from paragraphs of the rendered part.
+  pub(crate) fn scan_unresolved(
+    &mut self,
+    xml: &[u8],
+    open: &str,
+    close: &str,
+  ) -> Result<(), quick_xml::Error> {
+    for paragraph in Layout::read(xml)?.paragraphs {
+      let text = paragraph.text(xml);
+      for token in tokens(&text, open.as_bytes(), close.as_bytes()) {
+        let token = String::from_utf8_lossy(&text[token]);
+        let token = quick_xml::escape::unescape(&token).unwrap_or(token.clone()).into_owned();
+        if !self.unresolved_placeholders.contains(&token) {
+          self.unresolved_placeholders.push(token);
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Display for RenderReport {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let mut sections = Vec::new();
+    if !self.unresolved_placeholders.is_empty() {
+      sections
+        .push(format!("unresolved placeholders: {}", self.unresolved_placeholders.join(", ")));
+    }
+    if !self.unused_keys.is_empty() {
+      sections.push(format!("unused keys: {}", self.unused_keys.join(", ")));
+    }
+    f.write_str(&sections.join("; "))
+  }
+}
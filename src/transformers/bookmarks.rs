@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::io;
+
+use crate::transformers::find_and_replace::{inherit_styles, Value};
+use crate::transformers::tree::{self, apply_edits, child, cut_between, path, Edit, Node};
+use crate::transformers::TransformerError;
+
+/// Replaces contents of bookmarks, which some templates use instead of `{placeholders}`.
+///
+/// Everything between `<w:bookmarkStart w:name="…">` and the `<w:bookmarkEnd>` having
+/// the same `w:id` is removed, the value is put in place of the start marker.
+/// Paragraphs the bookmark spans are merged into the first one, which takes properties
+/// of the last one, like Word does.
+///
+/// ```xml
+/// <w:p><w:bookmarkStart w:id="0" w:name="ClientName"/><w:r><w:t>Name</w:t></w:r><w:bookmarkEnd w:id="0"/></w:p>
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Bookmarks<'b> {
+  /// Values by bookmark names, which are case-insensitive.
+  pub values: &'b [(String, Value)],
+  /// Keep the bookmarks around the new content.
+  pub keep: bool,
+}
+
+impl Bookmarks<'_> {
+  /// Names of the replaced bookmarks are put into `matched`.
+  pub fn transform_stream<In: AsRef<[u8]>, Out: io::Write>(
+    &self,
+    input: In,
+    output: Out,
+    matched: &mut HashSet<String>,
+  ) -> Result<Out, TransformerError> {
+    let input = input.as_ref();
+    let nodes = tree::read(input)?;
+
+    let mut edits = Vec::<Edit>::new();
+    for (start, node) in nodes.iter().enumerate() {
+      if node.name != b"w:bookmarkStart" {
+        continue;
+      }
+      let name = node.attribute(input, "w:name").unwrap_or_default();
+      let Some((key, value)) = self.values.iter().find(|(key, _)| key.eq_ignore_ascii_case(&name))
+      else {
+        continue;
+      };
+      let end = nodes
+        .iter()
+        .position(|it| it.name == b"w:bookmarkEnd" && it.id.is_some() && it.id == node.id);
+      let Some(end) = end.filter(|&end| end > start) else { continue };
+      matched.insert(key.clone());
+
+      let at = match self.keep {
+        true => node.range.end,
+        false => node.range.start,
+      };
+      edits.push((at..at, content(input, &nodes, start, value)));
+      edits.extend(self.cut(input, &nodes, start, end));
+    }
+
+    // insertions go before cuts starting at the same position, edits of nested bookmarks are skipped
+    Ok(apply_edits(input, edits, output)?)
+  }
+
+  /// Edits removing everything between the markers.
+  fn cut(&self, input: &[u8], nodes: &[Node], start: usize, end: usize) -> Vec<Edit> {
+    let (from, to) = (path(nodes, start), path(nodes, end));
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let names =
+      |path: &[usize]| path.iter().map(|&it| nodes[it].name.as_slice()).collect::<Vec<_>>();
+    let (starts_in, ends_in) =
+      (names(&from[common..from.len() - 1]), names(&to[common..to.len() - 1]));
+
+    // markers of the same paragraph, or of sibling ones, which are merged
+    if starts_in == ends_in && starts_in.iter().all(|&it| it == b"w:p") {
+      let (start_marker, end_marker) = (&nodes[start].range, &nodes[end].range);
+      let cut = match self.keep {
+        true => start_marker.end..end_marker.start,
+        false => start_marker.start..end_marker.end,
+      };
+      let mut edits = vec![(cut, Vec::new())];
+      // the merged paragraph ends with the mark of the last one, so it takes its properties
+      if !starts_in.is_empty() {
+        edits.push(take_properties(input, nodes, from[common], to[common]));
+      }
+      return edits;
+    }
+    let cuts = cut_between(nodes, &from, &to, !self.keep);
+    cuts.into_iter().map(|cut| (cut, Vec::new())).collect()
+  }
+}
+
+/// Replaces properties of the `first` paragraph with ones of the `last`, section ones included.
+fn take_properties(input: &[u8], nodes: &[Node], first: usize, last: usize) -> Edit {
+  let range = match child(nodes, first, b"w:pPr") {
+    Some(properties) => nodes[properties].range.clone(),
+    None => nodes[first].content.start..nodes[first].content.start,
+  };
+  let properties = child(nodes, last, b"w:pPr").map(|it| &input[nodes[it].range.clone()]);
+  (range, properties.unwrap_or_default().to_vec())
+}
+
+/// The value wrapped into a run, formatted like the first run of the bookmark,
+/// or into a paragraph if the bookmark starts between paragraphs.
+///
+/// Paragraphs of block values are joined into one if the bookmark starts inside a paragraph.
+fn content(input: &[u8], nodes: &[Node], start: usize, value: &Value) -> Vec<u8> {
+  let Some(paragraph) = nodes[start].parent.filter(|&it| nodes[it].name == b"w:p") else {
+    return format!(r#"<w:p><w:r><w:t xml:space="preserve">{}</w:t></w:r></w:p>"#, value.xml)
+      .into_bytes();
+  };
+  let run = value.to_runs();
+
+  let siblings = &nodes[paragraph].children;
+  let at = siblings.iter().position(|&it| it == start).unwrap_or_default();
+  let runs = siblings[at..].iter().chain(siblings[..at].iter().rev());
+  let run_style =
+    runs.copied().find(|&it| nodes[it].name == b"w:r").and_then(|it| child(nodes, it, b"w:rPr"));
+
+  let style = |node: Option<usize>| node.map_or(&[][..], |it| &input[nodes[it].range.clone()]);
+  inherit_styles(run.as_bytes(), style(child(nodes, paragraph, b"w:pPr")), style(run_style))
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use indoc::indoc;
+
+  use super::*;
+
+  fn run(input: &str, keep: bool) -> String {
+    let values = [("clientname".to_owned(), Value::from_text("Ann & Co"))];
+    let bookmarks = Bookmarks { values: &values, keep };
+    let mut matched = HashSet::new();
+    let buf = bookmarks.transform_stream(input, Vec::new(), &mut matched).unwrap();
+    assert_eq!(matched, HashSet::from(["clientname".to_owned()]));
+    String::from_utf8(buf).unwrap()
+  }
+
+  #[test]
+  fn replaces_runs() {
+    let input = indoc! {r#"
+      <w:p><w:r><w:t>Dear </w:t></w:r><w:bookmarkStart w:id="0" w:name="ClientName"/><w:r><w:rPr><w:b/></w:rPr><w:t>Na</w:t></w:r><w:r><w:t>me</w:t></w:r><w:bookmarkEnd w:id="0"/><w:r><w:t>,</w:t></w:r></w:p>
+    "#};
+    insta::assert_snapshot!(run(input, false), @r###"
+      <w:p><w:r><w:t>Dear </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve">Ann &amp; Co</w:t></w:r><w:r><w:t>,</w:t></w:r></w:p>
+    "###);
+    insta::assert_snapshot!(run(input, true), @r###"
+      <w:p><w:r><w:t>Dear </w:t></w:r><w:bookmarkStart w:id="0" w:name="ClientName"/><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve">Ann &amp; Co</w:t></w:r><w:bookmarkEnd w:id="0"/><w:r><w:t>,</w:t></w:r></w:p>
+    "###);
+  }
+
+  #[test]
+  fn joins_paragraphs_of_block_values() {
+    let input = indoc! {r#"
+      <w:p><w:r><w:t>Notes: </w:t></w:r><w:bookmarkStart w:id="0" w:name="Notes"/><w:r><w:t>Notes</w:t></w:r><w:bookmarkEnd w:id="0"/></w:p>
+    "#};
+    let values = [("notes".to_owned(), Value::from_markdown("**Paid**\n\n- in full"))];
+    let bookmarks = Bookmarks { values: &values, keep: false };
+    let buf = bookmarks.transform_stream(input, Vec::new(), &mut HashSet::new()).unwrap();
+    insta::assert_snapshot!(String::from_utf8(buf).unwrap(), @r###"
+      <w:p><w:r><w:t>Notes: </w:t></w:r><w:r><w:rPr><w:b/><w:bCs/></w:rPr><w:t xml:space="preserve">Paid</w:t></w:r><w:r><w:br/></w:r><w:r><w:t xml:space="preserve">in full</w:t></w:r></w:p>
+    "###);
+  }
+
+  #[test]
+  fn merges_paragraphs() {
+    let input = indoc! {r#"
+      <w:body>
+        <w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:t>Dear </w:t></w:r><w:bookmarkStart w:id="0" w:name="ClientName"/><w:r><w:t>first</w:t></w:r></w:p>
+        <w:p><w:r><w:t>second</w:t></w:r></w:p>
+        <w:p><w:pPr><w:jc w:val="right"/></w:pPr><w:r><w:t>third</w:t></w:r><w:bookmarkEnd w:id="0"/><w:r><w:t>,</w:t></w:r></w:p>
+      </w:body>
+    "#};
+    insta::assert_snapshot!(run(input, false), @r###"
+      <w:body>
+        <w:p><w:pPr><w:jc w:val="right"/></w:pPr><w:r><w:t>Dear </w:t></w:r><w:r><w:t xml:space="preserve">Ann &amp; Co</w:t></w:r><w:r><w:t>,</w:t></w:r></w:p>
+      </w:body>
+    "###);
+  }
+
+  #[test]
+  fn keeps_section_properties_of_the_last_paragraph() {
+    let input = indoc! {r#"
+      <w:body>
+        <w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:bookmarkStart w:id="0" w:name="ClientName"/><w:r><w:t>first</w:t></w:r></w:p>
+        <w:p><w:pPr><w:sectPr><w:pgSz w:w="11906" w:h="16838"/></w:sectPr></w:pPr><w:r><w:t>last</w:t></w:r><w:bookmarkEnd w:id="0"/></w:p>
+      </w:body>
+    "#};
+    insta::assert_snapshot!(run(input, false), @r###"
+      <w:body>
+        <w:p><w:pPr><w:sectPr><w:pgSz w:w="11906" w:h="16838"/></w:sectPr></w:pPr><w:r><w:t xml:space="preserve">Ann &amp; Co</w:t></w:r></w:p>
+      </w:body>
+    "###);
+  }
+
+  #[test]
+  fn replaces_tables() {
+    let input = indoc! {r#"
+      <w:body>
+        <w:bookmarkStart w:id="0" w:name="ClientName"/>
+        <w:tbl><w:tr><w:tc><w:p><w:r><w:t>cell</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+        <w:p><w:r><w:t>drop</w:t></w:r><w:bookmarkEnd w:id="0"/><w:r><w:t>keep</w:t></w:r></w:p>
+      </w:body>
+    "#};
+    insta::assert_snapshot!(run(input, true), @r###"
+      <w:body>
+        <w:bookmarkStart w:id="0" w:name="ClientName"/><w:p><w:r><w:t xml:space="preserve">Ann &amp; Co</w:t></w:r></w:p><w:p><w:bookmarkEnd w:id="0"/><w:r><w:t>keep</w:t></w:r></w:p>
+      </w:body>
+    "###);
+  }
+}
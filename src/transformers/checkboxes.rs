@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+
+use quick_xml::escape::unescape;
+
+use crate::transformers::tree::{self, apply_edits, child, set_attribute, Edit, Node};
+use crate::transformers::TransformerError;
+
+/// `☒`, shown by checked controls lacking `<w14:checkedState>`.
+const CHECKED: char = '\u{2612}';
+/// `☐`, shown by unchecked controls lacking `<w14:uncheckedState>`.
+const UNCHECKED: char = '\u{2610}';
+
+/// Ticks or clears checkboxes, both content controls and legacy form fields.
+///
+/// ```xml
+/// <w:sdt>
+///   <w:sdtPr><w:tag w:val="agree"/><w14:checkbox><w14:checked w14:val="0"/></w14:checkbox></w:sdtPr>
+///   <w:sdtContent><w:r><w:t>☐</w:t></w:r></w:sdtContent>
+/// </w:sdt>
+/// <w:r><w:fldChar w:fldCharType="begin"><w:ffData><w:name w:val="Agree"/><w:checkBox><w:default w:val="0"/></w:checkBox></w:ffData></w:fldChar></w:r>
+/// ```
+///
+/// Checkboxes are looked up by the control's tag or alias, by the form field's name
+/// or a bookmark around it, or by a `{placeholder}` run right before or after the checkbox,
+/// which is removed then.
+#[derive(Debug, Clone, Copy)]
+pub struct Checkboxes<'b> {
+  /// Brackets of the placeholders next to checkboxes.
+  pub open_bracket: &'b str,
+  pub close_bracket: &'b str,
+}
+
+impl Checkboxes<'_> {
+  pub fn transform_stream<In: AsRef<[u8]>, Out: io::Write>(
+    &self,
+    input: In,
+    output: Out,
+    lookup: impl FnMut(&str) -> Option<bool>,
+  ) -> Result<Out, TransformerError> {
+    let input = input.as_ref();
+    let edits = self.edits(input, lookup)?;
+    // a placeholder shared by neighbouring checkboxes is removed once
+    Ok(apply_edits(input, edits, output)?)
+  }
+
+  /// Edits ticking or clearing the checkboxes of the input.
+  pub(super) fn edits(
+    &self,
+    input: &[u8],
+    mut lookup: impl FnMut(&str) -> Option<bool>,
+  ) -> Result<Vec<Edit>, TransformerError> {
+    let nodes = tree::read(input)?;
+    let bookmarks = bookmarks(input, &nodes);
+
+    let mut edits = Vec::<Edit>::new();
+    for (idx, node) in nodes.iter().enumerate() {
+      let checkbox = match node.name.as_slice() {
+        b"w:sdt" => {
+          child(&nodes, idx, b"w:sdtPr").and_then(|it| child(&nodes, it, b"w14:checkbox"))
+        }
+        b"w:ffData" => child(&nodes, idx, b"w:checkBox"),
+        _ => None,
+      };
+      let Some(checkbox) = checkbox else { continue };
+
+      // runs the checkbox starts and ends with, placeholders may be next to them
+      let (first, last) = match node.name.as_slice() {
+        b"w:sdt" => (idx, idx),
+        _ => match field_runs(input, &nodes, idx) {
+          Some(runs) => runs,
+          None => continue,
+        },
+      };
+      let placeholders = [
+        self.placeholder(input, &nodes, first, true),
+        self.placeholder(input, &nodes, last, false),
+      ];
+
+      let mut names = self.names(input, &nodes, &bookmarks, idx);
+      names.extend(placeholders.iter().flatten().map(|(_, name)| name.clone()));
+      let Some((name, checked)) =
+        names.into_iter().find_map(|name| Some((name.clone(), lookup(&name)?)))
+      else {
+        continue;
+      };
+
+      let placeholder = placeholders.iter().flatten().find(|(_, it)| *it == name);
+      edits.extend(placeholder.map(|&(run, _)| (nodes[run].range.clone(), Vec::new())));
+      edits.extend(match node.name.as_slice() {
+        b"w:sdt" => tick_control(input, &nodes, idx, checkbox, checked),
+        _ => tick_field(input, &nodes, checkbox, checked),
+      });
+    }
+
+    Ok(edits)
+  }
+
+  /// Returns true if the input may hold checkboxes.
+  pub(super) fn are_met(input: &[u8]) -> bool {
+    [&b"<w14:checkbox"[..], b"<w:checkBox"]
+      .iter()
+      .any(|tag| input.windows(tag.len()).any(|window| window == *tag))
+  }
+
+  /// Tag and alias of the control, or the name of the form field and bookmarks around it.
+  fn names(
+    &self,
+    input: &[u8],
+    nodes: &[Node],
+    bookmarks: &[(Range<usize>, String)],
+    idx: usize,
+  ) -> Vec<String> {
+    let value = |node: Option<usize>| node.and_then(|it| nodes[it].attribute(input, "w:val"));
+    if nodes[idx].name == b"w:sdt" {
+      let properties = child(nodes, idx, b"w:sdtPr");
+      let names = [&b"w:tag"[..], b"w:alias"]
+        .map(|it| value(properties.and_then(|props| child(nodes, props, it))));
+      return names.into_iter().flatten().collect();
+    }
+
+    let mut names = Vec::from_iter(value(child(nodes, idx, b"w:name")));
+    let at = nodes[idx].range.start;
+    let started = &bookmarks[..bookmarks.partition_point(|(range, _)| range.start < at)];
+    names.extend(started.iter().filter(|(range, _)| range.end > at).map(|(_, name)| name.clone()));
+    names
+  }
+
+  /// The closest sibling run, before or after the node, if its text is a placeholder.
+  fn placeholder(
+    &self,
+    input: &[u8],
+    nodes: &[Node],
+    idx: usize,
+    before: bool,
+  ) -> Option<(usize, String)> {
+    let siblings = &nodes[nodes[idx].parent?].children;
+    let at = siblings.iter().position(|&it| it == idx)?;
+    let is_marker = |it: &usize| {
+      matches!(nodes[*it].name.as_slice(), b"w:bookmarkStart" | b"w:bookmarkEnd" | b"w:proofErr")
+    };
+    let run = match before {
+      true => siblings[..at].iter().rev().find(|it| !is_marker(it)),
+      false => siblings[at + 1..].iter().find(|it| !is_marker(it)),
+    };
+    let run = *run.filter(|&&it| nodes[it].name == b"w:r")?;
+
+    let texts = nodes[run].children.iter().filter(|&&it| nodes[it].name == b"w:t");
+    let text =
+      texts.map(|&it| String::from_utf8_lossy(&input[nodes[it].content.clone()]).into_owned());
+    let text = unescape(&text.collect::<String>()).ok()?.trim().to_owned();
+    let name = text.strip_prefix(self.open_bracket)?.strip_suffix(self.close_bracket)?;
+    Some((run, name.trim().to_owned()))
+  }
+}
+
+/// Names of the bookmarks along with bytes between their start and end markers,
+/// ordered by starts.
+fn bookmarks(input: &[u8], nodes: &[Node]) -> Vec<(Range<usize>, String)> {
+  let mut starts = HashMap::<&str, &Node>::new();
+  let mut bookmarks = Vec::new();
+  for node in nodes {
+    let Some(id) = node.id.as_deref() else { continue };
+    match node.name.as_slice() {
+      b"w:bookmarkStart" => {
+        starts.insert(id, node);
+      }
+      b"w:bookmarkEnd" => {
+        let Some(start) = starts.remove(id) else { continue };
+        let range = start.range.start..node.range.start;
+        bookmarks.extend(start.attribute(input, "w:name").map(|name| (range, name)));
+      }
+      _ => {}
+    }
+  }
+  bookmarks.sort_by_key(|(range, _)| range.start);
+  bookmarks
+}
+
+/// Runs holding the `begin` and the `end` characters of the form field.
+fn field_runs(input: &[u8], nodes: &[Node], data: usize) -> Option<(usize, usize)> {
+  let first = nodes[nodes[data].parent?].parent.filter(|&it| nodes[it].name == b"w:r")?;
+  let siblings = &nodes[nodes[first].parent?].children;
+  let at = siblings.iter().position(|&it| it == first)?;
+  let last = siblings[at..].iter().copied().find(|&run| {
+    let character = child(nodes, run, b"w:fldChar");
+    character
+      .and_then(|it| nodes[it].attribute(input, "w:fldCharType"))
+      .is_some_and(|it| it == "end")
+  })?;
+  Some((first, last))
+}
+
+/// Sets `<w14:checked>` of the control and the glyph it shows.
+fn tick_control(
+  input: &[u8],
+  nodes: &[Node],
+  sdt: usize,
+  checkbox: usize,
+  checked: bool,
+) -> Vec<Edit> {
+  let mut edits = Vec::new();
+  let flag = if checked { "1" } else { "0" };
+  match child(nodes, checkbox, b"w14:checked") {
+    Some(it) => edits.extend(set_attribute(input, &nodes[it], "w14:val", flag)),
+    None => {
+      let node = &nodes[checkbox];
+      let flag = format!(r#"<w14:checked w14:val="{flag}"/>"#);
+      edits.push(match input[node.range.clone()].ends_with(b"/>") {
+        true => (node.range.clone(), format!("<w14:checkbox>{flag}</w14:checkbox>").into_bytes()),
+        false => (node.content.start..node.content.start, flag.into_bytes()),
+      });
+    }
+  }
+  let properties = nodes[checkbox].parent.unwrap_or_default();
+  if let Some(it) = child(nodes, properties, b"w:showingPlcHdr") {
+    edits.push((nodes[it].range.clone(), Vec::new()));
+  }
+
+  let state =
+    child(nodes, checkbox, if checked { b"w14:checkedState" } else { b"w14:uncheckedState" });
+  let code = state.and_then(|it| nodes[it].attribute(input, "w14:val"));
+  let glyph = code.and_then(|it| u32::from_str_radix(&it, 16).ok()).and_then(char::from_u32);
+  let glyph = glyph.unwrap_or(if checked { CHECKED } else { UNCHECKED });
+
+  let content = child(nodes, sdt, b"w:sdtContent");
+  let text = content.and_then(|it| descendant(nodes, it, b"w:t"));
+  edits.extend(text.map(|it| (nodes[it].content.clone(), glyph.to_string().into_bytes())));
+  edits
+}
+
+/// Sets `<w:default>` of the form field, dropping `<w:checked>` which overrides it.
+fn tick_field(input: &[u8], nodes: &[Node], checkbox: usize, checked: bool) -> Vec<Edit> {
+  let flag = if checked { "1" } else { "0" };
+  let mut edits = Vec::new();
+  match child(nodes, checkbox, b"w:default") {
+    Some(it) => edits.extend(set_attribute(input, &nodes[it], "w:val", flag)),
+    None => {
+      let at = nodes[checkbox].content.end;
+      edits.push((at..at, format!(r#"<w:default w:val="{flag}"/>"#).into_bytes()));
+    }
+  }
+  if let Some(it) = child(nodes, checkbox, b"w:checked") {
+    edits.push((nodes[it].range.clone(), Vec::new()));
+  }
+  edits
+}
+
+fn descendant(nodes: &[Node], node: usize, name: &[u8]) -> Option<usize> {
+  nodes[node].children.iter().find_map(|&it| match nodes[it].name == name {
+    true => Some(it),
+    false => descendant(nodes, it, name),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use indoc::indoc;
+
+  use super::*;
+
+  fn run(input: &str) -> String {
+    let checkboxes = Checkboxes { open_bracket: "{", close_bracket: "}" };
+    let lookup = |name: &str| match name {
+      "agree" | "Paid" => Some(true),
+      "spam" => Some(false),
+      _ => None,
+    };
+    String::from_utf8(checkboxes.transform_stream(input, Vec::new(), lookup).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn ticks_content_controls() {
+    insta::assert_snapshot!(run(indoc! {r#"
+      <w:p><w:sdt><w:sdtPr><w:tag w:val="agree"/><w14:checkbox><w14:checked w14:val="0"/><w14:checkedState w14:val="2611" w14:font="MS Gothic"/><w14:uncheckedState w14:val="2610" w14:font="MS Gothic"/></w14:checkbox></w:sdtPr><w:sdtContent><w:r><w:t>☐</w:t></w:r></w:sdtContent></w:sdt></w:p>
+      <w:p><w:r><w:t>{spam}</w:t></w:r><w:sdt><w:sdtPr><w14:checkbox/></w:sdtPr><w:sdtContent><w:r><w:t>☒</w:t></w:r></w:sdtContent></w:sdt><w:r><w:t> Send me offers</w:t></w:r></w:p>
+    "#}), @r###"
+      <w:p><w:sdt><w:sdtPr><w:tag w:val="agree"/><w14:checkbox><w14:checked w14:val="1"/><w14:checkedState w14:val="2611" w14:font="MS Gothic"/><w14:uncheckedState w14:val="2610" w14:font="MS Gothic"/></w14:checkbox></w:sdtPr><w:sdtContent><w:r><w:t>☑</w:t></w:r></w:sdtContent></w:sdt></w:p>
+      <w:p><w:sdt><w:sdtPr><w14:checkbox><w14:checked w14:val="0"/></w14:checkbox></w:sdtPr><w:sdtContent><w:r><w:t>☐</w:t></w:r></w:sdtContent></w:sdt><w:r><w:t> Send me offers</w:t></w:r></w:p>
+    "###);
+  }
+
+  #[test]
+  fn ticks_form_fields() {
+    insta::assert_snapshot!(run(indoc! {r#"
+      <w:p><w:r><w:fldChar w:fldCharType="begin"><w:ffData><w:name w:val="Check1"/><w:checkBox><w:sizeAuto/><w:default w:val="0"/></w:checkBox></w:ffData></w:fldChar></w:r><w:r><w:instrText> FORMCHECKBOX </w:instrText></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r><w:r><w:t>{ Paid }</w:t></w:r></w:p>
+      <w:p><w:bookmarkStart w:id="0" w:name="agree"/><w:r><w:fldChar w:fldCharType="begin"><w:ffData><w:name w:val="Check2"/><w:checkBox><w:default w:val="0"/><w:checked w:val="0"/></w:checkBox></w:ffData></w:fldChar></w:r><w:r><w:instrText> FORMCHECKBOX </w:instrText></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r><w:bookmarkEnd w:id="0"/></w:p>
+    "#}), @r###"
+      <w:p><w:r><w:fldChar w:fldCharType="begin"><w:ffData><w:name w:val="Check1"/><w:checkBox><w:sizeAuto/><w:default w:val="1"/></w:checkBox></w:ffData></w:fldChar></w:r><w:r><w:instrText> FORMCHECKBOX </w:instrText></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r></w:p>
+      <w:p><w:bookmarkStart w:id="0" w:name="agree"/><w:r><w:fldChar w:fldCharType="begin"><w:ffData><w:name w:val="Check2"/><w:checkBox><w:default w:val="1"/></w:checkBox></w:ffData></w:fldChar></w:r><w:r><w:instrText> FORMCHECKBOX </w:instrText></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r><w:bookmarkEnd w:id="0"/></w:p>
+    "###);
+  }
+
+  #[test]
+  fn lists_bookmarks_by_starts() {
+    let input = r#"<w:p><w:bookmarkStart w:id="1" w:name="outer"/><w:bookmarkStart w:id="2" w:name="inner"/><w:bookmarkEnd w:id="2"/><w:bookmarkEnd w:id="1"/><w:bookmarkEnd w:id="3"/></w:p>"#;
+    let nodes = tree::read(input.as_bytes()).unwrap();
+    let bookmarks = bookmarks(input.as_bytes(), &nodes);
+    let names = bookmarks.iter().map(|(_, name)| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["outer", "inner"]);
+    assert!(bookmarks[0].0.contains(&bookmarks[1].0.start));
+  }
+}
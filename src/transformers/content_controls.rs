@@ -0,0 +1,340 @@
+use std::collections::HashSet;
+use std::io;
+
+use quick_xml::escape::escape;
+
+use crate::transformers::find_and_replace::{inherit_styles, Value};
+use crate::transformers::tree::{self, apply_edits, child, set_attribute, Edit, Node};
+use crate::transformers::TransformerError;
+
+/// Content of a control, see [ContentControls].
+#[derive(Debug, Clone)]
+pub enum Fill {
+  /// Replaces the content of a plain text, rich text, date picker, dropdown or combo box control.
+  Value(Value),
+  /// Repeats the first item of a repeating section control, filling its inner controls.
+  Items(Vec<Vec<(String, Value)>>),
+}
+
+/// Fills content controls, `<w:sdt>` elements found by their `<w:tag>` or `<w:alias>`.
+///
+/// ```xml
+/// <w:sdt>
+///   <w:sdtPr><w:alias w:val="Client"/><w:tag w:val="client"/><w:showingPlcHdr/><w:text/></w:sdtPr>
+///   <w:sdtContent><w:r><w:t>Click here to enter text.</w:t></w:r></w:sdtContent>
+/// </w:sdt>
+/// ```
+///
+/// The content is replaced with a run formatted like the control, the placeholder flag
+/// `<w:showingPlcHdr>` is cleared. Dates are set as `w:fullDate` if given as `YYYY-MM-DD`,
+/// dropdowns select the list item having the value or the display text.
+#[derive(Debug, Clone, Copy)]
+pub struct ContentControls<'c> {
+  /// Contents by tags or aliases, which are case-insensitive.
+  pub values: &'c [(String, Fill)],
+}
+
+impl ContentControls<'_> {
+  /// Tags or aliases of the filled controls are put into `matched`.
+  pub fn transform_stream<In: AsRef<[u8]>, Out: io::Write>(
+    &self,
+    input: In,
+    output: Out,
+    matched: &mut HashSet<String>,
+  ) -> Result<Out, TransformerError> {
+    let input = input.as_ref();
+    let nodes = tree::read(input)?;
+
+    let mut edits = Vec::<Edit>::new();
+    let mut filled_until = 0;
+    // ids given to the controls of repeated items, following the existing ones
+    let ids = nodes.iter().filter(|it| is_control_id(&nodes, it));
+    let ids = ids.filter_map(|it| it.attribute(input, "w:val")?.parse::<i64>().ok());
+    let mut next_id = ids.max().map_or(1, |it| it + 1);
+    for (idx, node) in nodes.iter().enumerate() {
+      if node.name != b"w:sdt" || node.range.start < filled_until {
+        continue;
+      }
+      let (Some(properties), Some(content)) =
+        (child(&nodes, idx, b"w:sdtPr"), child(&nodes, idx, b"w:sdtContent"))
+      else {
+        continue;
+      };
+      let names = [&b"w:tag"[..], b"w:alias"].map(|name| {
+        child(&nodes, properties, name).and_then(|it| nodes[it].attribute(input, "w:val"))
+      });
+      let names = names.iter().flatten();
+      let Some((key, fill)) = self
+        .values
+        .iter()
+        .find(|(key, _)| names.clone().any(|name| key.eq_ignore_ascii_case(name)))
+      else {
+        continue;
+      };
+
+      let control = Control { input, nodes: &nodes, properties, content };
+      let filled = match fill {
+        Fill::Value(value) => control.fill(value),
+        Fill::Items(items) => control.repeat(items, &mut next_id)?,
+      };
+      let Some(filled) = filled else { continue };
+      matched.insert(key.clone());
+      edits.extend(filled);
+      filled_until = node.range.end;
+    }
+
+    Ok(apply_edits(input, edits, output)?)
+  }
+}
+
+/// A control being filled, `<w:sdtPr>` and `<w:sdtContent>` nodes of the `<w:sdt>`.
+struct Control<'n> {
+  input: &'n [u8],
+  nodes: &'n [Node],
+  properties: usize,
+  content: usize,
+}
+
+impl Control<'_> {
+  fn fill(&self, value: &Value) -> Option<Vec<Edit>> {
+    let (input, nodes) = (self.input, self.nodes);
+    let mut edits = self.clear_placeholder();
+
+    // the first paragraph inside block-level controls, the enclosing one for inline controls
+    let paragraph = match child(nodes, self.content, b"w:p") {
+      Some(paragraph) => Some(paragraph),
+      None if nodes[self.content].children.iter().any(|&it| is_table_part(&nodes[it])) => {
+        return None
+      }
+      None => ancestor(nodes, self.content, b"w:p"),
+    };
+    let is_block = paragraph.is_some_and(|it| nodes[it].parent == Some(self.content));
+
+    let kind = nodes[self.properties].children.iter().copied().find(|&it| {
+      matches!(
+        nodes[it].name.as_slice(),
+        b"w:text" | b"w:date" | b"w:dropDownList" | b"w:comboBox" | b"w:richText" | b"w14:checkbox"
+      )
+    });
+    let text = value.text();
+    let run = |xml: &str| format!(r#"<w:r><w:t xml:space="preserve">{xml}</w:t></w:r>"#);
+    let mut content = match kind.map(|it| (it, nodes[it].name.as_slice())) {
+      Some((_, b"w14:checkbox")) => return None,
+      Some((_, b"w:text")) => run(&escape(text.as_str())),
+      Some((date, b"w:date")) => {
+        if is_date(&text) {
+          let full_date = format!("{}T00:00:00Z", &text[..10]);
+          edits.push(set_attribute(input, &nodes[date], "w:fullDate", &full_date)?);
+        }
+        run(&escape(text.as_str()))
+      }
+      Some((list, b"w:dropDownList" | b"w:comboBox")) => {
+        let items = nodes[list].children.iter().map(|&it| &nodes[it]);
+        let items = items.filter(|it| it.name == b"w:listItem").map(|it| {
+          let value = it.attribute(input, "w:value").unwrap_or_default();
+          let display = it.attribute(input, "w:displayText").unwrap_or_else(|| value.clone());
+          (value, display)
+        });
+        let mut items = items.collect::<Vec<_>>().into_iter();
+        let display = match items.find(|(value, display)| *value == text || *display == text) {
+          Some((value, display)) => {
+            edits.push(set_attribute(input, &nodes[list], "w:lastValue", &value)?);
+            display
+          }
+          None => text,
+        };
+        run(&escape(display.as_str()))
+      }
+      _ if is_block => run(&value.xml),
+      // paragraphs of block values can't be put inside a paragraph
+      _ => value.to_runs(),
+    };
+    if is_block {
+      content = format!("<w:p>{content}</w:p>");
+    }
+    let style = |node: Option<usize>| node.map_or(&[][..], |it| &input[nodes[it].range.clone()]);
+    let paragraph_style = style(paragraph.and_then(|it| child(nodes, it, b"w:pPr")));
+    let content = inherit_styles(content.as_bytes(), paragraph_style, style(self.run_style()));
+    edits.push((nodes[self.content].content.clone(), content.into_owned()));
+    Some(edits)
+  }
+
+  /// Repeats the first item of the section per each of `items`, filling its inner controls.
+  /// Copies of the controls are given new ids, starting from `next_id`.
+  fn repeat(
+    &self,
+    items: &[Vec<(String, Value)>],
+    next_id: &mut i64,
+  ) -> Result<Option<Vec<Edit>>, TransformerError> {
+    let nodes = self.nodes;
+    if child(nodes, self.properties, b"w15:repeatingSection").is_none() {
+      return Ok(None);
+    }
+    let sections = nodes[self.content].children.iter().copied().filter(|&it| {
+      child(nodes, it, b"w:sdtPr")
+        .and_then(|properties| child(nodes, properties, b"w15:repeatingSectionItem"))
+        .is_some()
+    });
+    let sections = sections.collect::<Vec<_>>();
+    let (Some(&first), Some(&last)) = (sections.first(), sections.last()) else { return Ok(None) };
+
+    let template = &self.input[nodes[first].range.clone()];
+    let mut repeated = Vec::new();
+    for item in items {
+      let values = item.iter().map(|(key, value)| (key.clone(), Fill::Value(value.clone())));
+      let values = values.collect::<Vec<_>>();
+      let controls = ContentControls { values: &values };
+      let copy = controls.transform_stream(template, Vec::new(), &mut HashSet::new())?;
+
+      let nodes = tree::read(&copy)?;
+      let mut ids = Vec::new();
+      for node in nodes.iter().filter(|it| is_control_id(&nodes, it)) {
+        ids.extend(set_attribute(&copy, node, "w:val", &next_id.to_string()));
+        *next_id += 1;
+      }
+      repeated = apply_edits(&copy, ids, repeated)?;
+    }
+
+    let mut edits = self.clear_placeholder();
+    edits.push((nodes[first].range.start..nodes[last].range.end, repeated));
+    Ok(Some(edits))
+  }
+
+  /// Removes `<w:showingPlcHdr>`, so the content is no longer shown as a placeholder.
+  fn clear_placeholder(&self) -> Vec<Edit> {
+    let flag = child(self.nodes, self.properties, b"w:showingPlcHdr");
+    flag.map(|it| (self.nodes[it].range.clone(), Vec::new())).into_iter().collect()
+  }
+
+  /// Formatting of the control, or of its first run unless it shows the placeholder.
+  fn run_style(&self) -> Option<usize> {
+    let nodes = self.nodes;
+    if let Some(style) = child(nodes, self.properties, b"w:rPr") {
+      return Some(style);
+    }
+    if child(nodes, self.properties, b"w:showingPlcHdr").is_some() {
+      return None;
+    }
+    let content = &nodes[self.content];
+    let run = (content.children.first().copied()).and_then(|it| match nodes[it].name.as_slice() {
+      b"w:p" => child(nodes, it, b"w:r"),
+      b"w:r" => Some(it),
+      _ => None,
+    });
+    run.and_then(|it| child(nodes, it, b"w:rPr"))
+  }
+}
+
+fn ancestor(nodes: &[Node], node: usize, name: &[u8]) -> Option<usize> {
+  let mut parent = nodes[node].parent;
+  while let Some(idx) = parent {
+    if nodes[idx].name == name {
+      return Some(idx);
+    }
+    parent = nodes[idx].parent;
+  }
+  None
+}
+
+/// Returns true for `<w:id>` of a control, which must be unique in the document.
+fn is_control_id(nodes: &[Node], node: &Node) -> bool {
+  node.name == b"w:id" && node.parent.is_some_and(|it| nodes[it].name == b"w:sdtPr")
+}
+
+/// Tables, rows and cells, controls holding them are not filled.
+fn is_table_part(node: &Node) -> bool {
+  matches!(node.name.as_slice(), b"w:tr" | b"w:tc" | b"w:tbl")
+}
+
+/// Returns true if the text starts with a `YYYY-MM-DD` date.
+fn is_date(text: &str) -> bool {
+  let bytes = text.as_bytes();
+  bytes.len() >= 10
+    && bytes[..10].iter().enumerate().all(|(idx, byte)| match idx {
+      4 | 7 => *byte == b'-',
+      _ => byte.is_ascii_digit(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use indoc::indoc;
+
+  use super::*;
+
+  fn run(input: &str, values: &[(&str, Fill)]) -> String {
+    let values =
+      values.iter().map(|(key, fill)| (key.to_string(), fill.clone())).collect::<Vec<_>>();
+    let mut matched = HashSet::new();
+    let buf = ContentControls { values: &values }.transform_stream(input, Vec::new(), &mut matched);
+    assert_eq!(matched.len(), values.len());
+    String::from_utf8(buf.unwrap()).unwrap()
+  }
+
+  fn text(text: &str) -> Fill {
+    Fill::Value(Value::from_text(text))
+  }
+
+  #[test]
+  fn fills_text_controls() {
+    let input = indoc! {r#"
+      <w:p><w:r><w:t>Dear </w:t></w:r><w:sdt><w:sdtPr><w:rPr><w:b/></w:rPr><w:alias w:val="Client"/><w:tag w:val="client"/><w:showingPlcHdr/><w:text/></w:sdtPr><w:sdtContent><w:r><w:rPr><w:rStyle w:val="PlaceholderText"/></w:rPr><w:t>Click here to enter text.</w:t></w:r></w:sdtContent></w:sdt></w:p>
+      <w:sdt><w:sdtPr><w:tag w:val="notes"/></w:sdtPr><w:sdtContent><w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t>Notes</w:t></w:r></w:p></w:sdtContent></w:sdt>
+    "#};
+    insta::assert_snapshot!(run(input, &[("CLIENT", text("Ann & Co")), ("notes", text("Paid"))]), @r###"
+      <w:p><w:r><w:t>Dear </w:t></w:r><w:sdt><w:sdtPr><w:rPr><w:b/></w:rPr><w:alias w:val="Client"/><w:tag w:val="client"/><w:text/></w:sdtPr><w:sdtContent><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve">Ann &amp; Co</w:t></w:r></w:sdtContent></w:sdt></w:p>
+      <w:sdt><w:sdtPr><w:tag w:val="notes"/></w:sdtPr><w:sdtContent><w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">Paid</w:t></w:r></w:p></w:sdtContent></w:sdt>
+    "###);
+  }
+
+  #[test]
+  fn joins_paragraphs_of_block_values_in_inline_controls() {
+    let input = indoc! {r#"
+      <w:p><w:r><w:t>Notes: </w:t></w:r><w:sdt><w:sdtPr><w:tag w:val="notes"/><w:richText/></w:sdtPr><w:sdtContent><w:r><w:t>Notes</w:t></w:r></w:sdtContent></w:sdt></w:p>
+    "#};
+    let notes = Fill::Value(Value::from_markdown("**Paid**\n\n- in full"));
+    insta::assert_snapshot!(run(input, &[("notes", notes)]), @r###"
+      <w:p><w:r><w:t>Notes: </w:t></w:r><w:sdt><w:sdtPr><w:tag w:val="notes"/><w:richText/></w:sdtPr><w:sdtContent><w:r><w:rPr><w:b/><w:bCs/></w:rPr><w:t xml:space="preserve">Paid</w:t></w:r><w:r><w:br/></w:r><w:r><w:t xml:space="preserve">in full</w:t></w:r></w:sdtContent></w:sdt></w:p>
+    "###);
+  }
+
+  #[test]
+  fn picks_dates_and_list_items() {
+    let input = indoc! {r#"
+      <w:p><w:sdt><w:sdtPr><w:tag w:val="due"/><w:date><w:dateFormat w:val="dd.MM.yyyy"/></w:date></w:sdtPr><w:sdtContent><w:r><w:t>Date</w:t></w:r></w:sdtContent></w:sdt></w:p>
+      <w:p><w:sdt><w:sdtPr><w:tag w:val="size"/><w:dropDownList><w:listItem w:displayText="Small" w:value="S"/><w:listItem w:displayText="Large" w:value="L"/></w:dropDownList></w:sdtPr><w:sdtContent><w:r><w:t>Small</w:t></w:r></w:sdtContent></w:sdt></w:p>
+    "#};
+    insta::assert_snapshot!(run(input, &[("due", text("2024-05-28")), ("size", text("L"))]), @r###"
+      <w:p><w:sdt><w:sdtPr><w:tag w:val="due"/><w:date w:fullDate="2024-05-28T00:00:00Z"><w:dateFormat w:val="dd.MM.yyyy"/></w:date></w:sdtPr><w:sdtContent><w:r><w:t xml:space="preserve">2024-05-28</w:t></w:r></w:sdtContent></w:sdt></w:p>
+      <w:p><w:sdt><w:sdtPr><w:tag w:val="size"/><w:dropDownList w:lastValue="L"><w:listItem w:displayText="Small" w:value="S"/><w:listItem w:displayText="Large" w:value="L"/></w:dropDownList></w:sdtPr><w:sdtContent><w:r><w:t xml:space="preserve">Large</w:t></w:r></w:sdtContent></w:sdt></w:p>
+    "###);
+  }
+
+  #[test]
+  fn repeats_sections() {
+    let input = indoc! {r#"
+      <w:sdt><w:sdtPr><w:tag w:val="items"/><w15:repeatingSection/></w:sdtPr><w:sdtContent><w:sdt><w:sdtPr><w15:repeatingSectionItem/></w:sdtPr><w:sdtContent><w:p><w:sdt><w:sdtPr><w:tag w:val="name"/><w:text/></w:sdtPr><w:sdtContent><w:r><w:t>Name</w:t></w:r></w:sdtContent></w:sdt></w:p></w:sdtContent></w:sdt></w:sdtContent></w:sdt>
+    "#};
+    let items = vec![
+      vec![("name".to_owned(), Value::from_text("Tea"))],
+      vec![("name".to_owned(), Value::from_text("Milk"))],
+    ];
+    insta::assert_snapshot!(run(input, &[("items", Fill::Items(items))]), @r###"
+      <w:sdt><w:sdtPr><w:tag w:val="items"/><w15:repeatingSection/></w:sdtPr><w:sdtContent><w:sdt><w:sdtPr><w15:repeatingSectionItem/></w:sdtPr><w:sdtContent><w:p><w:sdt><w:sdtPr><w:tag w:val="name"/><w:text/></w:sdtPr><w:sdtContent><w:r><w:t xml:space="preserve">Tea</w:t></w:r></w:sdtContent></w:sdt></w:p></w:sdtContent></w:sdt><w:sdt><w:sdtPr><w15:repeatingSectionItem/></w:sdtPr><w:sdtContent><w:p><w:sdt><w:sdtPr><w:tag w:val="name"/><w:text/></w:sdtPr><w:sdtContent><w:r><w:t xml:space="preserve">Milk</w:t></w:r></w:sdtContent></w:sdt></w:p></w:sdtContent></w:sdt></w:sdtContent></w:sdt>
+    "###);
+  }
+
+  #[test]
+  fn gives_copies_new_ids() {
+    let input = indoc! {r#"
+      <w:sdt><w:sdtPr><w:id w:val="7"/><w:tag w:val="items"/><w15:repeatingSection/></w:sdtPr><w:sdtContent><w:sdt><w:sdtPr><w:id w:val="-20"/><w15:repeatingSectionItem/></w:sdtPr><w:sdtContent><w:p><w:sdt><w:sdtPr><w:id w:val="3"/><w:tag w:val="name"/><w:text/></w:sdtPr><w:sdtContent><w:r><w:t>Name</w:t></w:r></w:sdtContent></w:sdt></w:p></w:sdtContent></w:sdt></w:sdtContent></w:sdt>
+    "#};
+    let items = vec![
+      vec![("name".to_owned(), Value::from_text("Tea"))],
+      vec![("name".to_owned(), Value::from_text("Milk"))],
+    ];
+    insta::assert_snapshot!(run(input, &[("items", Fill::Items(items))]), @r###"
+      <w:sdt><w:sdtPr><w:id w:val="7"/><w:tag w:val="items"/><w15:repeatingSection/></w:sdtPr><w:sdtContent><w:sdt><w:sdtPr><w:id w:val="8"/><w15:repeatingSectionItem/></w:sdtPr><w:sdtContent><w:p><w:sdt><w:sdtPr><w:id w:val="9"/><w:tag w:val="name"/><w:text/></w:sdtPr><w:sdtContent><w:r><w:t xml:space="preserve">Tea</w:t></w:r></w:sdtContent></w:sdt></w:p></w:sdtContent></w:sdt><w:sdt><w:sdtPr><w:id w:val="10"/><w15:repeatingSectionItem/></w:sdtPr><w:sdtContent><w:p><w:sdt><w:sdtPr><w:id w:val="11"/><w:tag w:val="name"/><w:text/></w:sdtPr><w:sdtContent><w:r><w:t xml:space="preserve">Milk</w:t></w:r></w:sdtContent></w:sdt></w:p></w:sdtContent></w:sdt></w:sdtContent></w:sdt>
+    "###);
+  }
+}
@@ -0,0 +1,150 @@
+use std::ops::Range;
+
+use quick_xml::events::{BytesStart, Event};
+
+/// A picture `<w:drawing>` met in a part.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Drawing {
+  /// Alt text, `descr` attribute of `<wp:docPr>`.
+  pub description: Option<String>,
+  /// `title` attribute of `<wp:docPr>`.
+  pub title: Option<String>,
+  /// `name` attribute of `<wp:docPr>`, like `Picture 1`.
+  pub name: Option<String>,
+  /// Relationship id of the image, `r:embed` attribute of `<a:blip>`.
+  pub embed: Option<String>,
+  /// Sizes of the frame `<wp:extent>` and of the picture `<a:ext>`.
+  pub extents: Vec<Extent>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Extent {
+  /// Bytes of the tag.
+  pub range: Range<usize>,
+  /// Width in EMUs.
+  pub cx: u64,
+  /// Height in EMUs.
+  pub cy: u64,
+}
+
+impl Drawing {
+  /// Returns true if the alt text, the title or the name equals to the `text`.
+  pub fn is_labeled(&self, text: &str) -> bool {
+    [&self.description, &self.title, &self.name].iter().any(|it| it.as_deref() == Some(text))
+  }
+
+  /// Size of the frame in EMUs.
+  pub fn size(&self) -> Option<(u64, u64)> {
+    self.extents.first().map(|it| (it.cx, it.cy))
+  }
+}
+
+/// Collects pictures of the part.
+pub fn read(input: &[u8]) -> Result<Vec<Drawing>, quick_xml::Error> {
+  let mut reader = quick_xml::Reader::from_reader(input);
+  let mut drawings = Vec::new();
+  let mut drawing = None::<Drawing>;
+  let mut in_transform = false;
+
+  loop {
+    let start = reader.buffer_position() as usize;
+    let event = reader.read_event()?;
+    let end = reader.buffer_position() as usize;
+
+    match event {
+      Event::Start(ref tag) | Event::Empty(ref tag) => {
+        let name = tag.name();
+        let Some(ref mut drawing) = drawing else {
+          if name.as_ref() == b"w:drawing" && matches!(event, Event::Start(_)) {
+            drawing = Some(Drawing::default());
+          }
+          continue;
+        };
+        match name.as_ref() {
+          b"wp:docPr" => {
+            drawing.description = attribute(tag, b"descr");
+            drawing.title = attribute(tag, b"title");
+            drawing.name = attribute(tag, b"name");
+          }
+          b"a:blip" => drawing.embed = attribute(tag, b"r:embed"),
+          b"a:xfrm" => in_transform = matches!(event, Event::Start(_)),
+          b"wp:extent" | b"a:ext" if name.as_ref() == b"wp:extent" || in_transform => {
+            let size = attribute(tag, b"cx").zip(attribute(tag, b"cy"));
+            if let Some((cx, cy)) = size.and_then(|(cx, cy)| cx.parse().ok().zip(cy.parse().ok())) {
+              drawing.extents.push(Extent { range: start..end, cx, cy });
+            }
+          }
+          _ => {}
+        }
+      }
+      Event::End(tag) => match tag.name().as_ref() {
+        b"a:xfrm" => in_transform = false,
+        b"w:drawing" => drawings.extend(drawing.take()),
+        _ => {}
+      },
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+
+  Ok(drawings)
+}
+
+/// Rewrites `cx` and `cy` attributes of the extents. Ranges must not overlap.
+pub fn resize(input: &[u8], mut extents: Vec<Extent>) -> Vec<u8> {
+  extents.sort_by_key(|it| it.range.start);
+  let mut output = Vec::with_capacity(input.len());
+  let mut position = 0;
+
+  for Extent { range, cx, cy } in extents {
+    output.extend_from_slice(&input[position..range.start]);
+    let tag = String::from_utf8_lossy(&input[range.clone()]);
+    let tag = set_attribute(&set_attribute(&tag, "cx", cx), "cy", cy);
+    output.extend_from_slice(tag.as_bytes());
+    position = range.end;
+  }
+
+  output.extend_from_slice(&input[position..]);
+  output
+}
+
+fn attribute(tag: &BytesStart, name: &[u8]) -> Option<String> {
+  let value = tag.try_get_attribute(name).ok()??;
+  value.unescape_value().ok().map(|it| it.into_owned())
+}
+
+/// Replaces a numeric attribute's value inside the tag's markup.
+fn set_attribute(tag: &str, name: &str, value: u64) -> String {
+  let prefix = format!(r#" {name}=""#);
+  let Some(start) = tag.find(&prefix).map(|it| it + prefix.len()) else {
+    return tag.to_owned();
+  };
+  let end = tag[start..].find('"').map_or(tag.len(), |it| start + it);
+  format!("{}{value}{}", &tag[..start], &tag[end..])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const XML: &str = r#"<w:r><w:drawing><wp:inline><wp:extent cx="200" cy="100"/><wp:docPr id="1" name="Picture 1" descr="{logo}"/><a:graphic><pic:pic><pic:blipFill><a:blip r:embed="rId5"><a:extLst><a:ext uri="x"/></a:extLst></a:blip></pic:blipFill><pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="200" cy="100"/></a:xfrm></pic:spPr></pic:pic></a:graphic></wp:inline></w:drawing></w:r>"#;
+
+  #[test]
+  fn reads_drawings() {
+    let drawings = read(XML.as_bytes()).unwrap();
+    assert_eq!(drawings.len(), 1);
+    assert!(drawings[0].is_labeled("{logo}"));
+    assert!(drawings[0].is_labeled("Picture 1"));
+    assert_eq!(drawings[0].embed.as_deref(), Some("rId5"));
+    assert_eq!(drawings[0].size(), Some((200, 100)));
+    assert_eq!(drawings[0].extents.len(), 2);
+  }
+
+  #[test]
+  fn resizes_extents() {
+    let drawings = read(XML.as_bytes()).unwrap();
+    let extents = drawings[0].extents.iter().map(|it| Extent { cx: 200, cy: 50, ..it.clone() });
+    let resized = String::from_utf8(resize(XML.as_bytes(), extents.collect())).unwrap();
+    assert_eq!(resized, XML.replace(r#"cx="200" cy="100""#, r#"cx="200" cy="50""#));
+  }
+}
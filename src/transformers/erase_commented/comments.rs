@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use quick_xml::events::{BytesStart, Event};
+
+use crate::docx_part::DocxPartType;
+
+/// A comment of `word/comments.xml`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Comment {
+  /// `w:id`, referenced by the markers of the commented range.
+  pub id: String,
+  /// Texts of the paragraphs, separated by line breaks.
+  pub text: String,
+  /// `w14:paraId` of the paragraphs, referenced by `commentsExtended.xml` and `commentsIds.xml`.
+  pub paragraph_ids: Vec<String>,
+}
+
+/// Comments to be removed, by the ids they are referenced with in the different parts.
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+  /// `w:id` of the comments.
+  pub ids: HashSet<String>,
+  /// `w14:paraId` of the comments' paragraphs.
+  pub paragraph_ids: HashSet<String>,
+  /// `w16cid:durableId` of the comments.
+  pub durable_ids: HashSet<String>,
+}
+
+impl Selection {
+  /// Selects the comment by all of its ids.
+  pub fn add(&mut self, comment: &Comment) {
+    self.ids.insert(comment.id.clone());
+    self.paragraph_ids.extend(comment.paragraph_ids.iter().cloned());
+  }
+
+  /// Adds replies to the selected comments, and replies to them, which `commentsExtended.xml`
+  /// links to the last paragraphs of their parents by `w15:paraIdParent`.
+  pub fn add_replies(&mut self, comments: &[Comment], xml: &[u8]) -> Result<(), quick_xml::Error> {
+    let mut reader = quick_xml::Reader::from_reader(xml);
+    let mut links = Vec::new();
+    loop {
+      match reader.read_event()? {
+        Event::Start(tag) | Event::Empty(tag) if tag.name().as_ref() == b"w15:commentEx" => {
+          links.push((attribute(&tag, b"w15:paraId"), attribute(&tag, b"w15:paraIdParent")));
+        }
+        Event::Eof => break,
+        _ => {}
+      }
+    }
+
+    loop {
+      let replies = links.iter().filter(|(paragraph, parent)| {
+        self.paragraph_ids.contains(parent) && !self.paragraph_ids.contains(paragraph)
+      });
+      let replies = replies
+        .filter_map(|(paragraph, _)| {
+          comments.iter().find(|it| it.paragraph_ids.contains(paragraph))
+        })
+        .collect::<Vec<_>>();
+      if replies.is_empty() {
+        return Ok(());
+      }
+      replies.into_iter().for_each(|it| self.add(it));
+    }
+  }
+
+  /// Removes the selected comments from `comments.xml`, `commentsExtended.xml`,
+  /// `commentsIds.xml` or `commentsExtensible.xml`.
+  pub fn remove_from(&self, part: DocxPartType, xml: &[u8]) -> Result<Vec<u8>, quick_xml::Error> {
+    match part {
+      DocxPartType::Comments => remove(xml, b"w:comment", b"w:id", &self.ids),
+      DocxPartType::CommentsExtended => {
+        remove(xml, b"w15:commentEx", b"w15:paraId", &self.paragraph_ids)
+      }
+      DocxPartType::CommentsIds => {
+        remove(xml, b"w16cid:commentId", b"w16cid:paraId", &self.paragraph_ids)
+      }
+      DocxPartType::CommentsExtensible => {
+        remove(xml, b"w16cex:commentExtensible", b"w16cex:durableId", &self.durable_ids)
+      }
+      _ => Ok(xml.to_vec()),
+    }
+  }
+}
+
+/// Reads comments of `word/comments.xml`.
+pub fn read(xml: &[u8]) -> Result<Vec<Comment>, quick_xml::Error> {
+  let mut reader = quick_xml::Reader::from_reader(xml);
+  let mut comments = Vec::new();
+  let mut comment = None::<Comment>;
+
+  loop {
+    match reader.read_event()? {
+      Event::Start(tag) | Event::Empty(tag) => match (tag.name().as_ref(), comment.as_mut()) {
+        (b"w:comment", _) => {
+          comment = Some(Comment { id: attribute(&tag, b"w:id"), ..Default::default() });
+        }
+        (b"w:p", Some(comment)) => {
+          if !comment.paragraph_ids.is_empty() || !comment.text.is_empty() {
+            comment.text.push('\n');
+          }
+          comment.paragraph_ids.push(attribute(&tag, b"w14:paraId"));
+        }
+        (b"w:t", Some(comment)) => {
+          let text = reader.read_text(tag.name())?;
+          comment.text += &quick_xml::escape::unescape(&text).map_err(quick_xml::Error::from)?;
+        }
+        _ => {}
+      },
+      Event::End(tag) if tag.name().as_ref() == b"w:comment" => comments.extend(comment.take()),
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+
+  Ok(comments)
+}
+
+/// Values of the `attribute` met in `tag` elements, like `w16cid:durableId` of `<w16cid:commentId>`
+/// having `w16cid:paraId` among `keys`.
+pub fn lookup(
+  xml: &[u8],
+  tag: &[u8],
+  key: &[u8],
+  keys: &HashSet<String>,
+  attribute_name: &[u8],
+) -> Result<HashSet<String>, quick_xml::Error> {
+  let mut reader = quick_xml::Reader::from_reader(xml);
+  let mut values = HashSet::new();
+  loop {
+    match reader.read_event()? {
+      Event::Start(element) | Event::Empty(element)
+        if element.name().as_ref() == tag && keys.contains(&attribute(&element, key)) =>
+      {
+        values.insert(attribute(&element, attribute_name));
+      }
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+  Ok(values)
+}
+
+/// Removes `tag` elements having the `key` attribute among `keys`, like `<w:comment w:id="0">`.
+pub fn remove(
+  xml: &[u8],
+  tag: &[u8],
+  key: &[u8],
+  keys: &HashSet<String>,
+) -> Result<Vec<u8>, quick_xml::Error> {
+  let mut reader = quick_xml::Reader::from_reader(xml);
+  let mut output = Vec::with_capacity(xml.len());
+  let mut reported = 0;
+
+  loop {
+    let position = reader.buffer_position() as usize;
+    let event = reader.read_event()?;
+    match event {
+      Event::Start(ref element) | Event::Empty(ref element) if element.name().as_ref() == tag => {
+        if !keys.contains(&attribute(element, key)) {
+          continue;
+        }
+        if let Event::Start(_) = event {
+          reader.read_to_end(element.name())?;
+        }
+        output.extend_from_slice(&xml[reported..position]);
+        reported = reader.buffer_position() as usize;
+      }
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+
+  output.extend_from_slice(&xml[reported..]);
+  Ok(output)
+}
+
+fn attribute(tag: &BytesStart, name: &[u8]) -> String {
+  let value = tag.try_get_attribute(name).ok().flatten();
+  value.and_then(|it| it.unescape_value().ok().map(|it| it.into_owned())).unwrap_or_default()
+}
@@ -1,218 +1,191 @@
-#![allow(dead_code)]
-
 use std::collections::HashSet;
 use std::io;
 use std::ops::Range;
 
-use quick_xml::events::Event;
+use crate::transformers::tree::{self, apply_edits, cut_between, path, Node};
+use crate::transformers::TransformerError;
 
-pub struct EraseCommentedRangeTransformerBrw<'i> {
-  xml: quick_xml::reader::Reader<&'i [u8]>,
-  #[allow(dead_code)]
-  comment_ids: HashSet<Vec<u8>>,
-  event: Event<'i>,
-  cur: Range<usize>,
-}
+pub mod comments;
 
-impl<'i> EraseCommentedRangeTransformerBrw<'i> {
-  pub fn from_reader(reader: &'i [u8]) -> Self {
-    Self {
-      xml: quick_xml::Reader::from_reader(reader),
-      comment_ids: Default::default(),
-      event: Event::Eof,
-      cur: 0..0,
-    }
-  }
+#[cfg(test)]
+mod tests;
+
+/// Erases everything between `<w:commentRangeStart>` and `<w:commentRangeEnd>` of the comments,
+/// along with the markers and the runs referencing the comments.
+///
+/// The range is cut out of the document tree: elements the range starts in keep their heads,
+/// ones it ends in keep their tails, everything in between is removed, like tables or images.
+/// Paragraphs, table rows and tables left with no content by the cut are removed as a whole,
+/// unless a paragraph holds section properties or is the only paragraph of a table cell.
+///
+/// ```xml
+/// <w:p><w:r><w:t>Keep </w:t></w:r><w:commentRangeStart w:id="0"/><w:r><w:t>this</w:t></w:r></w:p>
+/// <w:p><w:r><w:t>and that</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:t>, too</w:t></w:r></w:p>
+/// ```
+/// Becomes `<w:p><w:r><w:t>Keep </w:t></w:r></w:p><w:p><w:r><w:t>, too</w:t></w:r></w:p>`.
+#[derive(Debug, Clone)]
+pub struct EraseCommented<'c> {
+  /// `w:id` of the comments.
+  pub ids: &'c HashSet<String>,
 }
 
-mod joined_str {
-  pub struct JoinedStr<'a> {
-    prefix: &'static str,
-    infix: &'a str,
-    suffix: &'static str,
-  }
+/// Children which don't make a paragraph visible.
+const BLANK: &[&[u8]] = &[
+  b"w:pPr",
+  b"w:rPr",
+  b"w:bookmarkStart",
+  b"w:bookmarkEnd",
+  b"w:commentRangeStart",
+  b"w:commentRangeEnd",
+  b"w:commentReference",
+  b"w:proofErr",
+  b"w:permStart",
+  b"w:permEnd",
+];
+
+impl EraseCommented<'_> {
+  pub fn transform_stream<In: AsRef<[u8]>, Out: io::Write>(
+    &self,
+    input: In,
+    output: Out,
+  ) -> Result<Out, TransformerError> {
+    let input = input.as_ref();
+    let nodes = tree::read(input)?;
+    let find = |name: &[u8], id: &str| {
+      nodes.iter().position(|it| it.name == name && it.id.as_deref() == Some(id))
+    };
 
-  impl<'a> JoinedStr<'a> {
-    pub fn new(prefix: &'static str, infix: &'a str, suffix: &'static str) -> Self {
-      Self { prefix, infix, suffix }
+    let mut cuts = Vec::new();
+    for id in self.ids {
+      let (Some(start), Some(end)) =
+        (find(b"w:commentRangeStart", id), find(b"w:commentRangeEnd", id))
+      else {
+        continue;
+      };
+      cuts.extend(Self::cut(&nodes, start, end));
+
+      if let Some(reference) = find(b"w:commentReference", id) {
+        cuts.push(reference_range(&nodes, reference));
+      }
     }
-  }
-}
 
-fn prefix(e: &Event) -> &'static [u8] {
-  match e {
-    Event::Start(_) => b"<",
-    Event::End(_) => b"</",
-    Event::Empty(_) => b"<",
-    Event::Text(_) => b"",
-    Event::CData(_) => b"<![CDATA[",
-    Event::Comment(_) => b"<!--",
-    Event::Decl(_) => b"<?",
-    Event::PI(_) => b"<?",
-    Event::DocType(_) => b"<!DOCTYPE ",
-    Event::Eof => b"",
+    let edits = merge(cuts).into_iter().map(|cut| (cut, Vec::new())).collect();
+    Ok(apply_edits(input, edits, output)?)
   }
-}
 
-fn suffix(e: &Event) -> &'static [u8] {
-  match e {
-    Event::Start(_) => b">",
-    Event::End(_) => b">",
-    Event::Empty(_) => b"/>",
-    Event::Text(_) => b"",
-    Event::CData(_) => b"]]>",
-    Event::Comment(_) => b"-->",
-    Event::Decl(_) => b"?>",
-    Event::PI(_) => b"?>",
-    Event::DocType(_) => b">",
-    Event::Eof => b"",
-  }
-}
-
-impl<'i> io::Read for EraseCommentedRangeTransformerBrw<'i> {
-  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-    let mut cur = self.cur.clone();
-
-    if cur.is_empty() {
-      self.event = self.xml.read_event().map_err(io::Error::other)?;
-      cur = 0..(prefix(&self.event).len() + self.event.len() + suffix(&self.event).len());
-    }
+  /// Byte ranges to be removed to erase everything from the `start` marker to the `end` one.
+  fn cut(nodes: &[Node], start: usize, end: usize) -> Vec<Range<usize>> {
+    let (mut from, mut to) = (path(nodes, start), path(nodes, end));
 
-    let prefix = prefix(&self.event);
-    let suffix = suffix(&self.event);
-
-    let count = if cur.start < prefix.len() {
-      let head_size = prefix.len().min(buf.len());
-      let head = cur.start..(cur.start + head_size);
-      let tail = (cur.start + head_size)..cur.end;
-      buf[..head_size].copy_from_slice(&prefix[head]);
-      self.cur = tail;
-      head_size
-    } else if cur.start < prefix.len() + self.event.len() {
-      let head_size = self.event.len().min(buf.len());
-      let head = (cur.start - prefix.len())..(cur.start - prefix.len() + head_size);
-      let tail = (cur.start + head_size)..cur.end;
-      buf[..head_size].copy_from_slice(&self.event[head]);
-      self.cur = tail;
-      head_size
-    } else {
-      let head_size = suffix.len().min(buf.len());
-      let head = (cur.start - prefix.len() - self.event.len())
-        ..(cur.start - prefix.len() - self.event.len() + head_size);
-      let tail = (cur.start + head_size)..cur.end;
-      buf[..head_size].copy_from_slice(&suffix[head]);
-      self.cur = tail;
-      head_size
+    // paragraphs, rows and tables left blank by the cut are removed entirely
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let blank = |path: &[usize], at: usize, side| {
+      is_removable(nodes, path[at]) && is_blank(nodes, &path[at..], side)
     };
+    if let Some(at) =
+      (0..common).find(|&at| blank(&from, at, Side::Before) && blank(&to, at, Side::After))
+    {
+      return vec![nodes[from[at]].range.clone()];
+    }
+    if let Some(at) = (common..from.len()).find(|&at| blank(&from, at, Side::Before)) {
+      from.truncate(at + 1);
+    }
+    if let Some(at) = (common..to.len()).find(|&at| blank(&to, at, Side::After)) {
+      to.truncate(at + 1);
+    }
 
-    Ok(count)
+    cut_between(nodes, &from, &to, true)
   }
 }
 
-pub struct EraseCommentedRangeTransformer<R> {
-  input: R,
-  xml: quick_xml::reader::Reader<R>,
-  #[allow(dead_code)]
-  comment_ids: HashSet<Vec<u8>>,
-  offset: usize,
-  cur: Range<usize>,
+/// Removes markers and references of all the comments, keeping the commented content.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripComments;
+
+impl StripComments {
+  pub fn transform_stream<In: AsRef<[u8]>, Out: io::Write>(
+    &self,
+    input: In,
+    output: Out,
+  ) -> Result<Out, TransformerError> {
+    let input = input.as_ref();
+    let nodes = tree::read(input)?;
+    let cuts = nodes.iter().enumerate().filter_map(|(idx, node)| match node.name.as_slice() {
+      b"w:commentRangeStart" | b"w:commentRangeEnd" => Some(node.range.clone()),
+      b"w:commentReference" => Some(reference_range(&nodes, idx)),
+      _ => None,
+    });
+    let edits = cuts.map(|cut| (cut, Vec::new())).collect();
+    Ok(apply_edits(input, edits, output)?)
+  }
 }
 
-impl<R: Clone> EraseCommentedRangeTransformer<R> {
-  pub fn from_reader(reader: R) -> Self {
-    Self {
-      input: reader.clone(),
-      xml: quick_xml::Reader::from_reader(reader),
-      comment_ids: Default::default(),
-      offset: 0,
-      cur: 0..0,
+/// Joins overlapping cuts, so the union of overlapping comments is erased.
+fn merge(mut cuts: Vec<Range<usize>>) -> Vec<Range<usize>> {
+  cuts.sort_by_key(|it| it.start);
+  let mut merged = Vec::<Range<usize>>::with_capacity(cuts.len());
+  for cut in cuts {
+    match merged.last_mut() {
+      Some(last) if cut.start < last.end => last.end = last.end.max(cut.end),
+      _ => merged.push(cut),
     }
   }
+  merged
+}
 
-  // fn has_attribute_in_block_list(&self, attrs: Attributes) -> bool {
-  //   attrs
-  //     .flatten()
-  //     .any(|attr| attr.key.as_ref() == b"w:id" && self.comment_ids.contains(attr.value.as_ref()))
-  // }
+/// The comment reference, or its whole run if the run holds nothing else visible.
+fn reference_range(nodes: &[Node], reference: usize) -> Range<usize> {
+  let run = nodes[reference].parent.filter(|&run| !is_visible(nodes, run));
+  nodes[run.unwrap_or(reference)].range.clone()
 }
 
-impl<'i> io::Read for EraseCommentedRangeTransformer<&'i [u8]> {
-  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-    let mut cur = self.cur.clone();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+  Before,
+  After,
+}
 
-    if cur.is_empty() {
-      let _event = self.xml.read_event().map_err(io::Error::other)?;
-      cur = self.offset..self.xml.buffer_position() as usize;
-      self.offset = cur.end;
-    }
+/// Returns true if nothing visible precedes (or follows) the last node of the `path`
+/// inside the first one.
+fn is_blank(nodes: &[Node], path: &[usize], side: Side) -> bool {
+  path.windows(2).all(|pair| {
+    let siblings = &nodes[pair[0]].children;
+    let at = siblings.iter().position(|&it| it == pair[1]).unwrap_or_default();
+    let mut siblings = match side {
+      Side::Before => siblings[..at].iter(),
+      Side::After => siblings[at + 1..].iter(),
+    };
+    !siblings.any(|&it| is_visible(nodes, it))
+  })
+}
 
-    let head_size = cur.len().min(buf.len());
-    let head = cur.start..(cur.start + head_size);
-    let tail = head.end..cur.end;
-
-    let text = &self.input[head];
-    buf[..head_size].copy_from_slice(text);
-    self.cur = tail;
-
-    Ok(head_size)
-
-    // let mut skip = false;
-    //
-    // loop {
-    //   event.
-    //
-    //   match event {
-    //     Event::Start(tag) if tag.name().as_ref() == b"w:commentRangeStart" && self.has_attribute_in_block_list(tag.attributes()) => {
-    //       skip = true
-    //     },
-    //     Event::End(tag) if tag.name().as_ref() == b"w:commentRangeEnd" => {}
-    //
-    //
-    //     // Event::Start(tag) => match tag.name().as_ref() {
-    //     //   b"w:commentRangeStart" if self.has_attribute_in_block_list(tag.attributes()) => {
-    //     //     skip = true;
-    //     //   }
-    //     //   _ => {}
-    //     // },
-    //     Event::Eof => return Ok(0),
-    //     _ => {
-    //       todo!()
-    //     }
-    //   }
-    // }
+/// Returns true if the element makes some content, like a run with a text or a picture.
+fn is_visible(nodes: &[Node], idx: usize) -> bool {
+  let node = &nodes[idx];
+  match node.name.as_slice() {
+    name if BLANK.contains(&name) => false,
+    b"w:r" => node.children.iter().any(|&it| !BLANK.contains(&nodes[it].name.as_slice())),
+    _ => true,
   }
 }
 
-#[cfg(test)]
-mod tests {
-  use std::io::Read;
-
-  use crate::transformers::erase_commented::{
-    EraseCommentedRangeTransformer, EraseCommentedRangeTransformerBrw,
+/// Paragraphs, table rows and tables can be removed as a whole, except paragraphs holding
+/// section properties or being the only paragraph of a table cell.
+fn is_removable(nodes: &[Node], idx: usize) -> bool {
+  let node = &nodes[idx];
+  let children = |node: &Node, name: &[u8]| {
+    node.children.iter().map(|&it| &nodes[it]).filter(|it| it.name == name).collect::<Vec<_>>()
   };
-
-  #[test]
-  fn trans1() -> Result<(), Box<dyn std::error::Error>> {
-    let template =
-      include_bytes!("../../../features/comment_and_image/word/document.xml").as_slice();
-    let mut transformer = EraseCommentedRangeTransformer::from_reader(template);
-    let mut buf = String::new();
-    transformer.read_to_string(&mut buf)?;
-    println!("{}", buf.replace('\r', "\n"));
-    // std::io::copy(&mut transformer, &mut std::io::stdout())?;
-    Ok(())
-  }
-
-  #[test]
-  #[ignore]
-  fn trans2() -> Result<(), Box<dyn std::error::Error>> {
-    let template =
-      include_bytes!("../../../features/comment_and_image/word/document.xml").as_slice();
-    let mut transformer = EraseCommentedRangeTransformerBrw::from_reader(template);
-    let mut buf = String::new();
-    transformer.read_to_string(&mut buf)?;
-    println!("{}", buf.replace('\r', "\n"));
-    // std::io::copy(&mut transformer, &mut std::io::stdout())?;
-    Ok(())
+  match node.name.as_slice() {
+    b"w:tr" | b"w:tbl" => true,
+    b"w:p" => {
+      let has_section =
+        children(node, b"w:pPr").iter().any(|it| !children(it, b"w:sectPr").is_empty());
+      let parent = node.parent.map(|it| &nodes[it]);
+      let is_only_in_cell =
+        parent.is_some_and(|it| it.name == b"w:tc" && children(it, b"w:p").len() == 1);
+      !has_section && !is_only_in_cell
+    }
+    _ => false,
   }
 }
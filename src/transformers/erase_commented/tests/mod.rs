@@ -0,0 +1,178 @@
+use indoc::indoc;
+
+use super::*;
+
+fn run(input: &str) -> String {
+  let ids = HashSet::from(["0".to_owned()]);
+  let buf = EraseCommented { ids: &ids }.transform_stream(input, Vec::new()).unwrap();
+  String::from_utf8(buf).unwrap()
+}
+
+mod ranges {
+  use super::*;
+
+  #[test]
+  fn erases_runs_of_a_paragraph() {
+    insta::assert_snapshot!(
+      run(indoc! {r#"
+        <w:body>
+          <w:p><w:r><w:t>Keep </w:t></w:r><w:commentRangeStart w:id="0"/><w:r><w:t>drop</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:commentReference w:id="0"/></w:r><w:r><w:t> keep</w:t></w:r></w:p>
+        </w:body>
+      "#}),
+      @r###"
+        <w:body>
+          <w:p><w:r><w:t>Keep </w:t></w:r><w:r><w:t> keep</w:t></w:r></w:p>
+        </w:body>
+      "###,
+    );
+  }
+
+  #[test]
+  fn erases_overlapping_comments() {
+    let ids = HashSet::from(["0".to_owned(), "1".to_owned()]);
+    let input = indoc! {r#"
+      <w:p><w:r><w:t>a</w:t></w:r><w:commentRangeStart w:id="0"/><w:r><w:t>b</w:t></w:r><w:commentRangeStart w:id="1"/><w:r><w:t>c</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:t>d</w:t></w:r><w:commentRangeEnd w:id="1"/><w:r><w:t>e</w:t></w:r></w:p>
+    "#};
+    let buf = EraseCommented { ids: &ids }.transform_stream(input, Vec::new()).unwrap();
+    insta::assert_snapshot!(String::from_utf8(buf).unwrap(), @r###"
+      <w:p><w:r><w:t>a</w:t></w:r><w:r><w:t>e</w:t></w:r></w:p>
+    "###);
+  }
+
+  #[test]
+  fn erases_partial_paragraphs_and_tables_between() {
+    insta::assert_snapshot!(
+      run(indoc! {r#"
+        <w:body>
+          <w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:t>Keep </w:t></w:r><w:commentRangeStart w:id="0"/><w:r><w:t>drop</w:t></w:r></w:p>
+          <w:tbl><w:tblPr/><w:tblGrid/><w:tr><w:tc><w:p><w:r><w:t>drop</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+          <w:p><w:pPr><w:jc w:val="right"/></w:pPr><w:r><w:t>drop</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:commentReference w:id="0"/></w:r><w:r><w:t>keep</w:t></w:r></w:p>
+        </w:body>
+      "#}),
+      @r###"
+        <w:body>
+          <w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:t>Keep </w:t></w:r></w:p><w:p><w:pPr><w:jc w:val="right"/></w:pPr><w:r><w:t>keep</w:t></w:r></w:p>
+        </w:body>
+      "###,
+    );
+  }
+
+  #[test]
+  fn removes_paragraphs_left_blank() {
+    insta::assert_snapshot!(
+      run(indoc! {r#"
+        <w:body>
+          <w:p><w:r><w:t>Keep</w:t></w:r></w:p>
+          <w:p><w:pPr/><w:commentRangeStart w:id="0"/><w:r><w:drawing/></w:r></w:p>
+          <w:p><w:r><w:t>drop</w:t></w:r></w:p>
+          <w:p><w:pPr><w:sectPr/></w:pPr><w:r><w:t>drop</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:rPr/><w:commentReference w:id="0"/></w:r></w:p>
+        </w:body>
+      "#}),
+      @r###"
+        <w:body>
+          <w:p><w:r><w:t>Keep</w:t></w:r></w:p>
+          <w:p><w:pPr><w:sectPr/></w:pPr></w:p>
+        </w:body>
+      "###,
+    );
+  }
+
+  #[test]
+  fn erases_rows_of_a_table() {
+    insta::assert_snapshot!(
+      run(indoc! {r#"
+        <w:tbl>
+          <w:tblPr/>
+          <w:tr><w:tc><w:p><w:r><w:t>Keep</w:t></w:r></w:p></w:tc></w:tr>
+          <w:tr><w:tc><w:p><w:commentRangeStart w:id="0"/><w:r><w:t>drop</w:t></w:r></w:p></w:tc></w:tr>
+          <w:tr><w:tc><w:p><w:r><w:t>drop</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:commentReference w:id="0"/></w:r></w:p></w:tc></w:tr>
+        </w:tbl>
+      "#}),
+      @r###"
+        <w:tbl>
+          <w:tblPr/>
+          <w:tr><w:tc><w:p><w:r><w:t>Keep</w:t></w:r></w:p></w:tc></w:tr>
+          
+        </w:tbl>
+      "###,
+    );
+  }
+
+  #[test]
+  fn erases_images() {
+    let template = include_bytes!("../../../../features/comment_and_image/word/document.xml");
+    let ids = HashSet::from(["0".to_owned()]);
+    let erased = EraseCommented { ids: &ids }.transform_stream(template, Vec::new()).unwrap();
+    let (erased, template) =
+      (String::from_utf8(erased).unwrap(), String::from_utf8_lossy(template));
+
+    assert!(!erased.contains("<w:drawing>") && !erased.contains("w:comment"));
+    assert_eq!(erased.matches("<w:p ").count() + 1, template.matches("<w:p ").count());
+  }
+}
+
+mod strip {
+  use super::*;
+
+  #[test]
+  fn keeps_commented_content() {
+    let input = indoc! {r#"
+      <w:body>
+        <w:p><w:commentRangeStart w:id="0"/><w:r><w:t>Keep</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:rPr><w:rStyle w:val="CommentReference"/></w:rPr><w:commentReference w:id="0"/></w:r></w:p>
+        <w:p><w:commentRangeStart w:id="1"/><w:r><w:t>keep</w:t></w:r><w:commentRangeEnd w:id="1"/><w:r><w:t>, too</w:t><w:commentReference w:id="1"/></w:r></w:p>
+      </w:body>
+    "#};
+    let buf = StripComments.transform_stream(input, Vec::new()).unwrap();
+    insta::assert_snapshot!(String::from_utf8(buf).unwrap(), @r###"
+      <w:body>
+        <w:p><w:r><w:t>Keep</w:t></w:r></w:p>
+        <w:p><w:r><w:t>keep</w:t></w:r><w:r><w:t>, too</w:t></w:r></w:p>
+      </w:body>
+    "###);
+  }
+}
+
+mod comments {
+  use super::*;
+  use crate::transformers::erase_commented::comments::*;
+
+  const COMMENTS: &str = r#"<w:comments><w:comment w:id="0"><w:p w14:paraId="0A"><w:r><w:t>{image}</w:t></w:r></w:p></w:comment><w:comment w:id="1"><w:p w14:paraId="1A"><w:r><w:t>Fix </w:t></w:r><w:r><w:t>it</w:t></w:r></w:p><w:p w14:paraId="1B"/></w:comment></w:comments>"#;
+
+  #[test]
+  fn reads_comments() {
+    let comments = read(COMMENTS.as_bytes()).unwrap();
+    assert_eq!(comments.len(), 2);
+    assert_eq!((comments[0].id.as_str(), comments[0].text.as_str()), ("0", "{image}"));
+    assert_eq!(comments[1].text, "Fix it\n");
+    assert_eq!(comments[1].paragraph_ids, ["1A", "1B"]);
+  }
+
+  #[test]
+  fn removes_entries() {
+    let ids = HashSet::from(["1".to_owned()]);
+    let removed = remove(COMMENTS.as_bytes(), b"w:comment", b"w:id", &ids).unwrap();
+    assert_eq!(
+      String::from_utf8(removed).unwrap(),
+      r#"<w:comments><w:comment w:id="0"><w:p w14:paraId="0A"><w:r><w:t>{image}</w:t></w:r></w:p></w:comment></w:comments>"#
+    );
+
+    let xml = br#"<w16cid:commentsIds><w16cid:commentId w16cid:paraId="1B" w16cid:durableId="7F"/></w16cid:commentsIds>"#;
+    let paragraphs = HashSet::from(["1B".to_owned()]);
+    let durable =
+      lookup(xml, b"w16cid:commentId", b"w16cid:paraId", &paragraphs, b"w16cid:durableId");
+    assert_eq!(durable.unwrap(), HashSet::from(["7F".to_owned()]));
+  }
+
+  #[test]
+  fn selects_replies() {
+    let replies = r#"<w:comment w:id="2"><w:p w14:paraId="2A"/></w:comment><w:comment w:id="3"><w:p w14:paraId="3A"/></w:comment></w:comments>"#;
+    let comments = read(COMMENTS.replace("</w:comments>", replies).as_bytes()).unwrap();
+    let extended = br#"<w15:commentsEx><w15:commentEx w15:paraId="1B"/><w15:commentEx w15:paraId="3A" w15:paraIdParent="2A"/><w15:commentEx w15:paraId="2A" w15:paraIdParent="0A"/></w15:commentsEx>"#;
+
+    let mut selection = Selection::default();
+    selection.add(&comments[0]);
+    selection.add_replies(&comments, extended).unwrap();
+    let expected = ["0", "2", "3"].map(str::to_owned);
+    assert_eq!(selection.ids, HashSet::from(expected));
+  }
+}
@@ -0,0 +1,335 @@
+use std::ops::Range;
+
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::transformers::find_and_replace::{inherit_styles, Value};
+use crate::transformers::tree::{apply_edits, Edit};
+use crate::transformers::TransformerError;
+
+/// Fills in results of `MERGEFIELD` and `DOCPROPERTY` fields, which legacy templates use
+/// instead of `{placeholders}`.
+///
+/// Simple fields keep the instruction in an attribute:
+///
+/// ```xml
+/// <w:fldSimple w:instr=" MERGEFIELD CustomerName "><w:r><w:t>«CustomerName»</w:t></w:r></w:fldSimple>
+/// ```
+///
+/// Complex ones are spread over runs, the result lies between the `separate` and `end` characters:
+///
+/// ```xml
+/// <w:r><w:fldChar w:fldCharType="begin"/></w:r>
+/// <w:r><w:instrText> DOCPROPERTY "Customer" </w:instrText></w:r>
+/// <w:r><w:fldChar w:fldCharType="separate"/></w:r>
+/// <w:r><w:t>Acme</w:t></w:r>
+/// <w:r><w:fldChar w:fldCharType="end"/></w:r>
+/// ```
+///
+/// The result is the value's plain text, taking the formatting of the old one.
+/// Fields having no value are left as is, so are complex fields spanning over several
+/// paragraphs or holding nested fields.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Fields {
+  /// Replaces filled fields with their results, so Word doesn't refresh them.
+  pub unlink: bool,
+}
+
+/// What a field shows, defined by the instruction's keyword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+  /// `MERGEFIELD`, expected to be filled.
+  MergeField,
+  /// `DOCPROPERTY`, showing a property of the document.
+  DocProperty,
+}
+
+/// A complex field being read.
+#[derive(Debug, Default)]
+struct ComplexField {
+  /// Start of the run holding the `begin` character.
+  begin: usize,
+  instruction: String,
+  /// End of the run holding the `separate` character.
+  separate: Option<usize>,
+  /// `<w:rPr>` of the first run of the result, or of the instruction.
+  style: Option<Range<usize>>,
+  /// Paragraph boundaries met before the field began.
+  boundaries: usize,
+  has_nested: bool,
+}
+
+/// A `<w:fldSimple>` being read.
+#[derive(Debug)]
+struct SimpleField {
+  start: usize,
+  /// The opening tag, without the closing `>`.
+  tag: Range<usize>,
+  instruction: String,
+  style: Option<Range<usize>>,
+}
+
+impl Fields {
+  /// Pipes the input stream to the output one, looking up values of the fields by their kinds
+  /// and names.
+  ///
+  /// Merge fields having no value are added to `unresolved`, like `MERGEFIELD CustomerName`.
+  pub fn transform_stream<In: AsRef<[u8]>, Out: std::io::Write>(
+    &self,
+    input: In,
+    output: Out,
+    mut lookup: impl FnMut(FieldKind, &str) -> Option<Value>,
+    unresolved: &mut Vec<String>,
+  ) -> Result<Out, TransformerError> {
+    let mut value_of = |instruction: &str| {
+      let (kind, name) = field_name(instruction)?;
+      let value = lookup(kind, name);
+      let field = format!("MERGEFIELD {name}");
+      if value.is_none() && kind == FieldKind::MergeField && !unresolved.contains(&field) {
+        unresolved.push(field);
+      }
+      value
+    };
+    let input = input.as_ref();
+    let mut reader = quick_xml::Reader::from_reader(input);
+    reader.config_mut().check_end_names = true;
+
+    let mut edits = Vec::<Edit>::new();
+    let mut fields = Vec::<ComplexField>::new();
+    let mut simple = None::<SimpleField>;
+    let (mut paragraph_style, mut run_style) = (None::<Range<usize>>, None::<Range<usize>>);
+    let (mut run_start, mut in_run, mut boundaries) = (0, false, 0);
+    // the character met by the current run, handled once the run is closed
+    let mut field_char = None::<Vec<u8>>;
+
+    loop {
+      let position = reader.buffer_position() as usize;
+      let event = reader.read_event()?;
+      match event {
+        Event::Start(ref tag) | Event::Empty(ref tag) => match tag.name().as_ref() {
+          b"w:p" => {
+            boundaries += 1;
+            paragraph_style = None;
+          }
+          b"w:pPr" if !in_run && matches!(event, Event::Start(_)) => {
+            reader.read_to_end(tag.name())?;
+            paragraph_style = Some(position..reader.buffer_position() as usize);
+          }
+          b"w:r" if matches!(event, Event::Start(_)) => {
+            (run_start, in_run, run_style) = (position, true, None);
+          }
+          b"w:rPr" if in_run && matches!(event, Event::Start(_)) => {
+            reader.read_to_end(tag.name())?;
+            let style = position..reader.buffer_position() as usize;
+            run_style = Some(style.clone());
+            if let Some(ref mut field) = simple {
+              field.style.get_or_insert(style.clone());
+            }
+            if let Some(field) = fields.last_mut() {
+              field.style.get_or_insert(style);
+            }
+          }
+          b"w:fldChar" if in_run => field_char = attribute(tag, b"w:fldCharType").map(Vec::from),
+          b"w:instrText" if matches!(event, Event::Start(_)) => {
+            let text = reader.read_text(tag.name())?;
+            let text = quick_xml::escape::unescape(&text).map_err(quick_xml::Error::from)?;
+            if let Some(field) = fields.last_mut().filter(|it| it.separate.is_none()) {
+              field.instruction += &text;
+            }
+          }
+          b"w:fldSimple" => {
+            let instruction = attribute(tag, b"w:instr").unwrap_or_default();
+            let end = reader.buffer_position() as usize;
+            let closing = if matches!(event, Event::Empty(_)) { 2 } else { 1 };
+            let field = SimpleField {
+              start: position,
+              tag: position..end - closing,
+              instruction,
+              style: None,
+            };
+            match event {
+              Event::Start(_) => simple = Some(field),
+              _ => {
+                edits.extend(self.fill_simple(input, &field, end, &paragraph_style, &mut value_of))
+              }
+            }
+          }
+          _ => {}
+        },
+        Event::End(ref tag) => match tag.name().as_ref() {
+          b"w:p" => boundaries += 1,
+          b"w:r" => {
+            in_run = false;
+            let end = reader.buffer_position() as usize;
+            match field_char.take().as_deref() {
+              Some(b"begin") => {
+                if let Some(outer) = fields.last_mut() {
+                  outer.has_nested = true;
+                }
+                let style = run_style.clone();
+                fields.push(ComplexField {
+                  begin: run_start,
+                  style,
+                  boundaries,
+                  ..Default::default()
+                });
+              }
+              Some(b"separate") => {
+                if let Some(field) = fields.last_mut() {
+                  field.separate = Some(end);
+                  // the result's own formatting is preferred over the instruction's one
+                  field.style = None;
+                }
+              }
+              Some(b"end") => {
+                let Some(field) = fields.pop() else { continue };
+                if field.has_nested || field.boundaries != boundaries {
+                  continue;
+                }
+                let Some(value) = value_of(&field.instruction) else {
+                  continue;
+                };
+                let result = result(input, &value, &paragraph_style, &field.style);
+                edits.push(match (self.unlink, field.separate) {
+                  (true, _) => (field.begin..end, result),
+                  (false, Some(separate)) => (separate..run_start, result),
+                  (false, None) => {
+                    let separate = br#"<w:r><w:fldChar w:fldCharType="separate"/></w:r>"#;
+                    (run_start..run_start, [&separate[..], &result].concat())
+                  }
+                });
+              }
+              _ => {}
+            }
+          }
+          b"w:fldSimple" => {
+            let Some(field) = simple.take() else { continue };
+            let end = reader.buffer_position() as usize;
+            edits.extend(self.fill_simple(input, &field, end, &paragraph_style, &mut value_of));
+          }
+          _ => {}
+        },
+        Event::Eof => break,
+        _ => {}
+      }
+    }
+
+    Ok(apply_edits(input, edits, output)?)
+  }
+
+  /// Replaces the content of `<w:fldSimple>` ending at `end`, or the whole field if unlinked.
+  /// Values are looked up by instructions.
+  fn fill_simple(
+    &self,
+    input: &[u8],
+    field: &SimpleField,
+    end: usize,
+    paragraph_style: &Option<Range<usize>>,
+    value_of: &mut impl FnMut(&str) -> Option<Value>,
+  ) -> Option<(Range<usize>, Vec<u8>)> {
+    let value = value_of(&field.instruction)?;
+    let result = result(input, &value, paragraph_style, &field.style);
+    Some(match self.unlink {
+      true => (field.start..end, result),
+      false => {
+        let tag = &input[field.tag.clone()];
+        (field.start..end, [tag, b">", &result, b"</w:fldSimple>"].concat())
+      }
+    })
+  }
+}
+
+/// Kind and name of the merge field or the document property, like `Customer` in
+/// `DOCPROPERTY "Customer"`.
+fn field_name(instruction: &str) -> Option<(FieldKind, &str)> {
+  let instruction = instruction.trim_start();
+  let (keyword, rest) = instruction.split_once(char::is_whitespace)?;
+  let kind = match keyword {
+    _ if keyword.eq_ignore_ascii_case("MERGEFIELD") => FieldKind::MergeField,
+    _ if keyword.eq_ignore_ascii_case("DOCPROPERTY") => FieldKind::DocProperty,
+    _ => return None,
+  };
+  let rest = rest.trim_start();
+  let name = match rest.strip_prefix('"') {
+    Some(quoted) => quoted.split('"').next()?,
+    None => rest.split(char::is_whitespace).next()?,
+  };
+  Some((kind, name)).filter(|(_, it)| !it.is_empty())
+}
+
+/// A run holding the value, formatted like the old result.
+fn result(
+  input: &[u8],
+  value: &Value,
+  paragraph_style: &Option<Range<usize>>,
+  run_style: &Option<Range<usize>>,
+) -> Vec<u8> {
+  let style = |range: &Option<Range<usize>>| range.clone().map_or(&[][..], |it| &input[it]);
+  let run = format!(r#"<w:r><w:t xml:space="preserve">{}</w:t></w:r>"#, escape(value.text()));
+  inherit_styles(run.as_bytes(), style(paragraph_style), style(run_style)).into_owned()
+}
+
+fn attribute(tag: &BytesStart, name: &[u8]) -> Option<String> {
+  let value = tag.try_get_attribute(name).ok()??;
+  value.unescape_value().ok().map(|it| it.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(fields: Fields, input: &str) -> String {
+    let lookup = |_, name: &str| match name {
+      "Customer" => Some(Value::from_text("Acme & Co")),
+      "Note" => Some(Value::from_markdown("**Paid** in full")),
+      _ => None,
+    };
+    let output = fields.transform_stream(input, Vec::new(), lookup, &mut Vec::new()).unwrap();
+    String::from_utf8(output).unwrap()
+  }
+
+  #[test]
+  fn reads_field_names() {
+    let merge_field = field_name(" MERGEFIELD  Customer \\* MERGEFORMAT ");
+    assert_eq!(merge_field, Some((FieldKind::MergeField, "Customer")));
+    let property = field_name(r#" DOCPROPERTY "Customer Name" "#);
+    assert_eq!(property, Some((FieldKind::DocProperty, "Customer Name")));
+    assert_eq!(field_name(" PAGE "), None);
+  }
+
+  #[test]
+  fn fills_simple_fields() {
+    let input = r#"<w:p><w:fldSimple w:instr=" MERGEFIELD Customer "><w:r><w:rPr><w:b/></w:rPr><w:t>«Customer»</w:t></w:r></w:fldSimple><w:fldSimple w:instr=" MERGEFIELD Other "/></w:p>"#;
+    insta::assert_snapshot!(run(Fields::default(), input), @r###"
+      <w:p><w:fldSimple w:instr=" MERGEFIELD Customer "><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve">Acme &amp; Co</w:t></w:r></w:fldSimple><w:fldSimple w:instr=" MERGEFIELD Other "/></w:p>
+    "###);
+    insta::assert_snapshot!(run(Fields { unlink: true }, input), @r###"
+      <w:p><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve">Acme &amp; Co</w:t></w:r><w:fldSimple w:instr=" MERGEFIELD Other "/></w:p>
+    "###);
+  }
+
+  #[test]
+  fn fills_complex_fields() {
+    let input = r#"<w:p><w:r><w:fldChar w:fldCharType="begin"/></w:r><w:r><w:instrText xml:space="preserve"> DOCPROPERTY </w:instrText></w:r><w:r><w:instrText>"Customer"</w:instrText></w:r><w:r><w:fldChar w:fldCharType="separate"/></w:r><w:r><w:rPr><w:i/></w:rPr><w:t>Old</w:t></w:r><w:r><w:t>er</w:t></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r></w:p>"#;
+    insta::assert_snapshot!(run(Fields::default(), input), @r###"
+      <w:p><w:r><w:fldChar w:fldCharType="begin"/></w:r><w:r><w:instrText xml:space="preserve"> DOCPROPERTY </w:instrText></w:r><w:r><w:instrText>"Customer"</w:instrText></w:r><w:r><w:fldChar w:fldCharType="separate"/></w:r><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">Acme &amp; Co</w:t></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r></w:p>
+    "###);
+    insta::assert_snapshot!(run(Fields { unlink: true }, input), @r###"
+      <w:p><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">Acme &amp; Co</w:t></w:r></w:p>
+    "###);
+  }
+
+  #[test]
+  fn puts_plain_text_of_values() {
+    let input = r#"<w:p><w:fldSimple w:instr=" MERGEFIELD Note "><w:r><w:t>«Note»</w:t></w:r></w:fldSimple></w:p>"#;
+    insta::assert_snapshot!(run(Fields { unlink: true }, input), @r###"<w:p><w:r><w:t xml:space="preserve">Paid in full</w:t></w:r></w:p>"###);
+  }
+
+  #[test]
+  fn reports_merge_fields_without_values() {
+    let input = r#"<w:p><w:fldSimple w:instr=" MERGEFIELD Other "/><w:fldSimple w:instr=" DOCPROPERTY Other "/><w:r><w:fldChar w:fldCharType="begin"/></w:r><w:r><w:instrText> MERGEFIELD Other </w:instrText></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r></w:p>"#;
+    let mut unresolved = Vec::new();
+    Fields::default().transform_stream(input, Vec::new(), |_, _| None, &mut unresolved).unwrap();
+    assert_eq!(unresolved, ["MERGEFIELD Other"]);
+  }
+}
@@ -0,0 +1,703 @@
+use super::paragraph_styles::{ParagraphKind, ParagraphStyles};
+use super::rich_text::Span;
+use super::Value;
+use crate::relationships::{hyperlink_tag, Relationship};
+
+/// Elements which can't have children.
+const VOID_ELEMENTS: &[&str] =
+  &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "wbr"];
+
+/// Elements whose content is not rendered.
+const HIDDEN_ELEMENTS: &[&str] = &["head", "script", "style", "template", "title"];
+
+const BLOCK_ELEMENTS: &[&str] =
+  &["p", "div", "h1", "h2", "h3", "h4", "h5", "h6", "blockquote", "ul", "ol", "li", "table", "pre"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+  Element(Element),
+  Text(String),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Element {
+  name: String,
+  attributes: Vec<(String, String)>,
+  children: Vec<Node>,
+}
+
+impl Element {
+  fn attribute(&self, name: &str) -> Option<&str> {
+    self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+  }
+
+  /// Declarations of the `style` attribute, like `("color", "red")`.
+  fn style(&self) -> impl Iterator<Item = (String, &str)> {
+    let style = self.attribute("style").unwrap_or_default();
+    style.split(';').filter_map(|declaration| {
+      let (property, value) = declaration.split_once(':')?;
+      Some((property.trim().to_ascii_lowercase(), value.trim()))
+    })
+  }
+}
+
+/// Converts HTML into runs, paragraphs and tables, see [Value::from_html].
+///
+/// A single paragraph is put inline, as runs of the placeholder's paragraph.
+/// Otherwise, the placeholder's paragraph is split, and the blocks are put in between.
+pub(crate) fn to_value(html: &str, styles: &ParagraphStyles) -> Value {
+  let mut converter = Converter { styles, lists: Vec::new(), relationships: Vec::new() };
+  let mut blocks = Vec::new();
+  converter.walk(&parse(html), &Format::default(), &mut blocks);
+  blocks.retain(Block::is_kept);
+
+  let xml = match blocks.as_slice() {
+    [] => String::new(),
+    [Block::Paragraph(paragraph)] if paragraph.kind == ParagraphKind::Body => {
+      format!(r#"</w:t></w:r>{}<w:r><w:t xml:space="preserve">"#, paragraph.runs)
+    }
+    blocks => {
+      let blocks = blocks.iter().map(Block::to_xml).collect::<String>();
+      format!(r#"</w:t></w:r></w:p>{blocks}<w:p><w:r><w:t xml:space="preserve">"#)
+    }
+  };
+  Value::with_relationships(xml, converter.relationships)
+}
+
+/// Builds a tree of elements, closing the ones left open the way browsers do.
+fn parse(html: &str) -> Vec<Node> {
+  let mut stack = vec![Element::default()];
+
+  for token in tokens(html) {
+    match token {
+      Token::Text(text) => stack.last_mut().unwrap().children.push(Node::Text(text)),
+      Token::Start { name, attributes, self_closing } => {
+        match name.as_str() {
+          "li" => close_implied(&mut stack, &["li"], &["ul", "ol", "table"]),
+          "tr" => close_implied(&mut stack, &["tr"], &["table"]),
+          "td" | "th" => close_implied(&mut stack, &["td", "th"], &["tr", "table"]),
+          _ => {}
+        }
+        if BLOCK_ELEMENTS.contains(&name.as_str()) {
+          close_implied(&mut stack, &["p"], &["li", "td", "th", "blockquote", "div"]);
+        }
+
+        let element = Element { name, attributes, children: Vec::new() };
+        if self_closing || VOID_ELEMENTS.contains(&element.name.as_str()) {
+          stack.last_mut().unwrap().children.push(Node::Element(element));
+        } else {
+          stack.push(element);
+        }
+      }
+      Token::End(name) => {
+        // the root element is never matched, as it has no name
+        if let Some(idx) = stack.iter().rposition(|it| it.name == name) {
+          while stack.len() > idx {
+            pop(&mut stack);
+          }
+        }
+      }
+    }
+  }
+
+  while stack.len() > 1 {
+    pop(&mut stack);
+  }
+  stack.pop().unwrap().children
+}
+
+fn pop(stack: &mut Vec<Element>) {
+  if let Some(element) = stack.pop() {
+    stack.last_mut().unwrap().children.push(Node::Element(element));
+  }
+}
+
+/// Closes the nearest open element from `targets`, unless one of `boundaries` is met first.
+fn close_implied(stack: &mut Vec<Element>, targets: &[&str], boundaries: &[&str]) {
+  let nearest = stack
+    .iter()
+    .rposition(|it| targets.contains(&it.name.as_str()) || boundaries.contains(&it.name.as_str()));
+  if let Some(idx) = nearest.filter(|&idx| targets.contains(&stack[idx].name.as_str())) {
+    while stack.len() > idx {
+      pop(stack);
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+  Start { name: String, attributes: Vec<(String, String)>, self_closing: bool },
+  End(String),
+  Text(String),
+}
+
+/// Splits the markup into tags and texts, skipping comments and declarations.
+fn tokens(html: &str) -> Vec<Token> {
+  let mut tokens = Vec::new();
+  let mut rest = html;
+
+  while !rest.is_empty() {
+    let Some(start) = rest.find('<') else {
+      tokens.push(Token::Text(unescape(rest)));
+      break;
+    };
+    if start > 0 {
+      tokens.push(Token::Text(unescape(&rest[..start])));
+      rest = &rest[start..];
+    }
+
+    if let Some(comment) = rest.strip_prefix("<!--") {
+      rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+    } else if rest.starts_with("<!") || rest.starts_with("<?") {
+      rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+    } else if let Some((token, len)) = tag(rest) {
+      tokens.push(token);
+      rest = &rest[len..];
+    } else {
+      tokens.push(Token::Text("<".to_owned()));
+      rest = &rest[1..];
+    }
+  }
+
+  tokens
+}
+
+/// Parses a tag at the beginning of the text, returns it along with its length.
+fn tag(text: &str) -> Option<(Token, usize)> {
+  let is_end = text[1..].starts_with('/');
+  let body = &text[if is_end { 2 } else { 1 }..];
+  if !body.starts_with(|it: char| it.is_ascii_alphabetic()) {
+    return None;
+  }
+
+  let name_len = body.find(|it: char| it.is_ascii_whitespace() || it == '/' || it == '>');
+  let name = body[..name_len.unwrap_or(body.len())].to_ascii_lowercase();
+  let mut rest = &body[name.len()..];
+  let mut attributes = Vec::new();
+  let mut self_closing = false;
+
+  loop {
+    rest = rest.trim_start();
+    if let Some(after) = rest.strip_prefix('>') {
+      rest = after;
+      break;
+    } else if let Some(after) = rest.strip_prefix("/>") {
+      self_closing = true;
+      rest = after;
+      break;
+    } else if let Some(after) = rest.strip_prefix('/') {
+      rest = after;
+      continue;
+    } else if rest.is_empty() {
+      return None;
+    }
+
+    let key_len = rest.find(|it: char| it.is_ascii_whitespace() || "=/>".contains(it));
+    let key = rest[..key_len.unwrap_or(rest.len())].to_ascii_lowercase();
+    rest = rest[key.len()..].trim_start();
+
+    let mut value = String::new();
+    if let Some(after) = rest.strip_prefix('=') {
+      let after = after.trim_start();
+      let (raw, len) = match after.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+          let end = after[1..].find(quote)?;
+          (&after[1..end + 1], end + 2)
+        }
+        _ => {
+          let end = after.find(|it: char| it.is_ascii_whitespace() || it == '>');
+          let end = end.unwrap_or(after.len());
+          (&after[..end], end)
+        }
+      };
+      value = unescape(raw);
+      rest = &after[len..];
+    }
+    attributes.push((key, value));
+  }
+
+  let token = match is_end {
+    true => Token::End(name),
+    false => Token::Start { name, attributes, self_closing },
+  };
+  Some((token, text.len() - rest.len()))
+}
+
+/// Decodes character references, leaving unknown ones as is.
+fn unescape(text: &str) -> String {
+  let mut result = String::with_capacity(text.len());
+  let mut rest = text;
+
+  while let Some(start) = rest.find('&') {
+    result.push_str(&rest[..start]);
+    rest = &rest[start..];
+
+    let decoded = rest[1..].find(';').filter(|&end| end <= 10).and_then(|end| {
+      let entity = &rest[1..end + 1];
+      let char = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+          let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => entity.strip_prefix('#')?.parse().ok()?,
+          };
+          char::from_u32(code)?
+        }
+      };
+      Some((char, end + 2))
+    });
+
+    match decoded {
+      Some((char, len)) => {
+        result.push(char);
+        rest = &rest[len..];
+      }
+      None => {
+        result.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+
+  result.push_str(rest);
+  result
+}
+
+/// Formatting of the text, inherited from the enclosing elements.
+#[derive(Debug, Default, Clone)]
+struct Format {
+  bold: bool,
+  italic: bool,
+  underline: bool,
+  strike: bool,
+  code: bool,
+  color: Option<String>,
+  size: Option<f32>,
+  /// Opening `<w:hyperlink>` tag.
+  link: Option<String>,
+}
+
+impl Format {
+  fn run(&self, text: String) -> String {
+    let mut span = Span::new(text);
+    if self.bold {
+      span = span.bold();
+    }
+    if self.italic {
+      span = span.italic();
+    }
+    if self.underline || self.link.is_some() {
+      span = span.underline();
+    }
+    if self.strike {
+      span = span.strike();
+    }
+    if self.code {
+      span = span.font("Courier New");
+    }
+    match self.color {
+      Some(ref color) => span = span.color(color.as_str()),
+      None if self.link.is_some() => span = span.color("0563C1"),
+      None => {}
+    }
+    if let Some(size) = self.size {
+      span = span.size(size);
+    }
+
+    match self.link {
+      Some(ref link) => format!("{link}{}</w:hyperlink>", span.to_xml()),
+      None => span.to_xml(),
+    }
+  }
+}
+
+#[derive(Debug)]
+enum Block {
+  Paragraph(Paragraph),
+  /// `<w:tbl>…</w:tbl>`
+  Table(String),
+}
+
+#[derive(Debug)]
+struct Paragraph {
+  kind: ParagraphKind,
+  /// `<w:pPr>…</w:pPr>`, if any.
+  properties: String,
+  runs: String,
+  /// Explicit `<p>` elements are kept even if empty.
+  is_explicit: bool,
+  has_content: bool,
+  /// More inline content can be appended.
+  is_open: bool,
+  /// Following whitespace is collapsed.
+  ends_with_space: bool,
+}
+
+impl Block {
+  fn is_kept(&self) -> bool {
+    match self {
+      Block::Paragraph(paragraph) => paragraph.has_content || paragraph.is_explicit,
+      Block::Table(_) => true,
+    }
+  }
+
+  fn to_xml(&self) -> String {
+    match self {
+      Block::Paragraph(paragraph) => {
+        format!("<w:p>{}{}</w:p>", paragraph.properties, paragraph.runs)
+      }
+      Block::Table(xml) => xml.clone(),
+    }
+  }
+}
+
+struct Converter<'s> {
+  styles: &'s ParagraphStyles,
+  /// Open lists: whether a list is numbered, and the number of its last item.
+  lists: Vec<(bool, usize)>,
+  relationships: Vec<Relationship>,
+}
+
+impl Converter<'_> {
+  fn walk(&mut self, nodes: &[Node], format: &Format, blocks: &mut Vec<Block>) {
+    for node in nodes {
+      match node {
+        Node::Text(text) => self.text(text, format, blocks),
+        Node::Element(element) => self.element(element, format, blocks),
+      }
+    }
+  }
+
+  fn element(&mut self, element: &Element, format: &Format, blocks: &mut Vec<Block>) {
+    let mut format = format.clone();
+    let name = element.name.as_str();
+
+    match name {
+      _ if HIDDEN_ELEMENTS.contains(&name) => return,
+      "b" | "strong" => format.bold = true,
+      "i" | "em" => format.italic = true,
+      "u" | "ins" => format.underline = true,
+      "s" | "strike" | "del" => format.strike = true,
+      "code" | "kbd" | "samp" | "tt" => format.code = true,
+      "font" => format.color = element.attribute("color").and_then(color).or(format.color),
+      "a" => format.link = element.attribute("href").and_then(|href| self.link(href)),
+      "br" => {
+        let paragraph = self.paragraph(blocks);
+        paragraph.runs += "<w:r><w:br/></w:r>";
+        paragraph.ends_with_space = true;
+        return;
+      }
+      "table" => {
+        close(blocks);
+        let table = self.table(element, &format);
+        blocks.push(Block::Table(table));
+        return;
+      }
+      "ul" | "ol" => {
+        let start = element.attribute("start").and_then(|it| it.trim().parse::<usize>().ok());
+        self.lists.push((name == "ol", start.unwrap_or(1).saturating_sub(1)));
+        close(blocks);
+        self.walk(&element.children, &format, blocks);
+        close(blocks);
+        self.lists.pop();
+        return;
+      }
+      _ => {}
+    }
+
+    for (property, value) in element.style() {
+      match property.as_str() {
+        "color" => format.color = color(value).or(format.color),
+        "font-weight" => {
+          format.bold = value == "bold" || value.parse().is_ok_and(|it: u32| it >= 600)
+        }
+        "font-style" => format.italic = value == "italic" || value == "oblique",
+        "text-decoration" | "text-decoration-line" => {
+          format.underline |= value.contains("underline");
+          format.strike |= value.contains("line-through");
+        }
+        _ => {}
+      }
+    }
+
+    let kind = match name {
+      "p" | "div" | "blockquote" | "pre" => Some(ParagraphKind::Body),
+      "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+        Some(ParagraphKind::Heading(name[1..].parse().unwrap()))
+      }
+      "li" => Some(match self.lists.last_mut() {
+        Some((true, number)) => {
+          *number += 1;
+          ParagraphKind::NumberedItem(*number)
+        }
+        _ => ParagraphKind::BulletItem,
+      }),
+      _ => None,
+    };
+
+    match kind {
+      Some(kind) => {
+        close(blocks);
+        let depth = self.lists.len().saturating_sub(1);
+        let paragraph_format = self.styles.format(&kind, depth);
+        if let Some(size) = paragraph_format.heading_size {
+          format.bold = true;
+          format.size = Some(size);
+        }
+        blocks.push(Block::Paragraph(Paragraph {
+          kind,
+          properties: paragraph_format.properties,
+          runs: paragraph_format.marker.map(|it| it.to_xml()).unwrap_or_default(),
+          is_explicit: name == "p",
+          has_content: false,
+          is_open: true,
+          ends_with_space: false,
+        }));
+        self.walk(&element.children, &format, blocks);
+        close(blocks);
+      }
+      None => self.walk(&element.children, &format, blocks),
+    }
+  }
+
+  /// Appends the text with whitespace collapsed, as browsers do.
+  fn text(&mut self, text: &str, format: &Format, blocks: &mut Vec<Block>) {
+    let words = text.split_ascii_whitespace().collect::<Vec<_>>();
+    let is_open = matches!(blocks.last(), Some(Block::Paragraph(paragraph)) if paragraph.is_open);
+    if words.is_empty() && !is_open {
+      return;
+    }
+
+    let paragraph = self.paragraph(blocks);
+    let mut collapsed = String::new();
+    let starts_with_space = text.starts_with(|it: char| it.is_ascii_whitespace());
+    if starts_with_space && paragraph.has_content && !paragraph.ends_with_space {
+      collapsed.push(' ');
+    }
+    collapsed += &words.join(" ");
+    if !words.is_empty() && text.ends_with(|it: char| it.is_ascii_whitespace()) {
+      collapsed.push(' ');
+    }
+
+    if !collapsed.is_empty() {
+      paragraph.ends_with_space = collapsed.ends_with(' ');
+      paragraph.runs += &format.run(collapsed);
+      paragraph.has_content = true;
+    }
+  }
+
+  /// The open paragraph, or a new one.
+  fn paragraph<'b>(&self, blocks: &'b mut Vec<Block>) -> &'b mut Paragraph {
+    if !matches!(blocks.last(), Some(Block::Paragraph(paragraph)) if paragraph.is_open) {
+      blocks.push(Block::Paragraph(Paragraph {
+        kind: ParagraphKind::Body,
+        properties: String::new(),
+        runs: String::new(),
+        is_explicit: false,
+        has_content: false,
+        is_open: true,
+        ends_with_space: false,
+      }));
+    }
+    match blocks.last_mut() {
+      Some(Block::Paragraph(paragraph)) => paragraph,
+      _ => unreachable!(),
+    }
+  }
+
+  /// Opening tag of a hyperlink, either to an external resource or to a bookmark.
+  fn link(&mut self, href: &str) -> Option<String> {
+    let href = href.trim();
+    if href.is_empty() || href.to_ascii_lowercase().starts_with("javascript:") {
+      return None;
+    }
+    let (tag, relationship) = hyperlink_tag(href);
+    self.relationships.extend(relationship);
+    Some(tag)
+  }
+
+  fn table(&mut self, table: &Element, format: &Format) -> String {
+    let mut rows = Vec::new();
+    for row in rows_of(table) {
+      let mut cells = Vec::new();
+      for cell in row.children.iter().filter_map(|it| match it {
+        Node::Element(cell) if cell.name == "td" || cell.name == "th" => Some(cell),
+        _ => None,
+      }) {
+        let mut format = format.clone();
+        format.bold |= cell.name == "th";
+        let span = cell.attribute("colspan").and_then(|it| it.trim().parse().ok());
+        let span = span.unwrap_or(1usize).max(1);
+
+        let lists = std::mem::take(&mut self.lists);
+        let mut blocks = Vec::new();
+        self.element(&Element { name: String::new(), ..cell.clone() }, &format, &mut blocks);
+        self.lists = lists;
+        blocks.retain(Block::is_kept);
+
+        let mut content = blocks.iter().map(Block::to_xml).collect::<String>();
+        // a cell must end with a paragraph
+        if !matches!(blocks.last(), Some(Block::Paragraph(_))) {
+          content += "<w:p/>";
+        }
+        cells.push((span, content));
+      }
+      rows.push(cells);
+    }
+
+    let columns = rows.iter().map(|it| it.iter().map(|(span, _)| span).sum()).max().unwrap_or(0);
+    let border =
+      |side| format!(r#"<w:{side} w:val="single" w:sz="4" w:space="0" w:color="auto"/>"#);
+    let borders = ["top", "left", "bottom", "right", "insideH", "insideV"].map(border).concat();
+
+    let mut xml = format!(
+      r#"<w:tbl><w:tblPr><w:tblW w:w="0" w:type="auto"/><w:tblBorders>{borders}</w:tblBorders></w:tblPr><w:tblGrid>{}</w:tblGrid>"#,
+      "<w:gridCol/>".repeat(columns)
+    );
+    for cells in rows {
+      let width = cells.iter().map(|(span, _)| span).sum::<usize>();
+      xml += "<w:tr>";
+      for (span, content) in cells {
+        let grid_span = match span {
+          1 => String::new(),
+          span => format!(r#"<w:gridSpan w:val="{span}"/>"#),
+        };
+        xml += &format!(
+          r#"<w:tc><w:tcPr><w:tcW w:w="0" w:type="auto"/>{grid_span}</w:tcPr>{content}</w:tc>"#
+        );
+      }
+      // short rows are padded, so the grid stays rectangular
+      for _ in width..columns {
+        xml += r#"<w:tc><w:tcPr><w:tcW w:w="0" w:type="auto"/></w:tcPr><w:p/></w:tc>"#;
+      }
+      xml += "</w:tr>";
+    }
+    xml + "</w:tbl>"
+  }
+}
+
+/// Marks the last paragraph as complete, so the following content starts a new one.
+fn close(blocks: &mut [Block]) {
+  if let Some(Block::Paragraph(paragraph)) = blocks.last_mut() {
+    paragraph.is_open = false;
+  }
+}
+
+/// Rows of the table, including those inside `<thead>`, `<tbody>` and `<tfoot>`.
+fn rows_of(table: &Element) -> Vec<&Element> {
+  let mut rows = Vec::new();
+  for node in &table.children {
+    match node {
+      Node::Element(row) if row.name == "tr" => rows.push(row),
+      Node::Element(group) if ["thead", "tbody", "tfoot"].contains(&group.name.as_str()) => {
+        rows.extend(rows_of(group))
+      }
+      _ => {}
+    }
+  }
+  rows
+}
+
+/// Parses a CSS color into a hex string: `#f00`, `#ff0000`, `rgb(255, 0, 0)` or a basic name.
+fn color(value: &str) -> Option<String> {
+  let value = value.trim().to_ascii_lowercase();
+
+  if let Some(hex) = value.strip_prefix('#') {
+    if !hex.chars().all(|it| it.is_ascii_hexdigit()) {
+      return None;
+    }
+    return match hex.len() {
+      3 => Some(hex.chars().flat_map(|it| [it, it]).collect::<String>().to_uppercase()),
+      6 => Some(hex.to_uppercase()),
+      _ => None,
+    };
+  }
+
+  if let Some(channels) = value.strip_prefix("rgb(").and_then(|it| it.strip_suffix(')')) {
+    let channels =
+      channels.split(',').map(|it| it.trim().parse::<u8>().ok()).collect::<Option<Vec<_>>>()?;
+    return match channels.as_slice() {
+      [r, g, b] => Some(format!("{r:02X}{g:02X}{b:02X}")),
+      _ => None,
+    };
+  }
+
+  let hex = match value.as_str() {
+    "black" => "000000",
+    "white" => "FFFFFF",
+    "red" => "FF0000",
+    "green" => "008000",
+    "blue" => "0000FF",
+    "yellow" => "FFFF00",
+    "orange" => "FFA500",
+    "purple" => "800080",
+    "gray" | "grey" => "808080",
+    _ => return None,
+  };
+  Some(hex.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn closes_implied_elements() {
+    let text = |text: &str| Node::Text(text.to_owned());
+    let element = |name: &str, children| {
+      Node::Element(Element { name: name.to_owned(), attributes: Vec::new(), children })
+    };
+    assert_eq!(
+      parse("<ul><li>a<li>b &amp; c</ul><p>d<p>e"),
+      [
+        element("ul", vec![element("li", vec![text("a")]), element("li", vec![text("b & c")])]),
+        element("p", vec![text("d")]),
+        element("p", vec![text("e")]),
+      ]
+    );
+  }
+
+  #[test]
+  fn parses_colors() {
+    assert_eq!(color("#f0a").as_deref(), Some("FF00AA"));
+    assert_eq!(color("rgb(255, 0, 16)").as_deref(), Some("FF0010"));
+    assert_eq!(color("Purple").as_deref(), Some("800080"));
+    assert_eq!(color("#ggg"), None);
+  }
+
+  #[test]
+  fn converts_inline_markup() {
+    let value = to_value(
+      r#"<p>Hi <b>bold  <i>both</i></b><br><span style="color: #c00">red</span> <a href="https://a.b">site</a></p>"#,
+      &Default::default(),
+    );
+    let marker = value.relationships[0].marker();
+    insta::assert_snapshot!(
+      value.xml.replace(&marker, "{rel}"),
+      @r###"</w:t></w:r><w:r><w:t xml:space="preserve">Hi </w:t></w:r><w:r><w:rPr><w:b/><w:bCs/></w:rPr><w:t xml:space="preserve">bold </w:t></w:r><w:r><w:rPr><w:b/><w:bCs/><w:i/><w:iCs/></w:rPr><w:t xml:space="preserve">both</w:t></w:r><w:r><w:br/></w:r><w:r><w:rPr><w:color w:val="CC0000"/></w:rPr><w:t xml:space="preserve">red</w:t></w:r><w:r><w:t xml:space="preserve"> </w:t></w:r><w:hyperlink r:id="{rel}"><w:r><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr><w:t xml:space="preserve">site</w:t></w:r></w:hyperlink><w:r><w:t xml:space="preserve">"###,
+    );
+  }
+
+  #[test]
+  fn converts_lists_and_tables() {
+    let html = "<h2>Notes</h2><ol><li>one</li><li>two</li></ol>\
+      <table><tr><th>Name</th><th>Age</th></tr><tr><td>Ann</td></tr></table>";
+    insta::assert_snapshot!(
+      to_value(html, &ParagraphStyles::unstyled()).xml,
+      @r###"</w:t></w:r></w:p><w:p><w:pPr><w:keepNext/></w:pPr><w:r><w:rPr><w:b/><w:bCs/><w:sz w:val="32"/><w:szCs w:val="32"/></w:rPr><w:t xml:space="preserve">Notes</w:t></w:r></w:p><w:p><w:pPr><w:ind w:left="360"/></w:pPr><w:r><w:t xml:space="preserve">1. </w:t></w:r><w:r><w:t xml:space="preserve">one</w:t></w:r></w:p><w:p><w:pPr><w:ind w:left="360"/></w:pPr><w:r><w:t xml:space="preserve">2. </w:t></w:r><w:r><w:t xml:space="preserve">two</w:t></w:r></w:p><w:tbl><w:tblPr><w:tblW w:w="0" w:type="auto"/><w:tblBorders><w:top w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:left w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:bottom w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:right w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:insideH w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:insideV w:val="single" w:sz="4" w:space="0" w:color="auto"/></w:tblBorders></w:tblPr><w:tblGrid><w:gridCol/><w:gridCol/></w:tblGrid><w:tr><w:tc><w:tcPr><w:tcW w:w="0" w:type="auto"/></w:tcPr><w:p><w:r><w:rPr><w:b/><w:bCs/></w:rPr><w:t xml:space="preserve">Name</w:t></w:r></w:p></w:tc><w:tc><w:tcPr><w:tcW w:w="0" w:type="auto"/></w:tcPr><w:p><w:r><w:rPr><w:b/><w:bCs/></w:rPr><w:t xml:space="preserve">Age</w:t></w:r></w:p></w:tc></w:tr><w:tr><w:tc><w:tcPr><w:tcW w:w="0" w:type="auto"/></w:tcPr><w:p><w:r><w:t xml:space="preserve">Ann</w:t></w:r></w:p></w:tc><w:tc><w:tcPr><w:tcW w:w="0" w:type="auto"/></w:tcPr><w:p/></w:tc></w:tr></w:tbl><w:p><w:r><w:t xml:space="preserve">"###,
+    );
+  }
+
+  #[test]
+  fn numbers_nested_list_items_by_depth() {
+    let html = "<ul><li>a<ul><li>b</li></ul></li><li>c</li></ul>";
+    let styles = ParagraphStyles { bullet_numbering: Some("3".to_owned()), ..Default::default() };
+    insta::assert_snapshot!(to_value(html, &styles).xml, @r###"</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="ListBullet"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="3"/></w:numPr></w:pPr><w:r><w:t xml:space="preserve">a</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="ListBullet"/><w:numPr><w:ilvl w:val="1"/><w:numId w:val="3"/></w:numPr></w:pPr><w:r><w:t xml:space="preserve">b</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="ListBullet"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="3"/></w:numPr></w:pPr><w:r><w:t xml:space="preserve">c</w:t></w:r></w:p><w:p><w:r><w:t xml:space="preserve">"###);
+    insta::assert_snapshot!(to_value(html, &ParagraphStyles::unstyled()).xml, @r###"</w:t></w:r></w:p><w:p><w:pPr><w:ind w:left="360"/></w:pPr><w:r><w:t xml:space="preserve">• </w:t></w:r><w:r><w:t xml:space="preserve">a</w:t></w:r></w:p><w:p><w:pPr><w:ind w:left="720"/></w:pPr><w:r><w:t xml:space="preserve">• </w:t></w:r><w:r><w:t xml:space="preserve">b</w:t></w:r></w:p><w:p><w:pPr><w:ind w:left="360"/></w:pPr><w:r><w:t xml:space="preserve">• </w:t></w:r><w:r><w:t xml:space="preserve">c</w:t></w:r></w:p><w:p><w:r><w:t xml:space="preserve">"###);
+  }
+}
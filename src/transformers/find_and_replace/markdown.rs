@@ -0,0 +1,274 @@
+use super::paragraph_styles::{ParagraphKind, ParagraphStyles};
+use super::rich_text::Span;
+use super::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Block {
+  kind: ParagraphKind,
+  text: String,
+}
+
+/// Converts Markdown into runs and paragraphs, see [Value::from_markdown].
+///
+/// A single paragraph is put inline, as runs of the placeholder's paragraph.
+/// Otherwise, the placeholder's paragraph is split, and the blocks are put in between.
+pub(crate) fn to_value(markdown: &str, styles: &ParagraphStyles) -> Value {
+  let blocks = blocks(markdown);
+  let mut relationships = Vec::new();
+
+  if let [Block { kind: ParagraphKind::Body, text }] = blocks.as_slice() {
+    let runs =
+      spans(text).iter().map(|it| it.to_linked_xml(&mut relationships)).collect::<String>();
+    let xml = format!(r#"</w:t></w:r>{runs}<w:r><w:t xml:space="preserve">"#);
+    return Value::with_relationships(xml, relationships);
+  }
+
+  let mut xml = String::from("</w:t></w:r></w:p>");
+  for block in &blocks {
+    let format = styles.format(&block.kind, 0);
+    let mut spans = spans(&block.text);
+    if let Some(size) = format.heading_size {
+      spans = spans.into_iter().map(|span| span.bold().size(size)).collect();
+    }
+    let runs = format.marker.iter().chain(&spans).map(|it| it.to_linked_xml(&mut relationships));
+    xml += &format!("<w:p>{}{}</w:p>", format.properties, runs.collect::<String>());
+  }
+  xml += r#"<w:p><w:r><w:t xml:space="preserve">"#;
+  Value::with_relationships(xml, relationships)
+}
+
+/// Splits the text into paragraphs, headings and list items.
+fn blocks(markdown: &str) -> Vec<Block> {
+  let mut blocks = Vec::new();
+  let mut paragraph = Vec::<&str>::new();
+
+  let flush = |paragraph: &mut Vec<&str>, blocks: &mut Vec<Block>| {
+    if !paragraph.is_empty() {
+      blocks.push(Block { kind: ParagraphKind::Body, text: paragraph.join(" ") });
+      paragraph.clear();
+    }
+  };
+
+  for line in markdown.lines() {
+    let trimmed = line.trim();
+
+    if trimmed.is_empty() {
+      flush(&mut paragraph, &mut blocks);
+      continue;
+    }
+
+    let hashes = trimmed.chars().take_while(|&it| it == '#').count();
+    if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+      flush(&mut paragraph, &mut blocks);
+      let text = trimmed[hashes..].trim().to_owned();
+      blocks.push(Block { kind: ParagraphKind::Heading(hashes), text });
+      continue;
+    }
+
+    if let Some(text) = ["- ", "* ", "+ "].iter().find_map(|it| trimmed.strip_prefix(it)) {
+      flush(&mut paragraph, &mut blocks);
+      blocks.push(Block { kind: ParagraphKind::BulletItem, text: text.trim().to_owned() });
+      continue;
+    }
+
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    let rest = &trimmed[digits..];
+    if digits > 0 && (rest.starts_with(". ") || rest.starts_with(") ")) {
+      flush(&mut paragraph, &mut blocks);
+      let number = trimmed[..digits].parse().unwrap_or_default();
+      let text = rest[2..].trim().to_owned();
+      blocks.push(Block { kind: ParagraphKind::NumberedItem(number), text });
+      continue;
+    }
+
+    // indented lines continue the list item
+    let is_continuation = line.starts_with([' ', '\t']) && paragraph.is_empty();
+    match blocks.last_mut() {
+      Some(Block { kind: ParagraphKind::BulletItem | ParagraphKind::NumberedItem(_), text })
+        if is_continuation =>
+      {
+        text.push(' ');
+        text.push_str(trimmed);
+      }
+      _ => paragraph.push(trimmed),
+    }
+  }
+
+  flush(&mut paragraph, &mut blocks);
+  blocks
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Emphasis {
+  bold: bool,
+  italic: bool,
+}
+
+/// Splits the text by emphasis, strong emphasis, code spans and links.
+fn spans(text: &str) -> Vec<Span> {
+  let chars = text.chars().collect::<Vec<_>>();
+  let mut spans = Vec::new();
+  let mut emphasis = Emphasis::default();
+  let mut buffer = String::new();
+  let mut idx = 0;
+
+  let flush = |buffer: &mut String, spans: &mut Vec<Span>, emphasis: Emphasis| {
+    if !buffer.is_empty() {
+      spans.push(emphasize(Span::new(std::mem::take(buffer)), emphasis));
+    }
+  };
+
+  while idx < chars.len() {
+    let char = chars[idx];
+    let next = chars.get(idx + 1).copied();
+    let prev = idx.checked_sub(1).map(|it| chars[it]);
+
+    match char {
+      '\\' if next.is_some_and(|it| it.is_ascii_punctuation()) => {
+        buffer.extend(next);
+        idx += 2;
+      }
+      '`' => match chars[idx + 1..].iter().position(|&it| it == '`') {
+        Some(len) => {
+          flush(&mut buffer, &mut spans, emphasis);
+          let code = chars[idx + 1..idx + 1 + len].iter().collect::<String>();
+          spans.push(emphasize(Span::new(code).font("Courier New"), emphasis));
+          idx += len + 2;
+        }
+        None => {
+          buffer.push(char);
+          idx += 1;
+        }
+      },
+      '*' | '_' if next == Some(char) => {
+        if toggles(&chars, idx, 2, emphasis.bold) {
+          flush(&mut buffer, &mut spans, emphasis);
+          emphasis.bold = !emphasis.bold;
+        } else {
+          buffer.extend([char, char]);
+        }
+        idx += 2;
+      }
+      // snake_case words are left alone
+      '_' if prev.is_some_and(char::is_alphanumeric) && next.is_some_and(char::is_alphanumeric) => {
+        buffer.push(char);
+        idx += 1;
+      }
+      '*' | '_' => {
+        if toggles(&chars, idx, 1, emphasis.italic) {
+          flush(&mut buffer, &mut spans, emphasis);
+          emphasis.italic = !emphasis.italic;
+        } else {
+          buffer.push(char);
+        }
+        idx += 1;
+      }
+      '[' => match link(&chars[idx..]) {
+        Some((label, url, len)) => {
+          flush(&mut buffer, &mut spans, emphasis);
+          spans.push(emphasize(Span::new(label).link(url), emphasis));
+          idx += len;
+        }
+        None => {
+          buffer.push(char);
+          idx += 1;
+        }
+      },
+      _ => {
+        buffer.push(char);
+        idx += 1;
+      }
+    }
+  }
+
+  flush(&mut buffer, &mut spans, emphasis);
+  spans
+}
+
+/// Checks whether the delimiter of `len` chars at `idx` opens or closes emphasis.
+///
+/// Emphasis is closed by a delimiter following a non-whitespace, it's opened only if
+/// followed by a non-whitespace, and there is a closing delimiter further on. Lone `*` stay as is.
+fn toggles(chars: &[char], idx: usize, len: usize, is_open: bool) -> bool {
+  let is_delimiter = |at: usize| {
+    let char = chars[idx];
+    chars[at..].iter().take(len).all(|&it| it == char)
+      && chars.get(at + len) != Some(&char)
+      && chars[at - 1] != char
+  };
+  let follows_text = |at: usize| at > 0 && !chars[at - 1].is_whitespace();
+
+  if is_open {
+    return follows_text(idx);
+  }
+  let is_followed_by_text = chars.get(idx + len).is_some_and(|it| !it.is_whitespace());
+  is_followed_by_text && (idx + len + 1..chars.len()).any(|at| is_delimiter(at) && follows_text(at))
+}
+
+/// Parses `[label](url)`, returns the label, the url and the number of chars consumed.
+fn link(chars: &[char]) -> Option<(String, String, usize)> {
+  let label_end = chars.iter().position(|&it| it == ']')?;
+  if chars.get(label_end + 1) != Some(&'(') {
+    return None;
+  }
+  let url_len = chars[label_end + 2..].iter().position(|&it| it == ')')?;
+  let label = chars[1..label_end].iter().collect();
+  let url = chars[label_end + 2..label_end + 2 + url_len].iter().collect();
+  Some((label, url, label_end + 3 + url_len))
+}
+
+fn emphasize(mut span: Span, emphasis: Emphasis) -> Span {
+  if emphasis.bold {
+    span = span.bold();
+  }
+  if emphasis.italic {
+    span = span.italic();
+  }
+  span
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn splits_blocks() {
+    let blocks = blocks("# Title\n\nsome\ntext\n\n- a\n  b\n2. c\n");
+    let block = |kind, text: &str| Block { kind, text: text.to_owned() };
+    assert_eq!(
+      blocks,
+      [
+        block(ParagraphKind::Heading(1), "Title"),
+        block(ParagraphKind::Body, "some text"),
+        block(ParagraphKind::BulletItem, "a b"),
+        block(ParagraphKind::NumberedItem(2), "c"),
+      ]
+    );
+  }
+
+  #[test]
+  fn converts_inline_markup() {
+    let value = to_value("**Hi**, *snake_case* `x` [site](https://a.b)", &Default::default());
+    let marker = value.relationships[0].marker();
+    insta::assert_snapshot!(
+      value.xml.replace(&marker, "{rel}"),
+      @r###"</w:t></w:r><w:r><w:rPr><w:b/><w:bCs/></w:rPr><w:t xml:space="preserve">Hi</w:t></w:r><w:r><w:t xml:space="preserve">, </w:t></w:r><w:r><w:rPr><w:i/><w:iCs/></w:rPr><w:t xml:space="preserve">snake_case</w:t></w:r><w:r><w:t xml:space="preserve"> </w:t></w:r><w:r><w:rPr><w:rFonts w:ascii="Courier New" w:hAnsi="Courier New" w:cs="Courier New" w:eastAsia="Courier New"/></w:rPr><w:t xml:space="preserve">x</w:t></w:r><w:r><w:t xml:space="preserve"> </w:t></w:r><w:hyperlink r:id="{rel}"><w:r><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr><w:t xml:space="preserve">site</w:t></w:r></w:hyperlink><w:r><w:t xml:space="preserve">"###,
+    );
+  }
+
+  #[test]
+  fn keeps_unpaired_delimiters() {
+    insta::assert_snapshot!(
+      to_value("2 * 3 * 4, **not closed and *half", &Default::default()).xml,
+      @r###"</w:t></w:r><w:r><w:t xml:space="preserve">2 * 3 * 4, **not closed and *half</w:t></w:r><w:r><w:t xml:space="preserve">"###,
+    );
+  }
+
+  #[test]
+  fn falls_back_to_direct_formatting() {
+    insta::assert_snapshot!(
+      to_value("## Notes\n- one", &ParagraphStyles::unstyled()).xml,
+      @r###"</w:t></w:r></w:p><w:p><w:pPr><w:keepNext/></w:pPr><w:r><w:rPr><w:b/><w:bCs/><w:sz w:val="32"/><w:szCs w:val="32"/></w:rPr><w:t xml:space="preserve">Notes</w:t></w:r></w:p><w:p><w:pPr><w:ind w:left="360"/></w:pPr><w:r><w:t xml:space="preserve">• </w:t></w:r><w:r><w:t xml:space="preserve">one</w:t></w:r></w:p><w:p><w:r><w:t xml:space="preserve">"###,
+    );
+  }
+}
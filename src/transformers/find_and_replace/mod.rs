@@ -1,16 +1,31 @@
+use std::borrow::Cow;
 use std::io;
 use std::ops::Range;
 
+#[cfg(feature = "serde")]
+use crate::transformers::layout::{tokens, Layout};
 use crate::transformers::TransformerError;
-use aho_corasick::Anchored;
-use quick_xml::events::Event;
+use aho_corasick::{Anchored, PatternID};
+use quick_xml::events::{BytesStart, Event};
 
+#[cfg(feature = "serde")]
+pub use self::filters::Filters;
+pub use self::paragraph_styles::ParagraphStyles;
 pub use self::placeholders::Placeholders;
 pub use self::replacements::Replacements;
+pub use self::rich_text::{RichText, Span};
 pub use self::value::Value;
 
+#[cfg(feature = "serde")]
+pub(crate) mod filters;
+mod html;
+mod markdown;
+mod paragraph_styles;
+#[cfg(feature = "serde")]
+pub(crate) mod paths;
 mod placeholders;
 mod replacements;
+mod rich_text;
 mod value;
 
 #[cfg(test)]
@@ -45,6 +60,145 @@ pub struct FindAndReplace<'r> {
   pub replacements: Replacements<'r>,
 }
 
+/// Formatting of a paragraph, which is inherited by the runs and paragraphs a replacement opens.
+#[derive(Debug, Default, Clone)]
+pub struct Styles {
+  /// `<w:pPr>…</w:pPr>` of the paragraph, tags included.
+  pub paragraph: Option<Range<usize>>,
+  /// `<w:rPr>…</w:rPr>` of the run, per each text span of the paragraph.
+  pub runs: Vec<Option<Range<usize>>>,
+}
+
+/// A paragraph being read by [read_paragraphs].
+#[derive(Debug, Default)]
+struct Paragraph {
+  start: usize,
+  text_spans: Vec<Range<usize>>,
+  styles: Styles,
+  run_style: Option<Range<usize>>,
+  in_run: bool,
+}
+
+impl Paragraph {
+  /// Gives the text spans met so far to `on_text`, then forgets them.
+  fn flush<E>(
+    &mut self,
+    on_text: &mut impl FnMut(usize, &[Range<usize>], &Styles) -> Result<(), E>,
+  ) -> Result<(), E> {
+    on_text(self.start, &self.text_spans, &self.styles)?;
+    self.text_spans.clear();
+    self.styles.runs.clear();
+    Ok(())
+  }
+}
+
+/// Reads paragraphs of the stream, giving `<w:t>` spans of each one along with its formatting
+/// to `on_text`, which also receives the start of the paragraph.
+///
+/// Paragraphs of text boxes reside in runs of another paragraph. The outer paragraph's text met
+/// before a text box is given on its own, as a placeholder can't span over a text box.
+pub(crate) fn read_paragraphs<E: From<quick_xml::Error>>(
+  input: &[u8],
+  mut on_text: impl FnMut(usize, &[Range<usize>], &Styles) -> Result<(), E>,
+) -> Result<(), E> {
+  let mut reader = quick_xml::Reader::from_reader(input);
+  reader.config_mut().check_end_names = true;
+
+  // paragraphs of text boxes reside in runs of another paragraph, so they are stacked
+  let mut paragraphs = Vec::<Paragraph>::new();
+
+  loop {
+    let position = reader.buffer_position() as usize;
+    match reader.read_event()? {
+      Event::Start(tag) => match (tag.name().as_ref(), paragraphs.last_mut()) {
+        (b"w:p", outer) => {
+          if let Some(outer) = outer {
+            outer.flush(&mut on_text)?;
+          }
+          paragraphs.push(Paragraph { start: position, ..Paragraph::default() });
+        }
+        (b"w:r", Some(paragraph)) => {
+          paragraph.in_run = true;
+          paragraph.run_style = None;
+        }
+        (b"w:pPr", Some(paragraph)) if !paragraph.in_run => {
+          reader.read_to_end(tag.name())?;
+          paragraph.styles.paragraph = Some(position..reader.buffer_position() as usize);
+        }
+        (b"w:rPr", Some(paragraph)) if paragraph.in_run => {
+          reader.read_to_end(tag.name())?;
+          paragraph.run_style = Some(position..reader.buffer_position() as usize);
+        }
+        (b"w:t", Some(paragraph)) if paragraph.in_run => {
+          let span = reader.read_to_end(tag.name())?;
+          paragraph.text_spans.push(span.start as usize..span.end as usize);
+          paragraph.styles.runs.push(paragraph.run_style.clone());
+        }
+        _ => {}
+      },
+      Event::End(tag) => match (tag.name().as_ref(), paragraphs.last_mut()) {
+        (b"w:p", Some(paragraph)) => {
+          paragraph.flush(&mut on_text)?;
+          paragraphs.pop();
+        }
+        (b"w:r", Some(paragraph)) => paragraph.in_run = false,
+        _ => {}
+      },
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(feature = "serde")]
+impl FindAndReplace<'_> {
+  /// Placeholders derived from json hold neither elements of arrays, nor filters. The ones met
+  /// in the input, like `{items[0].sku}` or `{price | number:2}`, are resolved against the data
+  /// and appended to the patterns.
+  pub(crate) fn with_paths_met(
+    &self,
+    input: &[u8],
+    filters: &Filters,
+  ) -> Result<Option<Self>, TransformerError> {
+    let (Some(brackets), Some(data)) = (&self.placeholders.brackets, &self.replacements.data)
+    else {
+      return Ok(None);
+    };
+    let (open, close) = brackets.as_ref();
+
+    let layout = Layout::read(input)?;
+    let mut found = Vec::<(String, Value)>::new();
+    for paragraph in &layout.paragraphs {
+      let text = paragraph.text(input);
+      for token in tokens(&text, open.as_bytes(), close.as_bytes()) {
+        let token = String::from_utf8_lossy(&text[token]).into_owned();
+        let expression = &token[open.len()..(token.len() - close.len())];
+        if !expression.contains(['[', '|']) || found.iter().any(|(it, _)| *it == token) {
+          continue;
+        }
+        let expression = quick_xml::escape::unescape(expression).unwrap_or(expression.into());
+        if let Some(value) = filters.evaluate(&expression, |path| paths::resolve(data, path)) {
+          found.push((token, Value::from(value)));
+        }
+      }
+    }
+    if found.is_empty() {
+      return Ok(None);
+    }
+
+    let patterns = self.placeholders.patterns.iter().map(String::as_str);
+    let values = (0..self.replacements.len()).map(|idx| self.replacements[idx].clone());
+    Ok(Some(Self {
+      placeholders: Placeholders::from_iter(
+        patterns.chain(found.iter().map(|(it, _)| it.as_str())),
+      ),
+      replacements: Replacements::from_iter(values.chain(found.into_iter().map(|(_, it)| it))),
+    }))
+  }
+}
+
 #[allow(missing_docs)]
 impl<'subs> FindAndReplace<'subs> {
   pub fn transform_stream<In: AsRef<[u8]>, Out: io::Write>(
@@ -52,67 +206,114 @@ impl<'subs> FindAndReplace<'subs> {
     input: In,
     mut output: Out,
   ) -> Result<Out, TransformerError> {
-    let mut reader = quick_xml::Reader::from_reader(input.as_ref());
-    reader.config_mut().check_end_names = true;
+    let input = input.as_ref();
+    // the absolute position over the entire stream
+    let mut reported = 0;
+    read_paragraphs(input, |_, spans, styles: &Styles| {
+      reported = self.transform_paragraph(input, &mut output, spans, styles, reported)?;
+      Ok::<_, TransformerError>(())
+    })?;
 
-    let mut text_spans = Vec::<Range<usize>>::new();
-    let mut in_paragraph = false;
-    let mut in_run = false;
+    // return the tail
+    output.write_all(&input[reported..])?;
+    Ok(output)
+  }
 
-    // the absolute position over the entire stream
+  /// Replaces placeholders inside `Target` attributes of a `.rels` file, like `https://shop/{id}`.
+  pub fn transform_relationships<In: AsRef<[u8]>>(
+    &self,
+    input: In,
+  ) -> Result<Vec<u8>, TransformerError> {
+    let input = input.as_ref();
+    let mut reader = quick_xml::Reader::from_reader(input);
+    let mut output = Vec::with_capacity(input.len());
     let mut reported = 0;
 
     loop {
+      let position = reader.buffer_position() as usize;
       match reader.read_event()? {
-        Event::Start(tag) => match tag.name().as_ref() {
-          b"w:p" => in_paragraph = true,
-          b"w:r" => in_run = true,
-          b"w:t" if in_paragraph && in_run => {
-            let span = reader.read_to_end(tag.name())?;
-            text_spans.push(span.start as usize..span.end as usize);
+        Event::Empty(tag) if tag.name().as_ref() == b"Relationship" => {
+          let Some(target) = tag.try_get_attribute("Target").map_err(quick_xml::Error::from)?
+          else {
+            continue;
+          };
+          let target = target.unescape_value()?;
+          let replaced = self.replace_in_text(&target);
+          if replaced == target {
+            continue;
           }
-          _ => {}
-        },
-        Event::End(tag) => match tag.name().as_ref() {
-          b"w:p" => {
-            reported = self.transform_paragraph(&input, &mut output, &text_spans, reported)?;
-            text_spans.clear();
-            in_paragraph = false;
+
+          let mut patched = BytesStart::new("Relationship");
+          for attribute in tag.attributes() {
+            let attribute = attribute.map_err(quick_xml::Error::from)?;
+            match attribute.key.as_ref() {
+              b"Target" => patched.push_attribute(("Target", replaced.as_str())),
+              _ => patched.push_attribute(attribute),
+            }
           }
-          b"w:r" => in_run = false,
-          _ => {}
-        },
-        Event::Eof => break,
-        _ => {
-          // needs no action, as the content besides <w:p> tags is copied elsewhere
+
+          output.extend_from_slice(&input[reported..position]);
+          output.extend_from_slice(b"<");
+          output.extend_from_slice(&patched);
+          output.extend_from_slice(b"/>");
+          reported = reader.buffer_position() as usize;
         }
+        Event::Eof => break,
+        _ => {}
       }
     }
 
-    // return the tail
-    output.write_all(&input.as_ref()[reported..])?;
+    output.extend_from_slice(&input[reported..]);
     Ok(output)
   }
 
+  /// Replaces placeholders inside a plain text with texts of the values.
+  fn replace_in_text(&self, text: &str) -> String {
+    let Ok(start) = self.placeholders.automaton.start_state(Anchored::No) else {
+      unreachable!("aho-corasick automaton misconfiguration");
+    };
+    let (mut sid, mut result, mut reported) = (start, String::new(), 0);
+
+    for (offset, byte) in text.bytes().enumerate() {
+      sid = self.placeholders.automaton.next_state(Anchored::No, sid, byte);
+      if !self.placeholders.automaton.is_match(sid) {
+        continue;
+      }
+      let pat_id = self.placeholders.automaton.match_pattern(sid, 0);
+      let pat_len = self.placeholders.automaton.pattern_len(pat_id);
+      sid = start;
+
+      result += &text[reported..offset + 1 - pat_len];
+      result += &self.replacements[pat_id.as_usize()].text();
+      reported = offset + 1;
+    }
+
+    result + &text[reported..]
+  }
+
   pub fn transform_paragraph<In: AsRef<[u8]>, Out: io::Write>(
     &self,
     input: In,
     out: &mut Out,
     spans: &[Range<usize>],
+    styles: &Styles,
     mut reported: usize,
   ) -> io::Result<usize> {
+    let input = input.as_ref();
     let Ok(start) = self.placeholders.automaton.start_state(Anchored::No) else {
       unreachable!("aho-corasick automaton misconfiguration");
     };
     let mut sid = start;
 
+    // placeholders met, by their last spans
+    let mut matches = Vec::<(usize, Range<usize>, PatternID)>::new();
     for (span_idx, span) in spans.iter().enumerate() {
       // todo: quick_xml::Decoder::decode(&input[span])
       // don't decode for now, assume it's utf8
       // todo: encode replacements — should probably be done on the lower io level
 
       // span's space offset
-      for (offset, byte) in input.as_ref()[span.clone()].iter().copied().enumerate() {
+      for (offset, byte) in input[span.clone()].iter().copied().enumerate() {
         sid = self.placeholders.automaton.next_state(Anchored::No, sid, byte);
         if !self.placeholders.automaton.is_match(sid) {
           continue;
@@ -121,43 +322,367 @@ impl<'subs> FindAndReplace<'subs> {
         let pat_id = self.placeholders.automaton.match_pattern(sid, 0);
         let pat_len = self.placeholders.automaton.pattern_len(pat_id);
         sid = start;
+        matches.push((span_idx, (offset + 1).saturating_sub(pat_len)..offset + 1, pat_id));
+      }
+    }
 
-        let r#match = Range { start: (offset + 1).saturating_sub(pat_len), end: offset + 1 };
-
-        // if the match is split between K spans, let's go backwards and find the 1st span
-        if r#match.len() < pat_len {
-          let (mut idx, mut bytes_to_consume) = (span_idx, pat_len - r#match.len());
-          loop {
-            idx -= 1;
-            if bytes_to_consume > spans[idx].len() {
-              bytes_to_consume -= spans[idx].len();
-            } else {
-              break;
-            }
-          }
+    let style = |range: Option<&Range<usize>>| range.map_or(&[][..], |it| &input[it.clone()]);
+    let replacements = matches.iter().map(|(span_idx, _, pat_id)| {
+      let replacement = self.replacements[pat_id.as_usize()].xml.as_bytes();
+      restyle(replacement, style(styles.paragraph.as_ref()), style(styles.runs[*span_idx].as_ref()))
+    });
+    let mut replacements = replacements.collect::<Vec<_>>();
 
-          let (first_span_idx, tail_len) = (idx, bytes_to_consume);
+    // section properties move to the paragraph closed by the placeholder's `</w:p>`
+    let section = styles.paragraph.as_ref().and_then(|paragraph| {
+      let section = section_properties(&input[paragraph.clone()])?;
+      Some(paragraph.start + section.start..paragraph.start + section.end)
+    });
+    let moved = replacements.iter().rposition(|(_, reopened)| reopened.is_some());
+    let section = section.filter(|it| it.start >= reported && moved.is_some());
+    if let (Some(section), Some((xml, Some((at, wrap))))) =
+      (&section, moved.map(|it| &mut replacements[it]))
+    {
+      let properties = match wrap {
+        true => [b"<w:pPr>", &input[section.clone()], b"</w:pPr>"].concat(),
+        false => input[section.clone()].to_vec(),
+      };
+      xml.to_mut().splice(*at..*at, properties);
+    }
+    // writes the input, except for the section properties if they are moved
+    let copy = |out: &mut Out, range: Range<usize>| match &section {
+      Some(section) if range.start <= section.start && section.end <= range.end => {
+        out.write_all(&input[range.start..section.start])?;
+        out.write_all(&input[section.end..range.end])
+      }
+      _ => out.write_all(&input[range]),
+    };
 
-          for (idx, span) in spans[first_span_idx..span_idx].iter().enumerate() {
-            // for the 1st span we output the internal text as is, excluding the tail
-            // for the 2nd, 3rd, ..., (K-1)-th spans we omit the internal text
-            let until = if idx == 0 { span.end - tail_len } else { span.start };
-            out.write_all(&input.as_ref()[reported..until])?;
-            reported = span.end;
+    for ((span_idx, r#match, pat_id), (replacement, _)) in matches.into_iter().zip(replacements) {
+      let span = &spans[span_idx];
+      let pat_len = self.placeholders.automaton.pattern_len(pat_id);
+
+      // if the match is split between K spans, let's go backwards and find the 1st span
+      if r#match.len() < pat_len {
+        let (mut idx, mut bytes_to_consume) = (span_idx, pat_len - r#match.len());
+        loop {
+          idx -= 1;
+          if bytes_to_consume > spans[idx].len() {
+            bytes_to_consume -= spans[idx].len();
+          } else {
+            break;
           }
         }
 
-        // for K-th span we put the replacement instead of the match
-        out.write_all(&input.as_ref()[reported..(span.start + r#match.start)])?;
+        let (first_span_idx, tail_len) = (idx, bytes_to_consume);
 
-        let replacement = match &self.replacements[pat_id.as_usize()] {
-          Value(xml) => xml.as_bytes(),
-        };
-        out.write_all(replacement)?;
-        reported = span.start + r#match.end;
+        for (idx, span) in spans[first_span_idx..span_idx].iter().enumerate() {
+          // for the 1st span we output the internal text as is, excluding the tail
+          // for the 2nd, 3rd, ..., (K-1)-th spans we omit the internal text
+          let until = if idx == 0 { span.end - tail_len } else { span.start };
+          copy(out, reported..until)?;
+          reported = span.end;
+        }
       }
+
+      // for K-th span we put the replacement instead of the match
+      copy(out, reported..(span.start + r#match.start))?;
+      out.write_all(&replacement)?;
+      reported = span.start + r#match.end;
     }
 
     Ok(reported)
   }
 }
+
+/// Puts the placeholder's formatting into `<w:p>` and `<w:r>` tags of the replacement,
+/// so the inserted paragraphs and runs look like the placeholder did.
+///
+/// Runs defining own `<w:rPr>` get the properties merged, explicit ones take precedence,
+/// the same goes for paragraphs defining own `<w:pPr>`. Paragraphs having own style or numbering
+/// don't inherit the placeholder's ones. Paragraphs and runs nested into other elements,
+/// like tables, are left as is.
+pub(crate) fn inherit_styles<'x>(
+  xml: &'x [u8],
+  paragraph_style: &[u8],
+  run_style: &[u8],
+) -> Cow<'x, [u8]> {
+  restyle(xml, paragraph_style, run_style).0
+}
+
+/// Works like [inherit_styles], also returns where section properties of the placeholder's
+/// paragraph go if the replacement closes it and reopens another one: the position in the result
+/// and whether they need a `<w:pPr>` around.
+fn restyle<'x>(
+  xml: &'x [u8],
+  paragraph_style: &[u8],
+  run_style: &[u8],
+) -> (Cow<'x, [u8]>, Option<(usize, bool)>) {
+  const PARAGRAPH: &[u8] = b"<w:p>";
+  const PARAGRAPH_STYLE: &[u8] = b"<w:pPr>";
+  const PARAGRAPH_STYLE_END: &[u8] = b"</w:pPr>";
+  const RUN: &[u8] = b"<w:r>";
+  const RUN_STYLE: &[u8] = b"<w:rPr>";
+  const RUN_STYLE_END: &[u8] = b"</w:rPr>";
+  // elements which may wrap runs of a paragraph
+  const RUN_WRAPPERS: &[&[u8]] = &[b"w:hyperlink", b"w:fldSimple"];
+
+  let opens = |tag: &[u8], at: usize| xml[at..].starts_with(tag);
+  if !(0..xml.len()).any(|at| opens(PARAGRAPH, at) || opens(RUN, at)) {
+    return (Cow::Borrowed(xml), None);
+  }
+
+  // section properties end the section, only the paragraph closing it may hold them
+  let paragraph_style = without_section_properties(paragraph_style);
+  let inherited_paragraph = paragraph_style
+    .strip_prefix(PARAGRAPH_STYLE)
+    .and_then(|it| it.strip_suffix(PARAGRAPH_STYLE_END))
+    .unwrap_or_default();
+  let inherited = run_style
+    .strip_prefix(RUN_STYLE)
+    .and_then(|it| it.strip_suffix(RUN_STYLE_END))
+    .unwrap_or_default();
+
+  let mut result = Vec::with_capacity(xml.len() + paragraph_style.len() + run_style.len());
+  // elements opened by the replacement, the ones it closes enclose the placeholder
+  let mut opened = Vec::<&[u8]>::new();
+  // whether the placeholder's paragraph is closed, and the end of the reopened one's properties
+  let (mut closed, mut reopened) = (false, None);
+  let mut at = 0;
+  while at < xml.len() {
+    let is_top_level = opened.is_empty();
+    let is_run_top_level = opened
+      .iter()
+      .enumerate()
+      .all(|(idx, it)| idx == 0 && *it == b"w:p" || RUN_WRAPPERS.contains(it));
+
+    if is_top_level && opens(PARAGRAPH, at) && !opens(b"<w:pPr", at + PARAGRAPH.len()) {
+      result.extend_from_slice(PARAGRAPH);
+      result.extend_from_slice(&paragraph_style);
+      reopened = match paragraph_style.ends_with(PARAGRAPH_STYLE_END) {
+        true => Some((result.len() - PARAGRAPH_STYLE_END.len(), false)),
+        false => Some((result.len(), true)),
+      };
+      opened.push(b"w:p");
+      at += PARAGRAPH.len();
+    } else if is_top_level && opens(PARAGRAPH, at) && opens(PARAGRAPH_STYLE, at + PARAGRAPH.len()) {
+      let from = at + PARAGRAPH.len() + PARAGRAPH_STYLE.len();
+      let Some(until) = find(&xml[from..], PARAGRAPH_STYLE_END).map(|pos| from + pos) else {
+        result.extend_from_slice(&xml[at..]);
+        break;
+      };
+      let explicit = &xml[from..until];
+      // the paragraph's own kind, like a heading or a list item, takes precedence
+      let has_kind = children(explicit).iter().any(|(name, _)| is_kind(name));
+      let inherited = children(inherited_paragraph).into_iter();
+      let inherited = inherited.filter(|(name, _)| !(has_kind && is_kind(name)));
+      result.extend_from_slice(PARAGRAPH);
+      result.extend_from_slice(PARAGRAPH_STYLE);
+      result.extend_from_slice(&merge_properties(inherited, explicit, PARAGRAPH_PROPERTIES_ORDER));
+      reopened = Some((result.len(), false));
+      result.extend_from_slice(PARAGRAPH_STYLE_END);
+      opened.push(b"w:p");
+      at = until + PARAGRAPH_STYLE_END.len();
+    } else if is_run_top_level && opens(RUN, at) && opens(RUN_STYLE, at + RUN.len()) {
+      let from = at + RUN.len() + RUN_STYLE.len();
+      let Some(until) = find(&xml[from..], RUN_STYLE_END).map(|pos| from + pos) else {
+        result.extend_from_slice(&xml[at..]);
+        break;
+      };
+      let inherited = children(inherited).into_iter();
+      result.extend_from_slice(RUN);
+      result.extend_from_slice(RUN_STYLE);
+      result.extend_from_slice(&merge_properties(
+        inherited,
+        &xml[from..until],
+        RUN_PROPERTIES_ORDER,
+      ));
+      result.extend_from_slice(RUN_STYLE_END);
+      opened.push(b"w:r");
+      at = until + RUN_STYLE_END.len();
+    } else if is_run_top_level && opens(RUN, at) && opens(b"<w:rPr/>", at + RUN.len()) {
+      result.extend_from_slice(RUN);
+      result.extend_from_slice(run_style);
+      opened.push(b"w:r");
+      at += RUN.len() + b"<w:rPr/>".len();
+    } else if is_run_top_level && opens(RUN, at) && !opens(b"<w:rPr", at + RUN.len()) {
+      result.extend_from_slice(RUN);
+      result.extend_from_slice(run_style);
+      opened.push(b"w:r");
+      at += RUN.len();
+    } else if xml[at] == b'<' {
+      let end = find(&xml[at..], b">").map_or(xml.len(), |pos| at + pos + 1);
+      let tag = &xml[at..end];
+      let name = tag[1..].split(|&it| matches!(it, b' ' | b'/' | b'>')).next().unwrap_or_default();
+      match tag.get(1) {
+        Some(b'/') => {
+          if tag == b"</w:p>" && opened.len() <= 1 {
+            closed |= opened.is_empty();
+            reopened = None;
+          }
+          opened.pop();
+        }
+        Some(b'?' | b'!') => {}
+        _ if tag.ends_with(b"/>") => {}
+        _ => opened.push(name),
+      }
+      result.extend_from_slice(tag);
+      at = end;
+    } else {
+      result.push(xml[at]);
+      at += 1;
+    }
+  }
+  (Cow::Owned(result), reopened.filter(|_| closed))
+}
+
+/// Properties defining what the paragraph is, like a heading or a list item.
+fn is_kind(name: &[u8]) -> bool {
+  name == b"w:pStyle" || name == b"w:numPr"
+}
+
+/// Merges children of two properties tags, keeping the order prescribed by the schema.
+fn merge_properties(
+  inherited: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+  explicit: &[u8],
+  order: &[&str],
+) -> Vec<u8> {
+  let mut merged = children(explicit);
+  for (name, xml) in inherited {
+    if !merged.iter().any(|(it, _)| *it == name) {
+      merged.push((name, xml));
+    }
+  }
+  merged.sort_by_key(|(name, _)| {
+    order.iter().position(|it| it.as_bytes() == name.as_slice()).unwrap_or(usize::MAX)
+  });
+  merged.into_iter().flat_map(|(_, xml)| xml).collect()
+}
+
+/// Top-level elements of an XML fragment along with their markup.
+fn children(xml: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+  let mut reader = quick_xml::Reader::from_reader(xml);
+  let mut children = Vec::new();
+  loop {
+    let start = reader.buffer_position() as usize;
+    let name = match reader.read_event() {
+      Ok(Event::Start(tag)) => {
+        let name = tag.name().as_ref().to_vec();
+        if reader.read_to_end(tag.name()).is_err() {
+          break;
+        }
+        name
+      }
+      Ok(Event::Empty(tag)) => tag.name().as_ref().to_vec(),
+      Ok(Event::Eof) | Err(_) => break,
+      Ok(_) => continue,
+    };
+    children.push((name, xml[start..reader.buffer_position() as usize].to_vec()));
+  }
+  children
+}
+
+/// Children of `<w:pPr>` in the order of the schema.
+const PARAGRAPH_PROPERTIES_ORDER: &[&str] = &[
+  "w:pStyle",
+  "w:keepNext",
+  "w:keepLines",
+  "w:pageBreakBefore",
+  "w:framePr",
+  "w:widowControl",
+  "w:numPr",
+  "w:suppressLineNumbers",
+  "w:pBdr",
+  "w:shd",
+  "w:tabs",
+  "w:suppressAutoHyphens",
+  "w:kinsoku",
+  "w:wordWrap",
+  "w:overflowPunct",
+  "w:topLinePunct",
+  "w:autoSpaceDE",
+  "w:autoSpaceDN",
+  "w:bidi",
+  "w:adjustRightInd",
+  "w:snapToGrid",
+  "w:spacing",
+  "w:ind",
+  "w:contextualSpacing",
+  "w:mirrorIndents",
+  "w:suppressOverlap",
+  "w:jc",
+  "w:textDirection",
+  "w:textAlignment",
+  "w:textboxTightWrap",
+  "w:outlineLvl",
+  "w:divId",
+  "w:cnfStyle",
+  "w:rPr",
+  "w:sectPr",
+  "w:pPrChange",
+];
+
+/// Children of `<w:rPr>` in the order of the schema.
+const RUN_PROPERTIES_ORDER: &[&str] = &[
+  "w:rStyle",
+  "w:rFonts",
+  "w:b",
+  "w:bCs",
+  "w:i",
+  "w:iCs",
+  "w:caps",
+  "w:smallCaps",
+  "w:strike",
+  "w:dstrike",
+  "w:outline",
+  "w:shadow",
+  "w:emboss",
+  "w:imprint",
+  "w:noProof",
+  "w:snapToGrid",
+  "w:vanish",
+  "w:webHidden",
+  "w:color",
+  "w:spacing",
+  "w:w",
+  "w:kern",
+  "w:position",
+  "w:sz",
+  "w:szCs",
+  "w:highlight",
+  "w:u",
+  "w:effect",
+  "w:bdr",
+  "w:shd",
+  "w:fitText",
+  "w:vertAlign",
+  "w:rtl",
+  "w:cs",
+  "w:em",
+  "w:lang",
+  "w:eastAsianLayout",
+  "w:specVanish",
+  "w:oMath",
+];
+
+fn without_section_properties(style: &[u8]) -> Vec<u8> {
+  match section_properties(style) {
+    Some(section) => [&style[..section.start], &style[section.end..]].concat(),
+    None => style.to_vec(),
+  }
+}
+
+/// Position of `<w:sectPr>…</w:sectPr>` inside paragraph properties.
+fn section_properties(style: &[u8]) -> Option<Range<usize>> {
+  let start = find(style, b"<w:sectPr")?;
+  let tag_end = find(&style[start..], b">").map(|pos| start + pos + 1)?;
+  let end = match style[tag_end - 2] {
+    b'/' => tag_end,
+    _ => find(style, b"</w:sectPr>").map_or(style.len(), |pos| pos + b"</w:sectPr>".len()),
+  };
+  Some(start..end)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack.windows(needle.len()).position(|window| window == needle)
+}
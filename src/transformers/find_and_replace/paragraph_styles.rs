@@ -0,0 +1,114 @@
+use quick_xml::escape::escape;
+
+use super::rich_text::Span;
+
+/// Paragraph styles given to headings and list items converted from Markdown or HTML.
+///
+/// Built-in style ids are used by default: `Heading1`…`Heading6`, `ListBullet` and `ListNumber`.
+/// Styles of a particular document can be picked up by
+/// [DocxFile::paragraph_styles](crate::DocxFile::paragraph_styles). If a style is missing,
+/// direct formatting is applied: headings become bold and larger, list items get markers.
+///
+/// Items of nested lists are put on the deeper levels of the list's numbering.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParagraphStyles {
+  /// Style ids of the headings, from the 1st level to the 6th.
+  pub headings: [Option<String>; 6],
+  /// Style id of the bulleted list items.
+  pub bullet_list: Option<String>,
+  /// Style id of the numbered list items.
+  pub numbered_list: Option<String>,
+  /// Numbering id `w:numId` of the bulleted list style, otherwise it's taken from the style.
+  pub bullet_numbering: Option<String>,
+  /// Numbering id `w:numId` of the numbered list style, otherwise it's taken from the style.
+  pub numbered_numbering: Option<String>,
+}
+
+impl Default for ParagraphStyles {
+  fn default() -> Self {
+    Self {
+      headings: std::array::from_fn(|idx| Some(format!("Heading{}", idx + 1))),
+      bullet_list: Some("ListBullet".to_owned()),
+      numbered_list: Some("ListNumber".to_owned()),
+      bullet_numbering: None,
+      numbered_numbering: None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ParagraphKind {
+  Body,
+  /// A heading of the level from 1 to 6.
+  Heading(usize),
+  BulletItem,
+  /// A numbered list item, along with its number.
+  NumberedItem(usize),
+}
+
+/// Formatting of a paragraph, either by a style, or direct when the style is missing.
+pub(crate) struct ParagraphFormat {
+  /// `<w:pPr>…</w:pPr>`, if any.
+  pub properties: String,
+  /// A text put in front of the list item, like `•` or `1.`.
+  pub marker: Option<Span>,
+  /// Font size of the heading in points.
+  pub heading_size: Option<f32>,
+}
+
+impl ParagraphStyles {
+  /// No paragraph styles, direct formatting is used instead.
+  pub fn unstyled() -> Self {
+    Self {
+      headings: Default::default(),
+      bullet_list: None,
+      numbered_list: None,
+      bullet_numbering: None,
+      numbered_numbering: None,
+    }
+  }
+
+  /// Formatting of the paragraph, list items are put on the `depth` level of nested lists.
+  pub(crate) fn format(&self, kind: &ParagraphKind, depth: usize) -> ParagraphFormat {
+    let (style, numbering) = match kind {
+      ParagraphKind::Body => (None, None),
+      ParagraphKind::Heading(level) => (self.headings[level - 1].as_deref(), None),
+      ParagraphKind::BulletItem => (self.bullet_list.as_deref(), self.bullet_numbering.as_deref()),
+      ParagraphKind::NumberedItem(_) => {
+        (self.numbered_list.as_deref(), self.numbered_numbering.as_deref())
+      }
+    };
+
+    let mut format =
+      ParagraphFormat { properties: String::new(), marker: None, heading_size: None };
+    match (style, kind) {
+      (Some(style), _) => {
+        let numbering = match numbering {
+          Some(id) => {
+            let id = escape(id);
+            format!(r#"<w:numPr><w:ilvl w:val="{depth}"/><w:numId w:val="{id}"/></w:numPr>"#)
+          }
+          // the style's numbering is used
+          None if depth > 0 => format!(r#"<w:numPr><w:ilvl w:val="{depth}"/></w:numPr>"#),
+          None => String::new(),
+        };
+        format.properties =
+          format!(r#"<w:pPr><w:pStyle w:val="{}"/>{numbering}</w:pPr>"#, escape(style));
+      }
+      (None, ParagraphKind::Body) => {}
+      (None, ParagraphKind::Heading(level)) => {
+        format.properties = "<w:pPr><w:keepNext/></w:pPr>".to_owned();
+        // 20pt for the 1st level down to 11pt for the 6th one
+        format.heading_size = Some([20.0, 16.0, 14.0, 13.0, 12.0, 11.0][level - 1]);
+      }
+      (None, ParagraphKind::BulletItem | ParagraphKind::NumberedItem(_)) => {
+        format.properties = format!(r#"<w:pPr><w:ind w:left="{}"/></w:pPr>"#, 360 * (depth + 1));
+        format.marker = Some(Span::new(match kind {
+          ParagraphKind::NumberedItem(number) => format!("{number}. "),
+          _ => "• ".to_owned(),
+        }));
+      }
+    }
+    format
+  }
+}
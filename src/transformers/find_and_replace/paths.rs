@@ -0,0 +1,113 @@
+use serde_json::{Map, Value as JsonValue};
+
+/// Lists the fields of a json object, and the fields of nested objects and arrays,
+/// along with their paths like `client.address.city` or `items[0].sku`.
+///
+/// Parents precede their children, so the order is stable and predictable.
+pub fn flatten(value: &JsonValue) -> Vec<(String, &JsonValue)> {
+  let mut paths = Vec::new();
+  if let JsonValue::Object(fields) = value {
+    flatten_fields(fields, "", true, &mut paths);
+  }
+  paths
+}
+
+/// Same as [flatten], but arrays are listed as a whole, without their elements.
+///
+/// Paths of the elements, like `items[0].sku`, are meant to be [resolved](resolve) on demand.
+pub fn fields(value: &JsonValue) -> Vec<(String, &JsonValue)> {
+  let mut paths = Vec::new();
+  if let JsonValue::Object(fields) = value {
+    flatten_fields(fields, "", false, &mut paths);
+  }
+  paths
+}
+
+fn flatten_fields<'v>(
+  fields: &'v Map<String, JsonValue>,
+  prefix: &str,
+  with_items: bool,
+  paths: &mut Vec<(String, &'v JsonValue)>,
+) {
+  for (key, value) in fields {
+    let path = if prefix.is_empty() { key.clone() } else { format!("{prefix}.{key}") };
+    flatten_value(value, path, with_items, paths);
+  }
+}
+
+fn flatten_value<'v>(
+  value: &'v JsonValue,
+  path: String,
+  with_items: bool,
+  paths: &mut Vec<(String, &'v JsonValue)>,
+) {
+  paths.push((path.clone(), value));
+  match value {
+    JsonValue::Object(fields) => flatten_fields(fields, &path, with_items, paths),
+    JsonValue::Array(items) if with_items => {
+      for (idx, item) in items.iter().enumerate() {
+        flatten_value(item, format!("{path}[{idx}]"), with_items, paths);
+      }
+    }
+    _ => {}
+  }
+}
+
+/// Finds a value by its path, like `client.address.city` or `items[0].sku`.
+///
+/// Keys containing dots, like `key.with.dots`, take precedence over the nested ones.
+pub fn resolve<'v>(value: &'v JsonValue, path: &str) -> Option<&'v JsonValue> {
+  if let Some(value) = value.get(path) {
+    return Some(value);
+  }
+
+  let mut current = value;
+  for segment in path.split('.') {
+    let (key, mut indexes) = match segment.find('[') {
+      Some(pos) => (&segment[..pos], &segment[pos..]),
+      None => (segment, ""),
+    };
+
+    if !key.is_empty() {
+      current = current.get(key)?;
+    }
+
+    while let Some(rest) = indexes.strip_prefix('[') {
+      let (idx, tail) = rest.split_once(']')?;
+      current = current.get(idx.trim().parse::<usize>().ok()?)?;
+      indexes = tail;
+    }
+
+    if !indexes.is_empty() {
+      return None;
+    }
+  }
+
+  Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn flattens_nested_values() {
+    let value = json!({ "id": 1, "client": { "name": "Ann" }, "items": [{ "sku": "A1" }] });
+    let paths = flatten(&value).into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+    assert_eq!(paths, ["id", "client", "client.name", "items", "items[0]", "items[0].sku"]);
+
+    let paths = fields(&value).into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+    assert_eq!(paths, ["id", "client", "client.name", "items"]);
+  }
+
+  #[test]
+  fn resolves_paths() {
+    let value = json!({ "client": { "address": { "city": "Oslo" } }, "items": [[1, 2]], "a.b": 3 });
+    assert_eq!(resolve(&value, "client.address.city"), Some(&json!("Oslo")));
+    assert_eq!(resolve(&value, "items[0][1]"), Some(&json!(2)));
+    assert_eq!(resolve(&value, "a.b"), Some(&json!(3)));
+    assert_eq!(resolve(&value, "client.phone"), None);
+    assert_eq!(resolve(&value, "items[x]"), None);
+  }
+}
@@ -4,7 +4,7 @@ use std::sync::Arc;
 use aho_corasick::automaton::Automaton;
 use aho_corasick::{dfa, nfa, BuildError};
 #[cfg(feature = "serde")]
-use {crate::CantSerializeError, serde::Serialize};
+use {super::paths, crate::CantSerializeError, serde::Serialize};
 
 /// Is used to pattern match `{placeholders}` against the incoming stream of bytes.
 ///
@@ -12,6 +12,10 @@ use {crate::CantSerializeError, serde::Serialize};
 #[derive(Clone)]
 pub struct Placeholders {
   pub(crate) automaton: Arc<dyn Automaton>,
+  /// Patterns in the order of their ids, kept to report the unmatched ones.
+  pub(crate) patterns: Arc<[String]>,
+  /// Brackets of the patterns, to find the tokens which aren't listed, like `{items[0]}`.
+  pub(crate) brackets: Option<Arc<(String, String)>>,
 }
 
 impl Default for Placeholders {
@@ -85,9 +89,13 @@ impl Placeholders {
   /// ```
   #[allow(clippy::should_implement_trait)]
   pub fn from_iter<P: AsRef<[u8]>, I: IntoIterator<Item = P>>(placeholders: I) -> Self {
+    let patterns = placeholders.into_iter().collect::<Vec<_>>();
     // as it fails only on extreme values, we unwrap for better api
-    let automaton = Self::build(placeholders).unwrap();
-    Self { automaton }
+    let automaton = Self::build(&patterns).unwrap();
+    let patterns = patterns.iter().map(|it| String::from_utf8_lossy(it.as_ref()).into_owned());
+    let patterns = patterns.collect::<Arc<[String]>>();
+    let brackets = shared_brackets(&patterns).map(Arc::new);
+    Self { automaton, patterns, brackets }
   }
 
   /// Build placeholders from an iterator.
@@ -106,15 +114,26 @@ impl Placeholders {
     placeholders: I,
   ) -> Self {
     let placeholders = placeholders.into_iter().map(|pattern| {
-      let mut new = Vec::with_capacity(
-        open_bracket.as_bytes().len() + close_bracket.as_bytes().len() + pattern.as_ref().len(),
-      );
+      let mut new =
+        Vec::with_capacity(open_bracket.len() + close_bracket.len() + pattern.as_ref().len());
       new.extend_from_slice(open_bracket.as_bytes());
       new.extend_from_slice(pattern.as_ref());
       new.extend_from_slice(close_bracket.as_bytes());
       new
     });
-    Self::from_iter(placeholders)
+    Self::from_iter(placeholders).with_brackets(open_bracket, close_bracket)
+  }
+
+  /// Sets brackets of the tokens reported if left unfilled, for patterns which don't share ones,
+  /// like `${id}` and `{price}`.
+  ///
+  /// ```rust
+  ///# use crate::docx_template::Placeholders;
+  /// Placeholders::from_iter(["${id}", "{price}"]).with_brackets("{", "}");
+  /// ```
+  pub fn with_brackets(self, open_bracket: &str, close_bracket: &str) -> Self {
+    let brackets = (open_bracket.to_owned(), close_bracket.to_owned());
+    Self { brackets: Some(Arc::new(brackets)), ..self }
   }
 }
 
@@ -140,14 +159,14 @@ impl Placeholders {
   /// Placeholders::from_iter(["{{id}}", "{{price}}", "{{consumer_name}}", "{{seller_name}}"]);
   /// ```
   pub fn from_json_keys(value: &serde_json::Value) -> Self {
-    match value {
-      serde_json::Value::Object(map) => Placeholders::from_iter(map.keys()),
-      _ => Placeholders::default(),
-    }
+    Placeholders::from_iter(paths::fields(value).into_iter().map(|(path, _)| path))
   }
 
   /// Derive placeholders from keys of a json object.
   ///
+  /// Nested objects and arrays are reachable by paths, like `{{consumer.name}}` or `{{items[0]}}`.
+  /// Elements of arrays are looked up when met in the document, they aren't listed upfront.
+  ///
   /// ```rust
   ///# use docx_template::Placeholders;
   /// use serde::Serialize;
@@ -156,26 +175,22 @@ impl Placeholders {
   /// let value = json!({
   ///     "id": 42,
   ///     "price": 13.37,
-  ///     "consumer_name": "Ryan Gosling",
-  ///     "seller_name": "John Doe",
+  ///     "consumer": { "name": "Ryan Gosling" },
+  ///     "items": ["pen"],
   /// });
   ///
   /// Placeholders::from_json_keys_with_brackets("{{", "}}", &value);
   ///
   /// // same as
-  /// Placeholders::from_iter(["{{id}}", "{{price}}", "{{consumer_name}}", "{{seller_name}}"]);
+  /// Placeholders::from_iter(["{{id}}", "{{price}}", "{{consumer}}", "{{consumer.name}}", "{{items}}"]);
   /// ```
   pub fn from_json_keys_with_brackets(
     open_bracket: &str,
     close_bracket: &str,
     value: &serde_json::Value,
   ) -> Self {
-    match value {
-      serde_json::Value::Object(map) => {
-        Placeholders::from_iter_with_brackets(open_bracket, close_bracket, map.keys())
-      }
-      _ => Placeholders::default(),
-    }
+    let paths = paths::fields(value).into_iter().map(|(path, _)| path);
+    Placeholders::from_iter_with_brackets(open_bracket, close_bracket, paths)
   }
 
   /// Derive placeholders from keys of a serializable `struct`.
@@ -207,18 +222,17 @@ impl Placeholders {
 
   /// Derive placeholders from keys of a serializable `struct`.
   ///
+  /// Fields of nested structs are reachable by dotted paths.
+  ///
   /// ```rust
   ///# use docx_template::Placeholders;
   /// use serde::Serialize;
-  /// use serde_with::with_prefix;
   ///
   /// #[derive(Default, Serialize)]
   /// struct Invoice {
   ///     id: i64,
   ///     price: f64,
-  ///     #[serde(flatten, with = "consumer_")]
   ///     consumer: Person,
-  ///     #[serde(flatten, with = "seller_")]
   ///     seller: Person,
   /// }
   ///
@@ -227,13 +241,14 @@ impl Placeholders {
   ///     name: String,
   /// }
   ///
-  /// serde_with::with_prefix!(consumer_ "consumer_");
-  /// serde_with::with_prefix!(seller_ "seller_");
-  ///
   /// Placeholders::from_struct_keys_with_brackets::<Invoice>("{{", "}}").unwrap();
   ///
   /// // same as
-  /// Placeholders::from_iter(["{{id}}", "{{price}}", "{{consumer_name}}", "{{seller_name}}"]);
+  /// Placeholders::from_iter([
+  ///   "{{id}}", "{{price}}",
+  ///   "{{consumer}}", "{{consumer.name}}",
+  ///   "{{seller}}", "{{seller.name}}",
+  /// ]);
   /// ```
   pub fn from_struct_keys_with_brackets<D: Default + Serialize>(
     open_bracket: &str,
@@ -250,6 +265,32 @@ impl<A: AsRef<[u8]>> FromIterator<A> for Placeholders {
   }
 }
 
+/// Leading and trailing punctuation shared by all the patterns, like `{{` and `}}` of `{{id}}`.
+/// Returns `None` unless both brackets are non-empty.
+fn shared_brackets(patterns: &[String]) -> Option<(String, String)> {
+  let edges = |pattern: &str| {
+    let is_bracket = |c: &char| c.is_ascii_punctuation() && *c != '_';
+    let open = pattern.chars().take_while(is_bracket).collect::<String>();
+    let close = pattern.chars().rev().take_while(is_bracket).collect::<Vec<_>>();
+    match open.len() == pattern.len() {
+      true => (String::new(), String::new()),
+      false => (open, close.into_iter().rev().collect::<String>()),
+    }
+  };
+
+  let (mut open, mut close) = edges(patterns.first()?);
+  for pattern in &patterns[1..] {
+    let (other_open, other_close) = edges(pattern);
+    while !other_open.starts_with(&open) {
+      open.pop();
+    }
+    while !other_close.ends_with(&close) {
+      close.remove(0);
+    }
+  }
+  (!open.is_empty() && !close.is_empty()).then_some((open, close))
+}
+
 #[cfg(feature = "serde")]
 impl<'a> From<&'a serde_json::Value> for Placeholders {
   fn from(value: &'a serde_json::Value) -> Self {
@@ -260,6 +301,25 @@ impl<'a> From<&'a serde_json::Value> for Placeholders {
 #[cfg(feature = "serde")]
 impl<'a> From<&'a serde_json::Map<String, serde_json::Value>> for Placeholders {
   fn from(map: &'a serde_json::Map<String, serde_json::Value>) -> Self {
-    Placeholders::from_iter(map.keys())
+    Placeholders::from_json_keys(&serde_json::Value::Object(map.clone()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn derives_brackets_from_patterns() {
+    let brackets = |placeholders: Placeholders| placeholders.brackets.as_deref().cloned();
+    let pair = |open: &str, close: &str| Some((open.to_owned(), close.to_owned()));
+
+    assert_eq!(brackets(Placeholders::from_iter(["{{id}}", "{{ price }}"])), pair("{{", "}}"));
+    assert_eq!(brackets(Placeholders::from_iter(["${id}", "{price}"])), None);
+    assert_eq!(brackets(Placeholders::from_iter(["ID", "PRICE"])), None);
+    let mixed = Placeholders::from_iter(["${id}", "{price}"]).with_brackets("{", "}");
+    assert_eq!(brackets(mixed), pair("{", "}"));
+    assert_eq!(brackets(Placeholders::from_iter_with_brackets("<", ">", ["id"])), pair("<", ">"));
+    assert_eq!(brackets(Placeholders::default()), None);
   }
 }
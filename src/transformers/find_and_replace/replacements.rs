@@ -1,42 +1,59 @@
+#[cfg(feature = "serde")]
+use super::paths;
 use super::value::Value;
+use crate::relationships::Relationship;
 #[cfg(feature = "serde")]
 use serde::Serialize;
 #[cfg(feature = "serde")]
 use serde_json::Value as JsonValue;
 use std::borrow::Cow;
 use std::ops::Index;
+#[cfg(feature = "serde")]
+use std::sync::Arc;
 
 /// Struct of values to fill in placeholders during rendering.
 #[derive(Debug, Default, Clone)]
 pub struct Replacements<'a> {
   /// The order is important as indexes are encoded at `Placeholders`' automatons
   values: Cow<'a, [Value]>,
+  /// The object values were derived from, to resolve the indexed paths like `{items[0]}`.
+  #[cfg(feature = "serde")]
+  pub(crate) data: Option<Arc<JsonValue>>,
 }
 
 impl<'a> Replacements<'a> {
   #[allow(missing_docs)]
   pub fn from_slice<I: Into<Cow<'a, [Value]>>>(slice: I) -> Self {
-    Self { values: slice.into() }
+    Self {
+      values: slice.into(),
+      #[cfg(feature = "serde")]
+      data: None,
+    }
   }
 
   #[allow(missing_docs)]
   #[allow(clippy::should_implement_trait)]
   pub fn from_iter<V: Into<Value>, I: IntoIterator<Item = V>>(iter: I) -> Self {
-    Self { values: iter.into_iter().map(Into::into).collect() }
+    Self {
+      values: iter.into_iter().map(Into::into).collect(),
+      #[cfg(feature = "serde")]
+      data: None,
+    }
   }
 }
 
 #[cfg(feature = "serde")]
 impl<'a> Replacements<'a> {
-  #[allow(missing_docs)]
+  /// Values of the object's fields, including the nested ones, in the order
+  /// of [Placeholders::from_json_keys](crate::Placeholders::from_json_keys).
   pub fn from_json_object_fields(object: &JsonValue) -> Self {
     debug_assert!(
       object.is_object(),
       "pass an object, as placeholders won't be replaced otherwise"
     );
-    match object.as_object() {
-      Some(obj) => Self { values: obj.values().map(Value::from).collect() },
-      None => Default::default(),
+    Self {
+      values: paths::fields(object).into_iter().map(|(_, value)| Value::from(value)).collect(),
+      data: Some(Arc::new(object.clone())),
     }
   }
 
@@ -57,6 +74,11 @@ impl<'a> Replacements<'a> {
   pub fn is_empty(&self) -> bool {
     self.values.is_empty()
   }
+
+  /// Resources referenced by the values.
+  pub(crate) fn relationships(&self) -> impl Iterator<Item = &Relationship> {
+    self.values.iter().flat_map(|value| &value.relationships)
+  }
 }
 
 impl<'a> Index<usize> for Replacements<'a> {
@@ -0,0 +1,224 @@
+use quick_xml::escape::escape;
+
+use super::Value;
+use crate::relationships::{hyperlink_tag, Relationship};
+
+/// A sequence of differently formatted text spans, see [Value::rich].
+///
+/// Each span becomes a separate run. Its properties are merged with the placeholder's ones,
+/// so a bold span inside a red sentence stays red.
+#[derive(Debug, Default, Clone)]
+pub struct RichText {
+  spans: Vec<Span>,
+}
+
+/// A piece of text sharing the same formatting.
+///
+/// ```rust
+/// # use docx_template::Span;
+/// Span::new("Ann").bold().color("#C00000").size(14.0);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Span {
+  text: String,
+  bold: bool,
+  italic: bool,
+  underline: bool,
+  strike: bool,
+  color: Option<String>,
+  size: Option<f32>,
+  font: Option<String>,
+  highlight: Option<String>,
+  link: Option<String>,
+}
+
+impl RichText {
+  /// Appends a span of text formatted like the placeholder.
+  pub fn text(mut self, text: impl Into<String>) -> Self {
+    self.spans.push(Span::new(text));
+    self
+  }
+
+  /// Appends a formatted span.
+  pub fn span(mut self, span: Span) -> Self {
+    self.spans.push(span);
+    self
+  }
+}
+
+impl Span {
+  #[allow(missing_docs)]
+  pub fn new(text: impl Into<String>) -> Self {
+    Self { text: text.into(), ..Default::default() }
+  }
+
+  #[allow(missing_docs)]
+  pub fn bold(mut self) -> Self {
+    self.bold = true;
+    self
+  }
+
+  #[allow(missing_docs)]
+  pub fn italic(mut self) -> Self {
+    self.italic = true;
+    self
+  }
+
+  /// Single line underline.
+  pub fn underline(mut self) -> Self {
+    self.underline = true;
+    self
+  }
+
+  #[allow(missing_docs)]
+  pub fn strike(mut self) -> Self {
+    self.strike = true;
+    self
+  }
+
+  /// Hex color of the text, like `FF0000` or `#FF0000`.
+  pub fn color(mut self, color: impl Into<String>) -> Self {
+    self.color = Some(color.into().trim_start_matches('#').to_owned());
+    self
+  }
+
+  /// Font size in points, rounded to halves.
+  pub fn size(mut self, points: f32) -> Self {
+    self.size = Some(points);
+    self
+  }
+
+  /// Font family, like `Arial`.
+  pub fn font(mut self, font: impl Into<String>) -> Self {
+    self.font = Some(font.into());
+    self
+  }
+
+  /// One of the predefined highlight colors: `yellow`, `green`, `cyan`, `lightGray`, etc.
+  pub fn highlight(mut self, color: impl Into<String>) -> Self {
+    self.highlight = Some(color.into());
+    self
+  }
+
+  /// Makes the span a clickable link to the `url`, styled as a hyperlink unless set otherwise.
+  ///
+  /// Links to `#bookmarks` are anchors, others are registered as relationships of the part
+  /// the value is put into.
+  pub fn link(mut self, url: impl Into<String>) -> Self {
+    self.link = Some(url.into());
+    self.underline = true;
+    self.color.get_or_insert_with(|| "0563C1".to_owned());
+    self
+  }
+
+  /// A run, or a hyperlink wrapping it in case of a link, whose relationship is collected.
+  pub(crate) fn to_linked_xml(&self, relationships: &mut Vec<Relationship>) -> String {
+    let Some(ref url) = self.link else { return self.to_xml() };
+    let (tag, relationship) = hyperlink_tag(url);
+    relationships.extend(relationship);
+    format!("{tag}{}</w:hyperlink>", self.to_xml())
+  }
+
+  /// A run of the span's text, links aside.
+  pub(crate) fn to_xml(&self) -> String {
+    let mut xml = String::from("<w:r>");
+    let properties = self.properties();
+    if !properties.is_empty() {
+      xml += &format!("<w:rPr>{properties}</w:rPr>");
+    }
+    let lines = self.text.lines().map(escape);
+    let text = crate::iter_tools::join(lines, r#"</w:t><w:br/><w:t xml:space="preserve">"#);
+    xml += &format!(r#"<w:t xml:space="preserve">{text}</w:t></w:r>"#);
+    xml
+  }
+
+  /// Children of `<w:rPr>`, in the order of the schema.
+  fn properties(&self) -> String {
+    let mut xml = String::new();
+    if let Some(ref font) = self.font {
+      let font = escape(font);
+      xml += &format!(
+        r#"<w:rFonts w:ascii="{font}" w:hAnsi="{font}" w:cs="{font}" w:eastAsia="{font}"/>"#
+      );
+    }
+    if self.bold {
+      xml += "<w:b/><w:bCs/>";
+    }
+    if self.italic {
+      xml += "<w:i/><w:iCs/>";
+    }
+    if self.strike {
+      xml += "<w:strike/>";
+    }
+    if let Some(ref color) = self.color {
+      xml += &format!(r#"<w:color w:val="{}"/>"#, escape(color));
+    }
+    if let Some(points) = self.size {
+      let half_points = (points * 2.0).round() as u32;
+      xml += &format!(r#"<w:sz w:val="{half_points}"/><w:szCs w:val="{half_points}"/>"#);
+    }
+    if let Some(ref highlight) = self.highlight {
+      xml += &format!(r#"<w:highlight w:val="{}"/>"#, escape(highlight));
+    }
+    if self.underline {
+      xml += r#"<w:u w:val="single"/>"#;
+    }
+    xml
+  }
+}
+
+impl From<RichText> for Value {
+  fn from(rich: RichText) -> Self {
+    // the placeholder's run is closed, spans are put in between, then the run is reopened
+    let mut relationships = Vec::new();
+    let mut xml = String::from("</w:t></w:r>");
+    xml.extend(rich.spans.iter().map(|span| span.to_linked_xml(&mut relationships)));
+    xml += r#"<w:r><w:t xml:space="preserve">"#;
+    Value::with_relationships(xml, relationships)
+  }
+}
+
+impl From<Span> for Value {
+  fn from(span: Span) -> Self {
+    Value::rich().span(span).into()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn orders_properties_by_the_schema() {
+    let span = Span::new("Ann").underline().highlight("yellow").size(11.0).color("#C00000");
+    let span = span.strike().italic().bold().font("Arial");
+    insta::assert_snapshot!(span.properties(), @r###"<w:rFonts w:ascii="Arial" w:hAnsi="Arial" w:cs="Arial" w:eastAsia="Arial"/><w:b/><w:bCs/><w:i/><w:iCs/><w:strike/><w:color w:val="C00000"/><w:sz w:val="22"/><w:szCs w:val="22"/><w:highlight w:val="yellow"/><w:u w:val="single"/>"###);
+  }
+
+  #[test]
+  fn rounds_sizes_to_halves() {
+    let half_points = |points| Span::new("Ann").size(points).properties();
+    assert_eq!(half_points(10.3), r#"<w:sz w:val="21"/><w:szCs w:val="21"/>"#);
+    assert_eq!(half_points(10.7), r#"<w:sz w:val="21"/><w:szCs w:val="21"/>"#);
+    assert_eq!(half_points(10.75), r#"<w:sz w:val="22"/><w:szCs w:val="22"/>"#);
+  }
+
+  #[test]
+  fn breaks_lines() {
+    insta::assert_snapshot!(Span::new("Ann\nBob").to_xml(), @r###"<w:r><w:t xml:space="preserve">Ann</w:t><w:br/><w:t xml:space="preserve">Bob</w:t></w:r>"###);
+  }
+
+  #[test]
+  fn escapes_properties() {
+    let span = Span::new("<Ann>").color("\"C00000").font("A&B").highlight("<yellow>");
+    insta::assert_snapshot!(span.to_xml(), @r###"<w:r><w:rPr><w:rFonts w:ascii="A&amp;B" w:hAnsi="A&amp;B" w:cs="A&amp;B" w:eastAsia="A&amp;B"/><w:color w:val="&quot;C00000"/><w:highlight w:val="&lt;yellow&gt;"/></w:rPr><w:t xml:space="preserve">&lt;Ann&gt;</w:t></w:r>"###);
+  }
+
+  #[test]
+  fn links_to_bookmarks_by_anchors() {
+    let mut relationships = Vec::new();
+    let xml = Span::new("Terms").link("#terms").to_linked_xml(&mut relationships);
+    insta::assert_snapshot!(xml, @r###"<w:hyperlink w:anchor="terms"><w:r><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr><w:t xml:space="preserve">Terms</w:t></w:r></w:hyperlink>"###);
+    assert!(relationships.is_empty());
+  }
+}
@@ -245,4 +245,260 @@ mod structural {
       "###,
     );
   }
+
+  #[test]
+  fn text_boxes() {
+    insta::assert_snapshot!(
+      run(
+        [("{hole}", "[]")],
+        indoc! {r#"
+          <w:p><w:r><w:t>{hole}</w:t></w:r><w:r><mc:AlternateContent>
+            <mc:Choice><w:drawing><wps:txbx><w:txbxContent><w:p><w:r><w:t>{ho</w:t></w:r><w:r><w:t>le}</w:t></w:r></w:p></w:txbxContent></wps:txbx></w:drawing></mc:Choice>
+            <mc:Fallback><w:pict><v:textbox><w:txbxContent><w:p><w:r><w:t>{hole}</w:t></w:r></w:p></w:txbxContent></v:textbox></w:pict></mc:Fallback>
+          </mc:AlternateContent></w:r><w:r><w:t>{hole}</w:t></w:r></w:p>
+        "#},
+      ),
+      @r###"
+        <w:p><w:r><w:t>[]</w:t></w:r><w:r><mc:AlternateContent>
+          <mc:Choice><w:drawing><wps:txbx><w:txbxContent><w:p><w:r><w:t></w:t></w:r><w:r><w:t>[]</w:t></w:r></w:p></w:txbxContent></wps:txbx></w:drawing></mc:Choice>
+          <mc:Fallback><w:pict><v:textbox><w:txbxContent><w:p><w:r><w:t>[]</w:t></w:r></w:p></w:txbxContent></v:textbox></w:pict></mc:Fallback>
+        </mc:AlternateContent></w:r><w:r><w:t>[]</w:t></w:r></w:p>
+      "###,
+    );
+  }
+
+  #[test]
+  fn reads_text_of_paragraphs_split_by_text_boxes() {
+    let input = br#"<w:p><w:r><w:t>{a</w:t></w:r><w:r><w:pict><w:txbxContent><w:p><w:r><w:t>b</w:t></w:r></w:p></w:txbxContent></w:pict></w:r><w:r><w:t>}</w:t></w:r></w:p>"#;
+    let mut texts = Vec::new();
+    read_paragraphs(input, |start, spans, _| {
+      let text = spans.iter().map(|it| String::from_utf8_lossy(&input[it.clone()])).collect();
+      texts.push((start, text));
+      Ok::<_, quick_xml::Error>(())
+    })
+    .unwrap();
+    assert_eq!(texts, [(0, "{a".to_owned()), (57, "b".to_owned()), (0, "}".to_owned())]);
+  }
+}
+
+#[cfg(feature = "serde")]
+mod json {
+  use super::*;
+  use serde_json::{json, Value as JsonValue};
+
+  fn run_json(placeholders: Placeholders, data: &JsonValue, input: &str) -> String {
+    let find_and_replace =
+      FindAndReplace { placeholders, replacements: Replacements::from_json_object_fields(data) };
+    let resolved = find_and_replace.with_paths_met(input.as_bytes(), &Filters::default()).unwrap();
+    let buf = resolved.unwrap_or(find_and_replace).transform_stream(input, Vec::new()).unwrap();
+    String::from_utf8(buf).unwrap()
+  }
+
+  #[test]
+  fn replaces_nested_paths() {
+    let data = json!({ "client": { "name": "Ann", "address": { "city": "Oslo" } }, "items": [{ "sku": "A1" }] });
+    let placeholders = Placeholders::from_json_keys_with_brackets("{", "}", &data);
+
+    insta::assert_snapshot!(
+      run_json(
+        placeholders,
+        &data,
+        r#"<w:p><w:r><w:t>{client.name}, {client.address.city}: {items[0].sku}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:r><w:t>Ann, Oslo: A1</w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn resolves_array_elements_met() {
+    let data = json!({ "items": ["pen", "ink", "nib"] });
+    let placeholders = Placeholders::from_json_keys_with_brackets("{", "}", &data);
+    assert_eq!(placeholders.len(), 1);
+
+    insta::assert_snapshot!(
+      run_json(
+        placeholders,
+        &data,
+        r#"<w:p><w:r><w:t>{items[2]}, {items[</w:t></w:r><w:r><w:t>0]}, {items[3]}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:r><w:t>nib, </w:t></w:r><w:r><w:t>pen, {items[3]}</w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn applies_filters() {
+    let data = json!({ "name": "ann", "price": 1234.5, "items": ["pen", "ink"] });
+    let placeholders = Placeholders::from_json_keys_with_brackets("{", "}", &data);
+
+    insta::assert_snapshot!(
+      run_json(
+        placeholders,
+        &data,
+        r#"<w:p><w:r><w:t>{name | upper}: {price | number:2," "} for {items | join:", "}, {name | nope}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:r><w:t>ANN: 1 234.50 for pen, ink, {name | nope}</w:t></w:r></w:p>"###,
+    );
+  }
+}
+
+mod styles {
+  use super::*;
+
+  fn run_xml(xml: &str, input: &str) -> String {
+    let buf = FindAndReplace {
+      placeholders: Placeholders::from_iter(["{hole}"]),
+      replacements: Replacements::from_slice(vec![Value::from_xml(xml)]),
+    }
+    .transform_stream(input, Vec::new())
+    .unwrap();
+    String::from_utf8(buf).unwrap()
+  }
+
+  #[test]
+  fn reopened_runs_inherit_run_properties() {
+    insta::assert_snapshot!(
+      run_xml(
+        "a</w:t></w:r><w:r><w:tab/></w:r><w:r><w:t>b",
+        r#"<w:p><w:r><w:rPr><w:b/></w:rPr><w:t>{hole}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:r><w:rPr><w:b/></w:rPr><w:t>a</w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:tab/></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>b</w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn reopened_paragraphs_inherit_paragraph_properties() {
+    insta::assert_snapshot!(
+      run_xml(
+        "</w:t></w:r></w:p><w:tbl/><w:p><w:r><w:t>",
+        r#"<w:p><w:pPr><w:jc w:val="center"/><w:sectPr/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t>{hole}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t></w:t></w:r></w:p><w:tbl/><w:p><w:pPr><w:jc w:val="center"/><w:sectPr/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t></w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn section_properties_move_to_the_last_paragraph() {
+    insta::assert_snapshot!(
+      run_xml(
+        r#"</w:t></w:r></w:p><w:p><w:r><w:t>a</w:t></w:r></w:p><w:p><w:pPr><w:jc w:val="right"/></w:pPr><w:r><w:t>"#,
+        r#"<w:p><w:pPr><w:sectPr><w:pgSz w:w="11906"/></w:sectPr></w:pPr><w:r><w:t>{hole}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:pPr></w:pPr><w:r><w:t></w:t></w:r></w:p><w:p><w:pPr></w:pPr><w:r><w:t>a</w:t></w:r></w:p><w:p><w:pPr><w:jc w:val="right"/><w:sectPr><w:pgSz w:w="11906"/></w:sectPr></w:pPr><w:r><w:t></w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn nested_paragraphs_keep_own_properties() {
+    insta::assert_snapshot!(
+      run_xml(
+        "</w:t></w:r></w:p><w:tbl><w:tr><w:tc><w:p><w:r><w:t>cell</w:t></w:r></w:p></w:tc></w:tr></w:tbl><w:p><w:r><w:t>",
+        r#"<w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t>{hole}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t></w:t></w:r></w:p><w:tbl><w:tr><w:tc><w:p><w:r><w:t>cell</w:t></w:r></w:p></w:tc></w:tr></w:tbl><w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t></w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn paragraphs_of_own_kind_skip_style_and_numbering() {
+    insta::assert_snapshot!(
+      run_xml(
+        r#"</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Title</w:t></w:r></w:p><w:p><w:r><w:t>"#,
+        r#"<w:p><w:pPr><w:pStyle w:val="ListParagraph"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr><w:jc w:val="center"/></w:pPr><w:r><w:t>{hole}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:pPr><w:pStyle w:val="ListParagraph"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr><w:jc w:val="center"/></w:pPr><w:r><w:t></w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="Heading1"/><w:jc w:val="center"/></w:pPr><w:r><w:t>Title</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="ListParagraph"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr><w:jc w:val="center"/></w:pPr><w:r><w:t></w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn explicit_properties_take_precedence() {
+    insta::assert_snapshot!(
+      run_xml(
+        r#"</w:t></w:r><w:r><w:rPr><w:color w:val="FF0000"/><w:i/></w:rPr><w:t>b"#,
+        r#"<w:p><w:r><w:rPr><w:b/><w:color w:val="111111"/></w:rPr><w:t>{hole}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:r><w:rPr><w:b/><w:color w:val="111111"/></w:rPr><w:t></w:t></w:r><w:r><w:rPr><w:b/><w:i/><w:color w:val="FF0000"/></w:rPr><w:t>b</w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn hyperlinks_inherit_run_properties() {
+    let buf = FindAndReplace {
+      placeholders: Placeholders::from_iter(["{hole}"]),
+      replacements: Replacements::from_slice(vec![Value::hyperlink("appendix", "#appendix")]),
+    }
+    .transform_stream(
+      r#"<w:p><w:r><w:rPr><w:b/></w:rPr><w:t>See {hole}</w:t></w:r></w:p>"#,
+      Vec::new(),
+    )
+    .unwrap();
+
+    insta::assert_snapshot!(
+      String::from_utf8(buf).unwrap(),
+      @r###"<w:p><w:r><w:rPr><w:b/></w:rPr><w:t>See </w:t></w:r><w:hyperlink w:anchor="appendix"><w:r><w:rPr><w:b/><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr><w:t xml:space="preserve">appendix</w:t></w:r></w:hyperlink><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve"></w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn rich_text_spans_merge_properties() {
+    let buf = FindAndReplace {
+      placeholders: Placeholders::from_iter(["{hole}"]),
+      replacements: Replacements::from_slice(vec![Value::rich()
+        .text("Dear ")
+        .span(Span::new("Ann").bold().size(14.0))
+        .into()]),
+    }
+    .transform_stream(
+      r#"<w:p><w:r><w:rPr><w:color w:val="111111"/></w:rPr><w:t>{hole}, hi</w:t></w:r></w:p>"#,
+      Vec::new(),
+    )
+    .unwrap();
+
+    insta::assert_snapshot!(
+      String::from_utf8(buf).unwrap(),
+      @r###"<w:p><w:r><w:rPr><w:color w:val="111111"/></w:rPr><w:t></w:t></w:r><w:r><w:rPr><w:color w:val="111111"/></w:rPr><w:t xml:space="preserve">Dear </w:t></w:r><w:r><w:rPr><w:b/><w:bCs/><w:color w:val="111111"/><w:sz w:val="28"/><w:szCs w:val="28"/></w:rPr><w:t xml:space="preserve">Ann</w:t></w:r><w:r><w:rPr><w:color w:val="111111"/></w:rPr><w:t xml:space="preserve">, hi</w:t></w:r></w:p>"###,
+    );
+  }
+}
+
+mod relationships {
+  use super::*;
+
+  #[test]
+  fn replaces_placeholders_in_targets() {
+    let buf = FindAndReplace {
+      placeholders: Placeholders::from_iter(["{id}", "{q}"]),
+      replacements: Replacements::from_iter(["42", "a&b \"c\""]),
+    }
+    .transform_relationships(
+      r#"<Relationships><Relationship Id="rId1" Type="t" Target="https://portal/orders/{id}?q={q}" TargetMode="External"/><Relationship Id="rId2" Type="t" Target="styles.xml"/></Relationships>"#,
+    )
+    .unwrap();
+
+    insta::assert_snapshot!(
+      String::from_utf8(buf).unwrap(),
+      @r###"<Relationships><Relationship Id="rId1" Type="t" Target="https://portal/orders/42?q=a&amp;b &quot;c&quot;" TargetMode="External"/><Relationship Id="rId2" Type="t" Target="styles.xml"/></Relationships>"###,
+    );
+  }
+
+  #[test]
+  fn rich_text_links_are_relationships() {
+    let value = Value::from(
+      Value::rich()
+        .span(Span::new("site").link(r#"https://a.b/?q="x""#))
+        .span(Span::new("top").link("#top")),
+    );
+    let marker = value.relationships[0].marker();
+
+    assert_eq!(value.relationships.len(), 1);
+    insta::assert_snapshot!(
+      value.xml.replace(&marker, "{rel}"),
+      @r###"</w:t></w:r><w:hyperlink r:id="{rel}"><w:r><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr><w:t xml:space="preserve">site</w:t></w:r></w:hyperlink><w:hyperlink w:anchor="top"><w:r><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr><w:t xml:space="preserve">top</w:t></w:r></w:hyperlink><w:r><w:t xml:space="preserve">"###,
+    );
+  }
+
+  #[test]
+  fn rejects_images_of_unknown_formats() {
+    assert!(Value::image(b"<svg/>".to_vec(), 120, 80).is_none());
+    assert!(Value::image(b"GIF89a".to_vec(), 120, 80).is_some());
+  }
 }
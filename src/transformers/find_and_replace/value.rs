@@ -1,3 +1,12 @@
+use super::html;
+use super::markdown;
+use super::paragraph_styles::ParagraphStyles;
+use super::rich_text::{RichText, Span};
+use std::sync::Arc;
+
+use crate::image::{self, ImageFormat, EMU_PER_PIXEL};
+use crate::relationships::{hyperlink_tag, Relationship, Target};
+use crate::transformers::tree::{self, Node};
 #[cfg(feature = "docx-rs")]
 use crate::DocxRsMarkupNode;
 #[cfg(feature = "docx-rust")]
@@ -5,27 +14,192 @@ use crate::DocxRustMarkupNode;
 
 /// A text value or a piece of XML ready to replace a placeholder.
 #[derive(Debug, Default, Clone)]
-pub struct Value(pub(crate) String);
+pub struct Value {
+  pub(crate) xml: String,
+  /// Hyperlinks and images referenced by the markup, see [Relationship::marker].
+  pub(crate) relationships: Vec<Relationship>,
+}
 
 impl Value {
-  #[allow(missing_docs)]
+  /// Replaces a placeholder with a piece of XML, which is put inside the placeholder's `<w:t>`.
+  ///
+  /// Runs `<w:r>` opened by the piece inherit the formatting of the placeholder's run,
+  /// properties set in their own `<w:rPr>` take precedence. Bare `<w:p>` paragraphs
+  /// inherit the placeholder paragraph's `<w:pPr>`.
   pub fn from_xml(xml: impl Into<String>) -> Self {
-    Self(xml.into())
+    Self { xml: xml.into(), relationships: Vec::new() }
+  }
+
+  /// Markup referencing resources, which are linked to the part the value is put into.
+  pub(crate) fn with_relationships(xml: String, relationships: Vec<Relationship>) -> Self {
+    Self { xml, relationships }
+  }
+
+  /// Text of the value without markup, to be put into attributes.
+  pub(crate) fn text(&self) -> String {
+    let mut reader = quick_xml::Reader::from_str(&self.xml);
+    reader.config_mut().check_end_names = false;
+    reader.config_mut().allow_unmatched_ends = true;
+    let mut text = String::new();
+    loop {
+      match reader.read_event() {
+        Ok(quick_xml::events::Event::Text(chunk)) => {
+          text += &chunk.unescape().unwrap_or_default();
+        }
+        Ok(quick_xml::events::Event::Eof) | Err(_) => break,
+        _ => {}
+      }
+    }
+    text
+  }
+
+  /// The value as runs put into a paragraph. Paragraphs of block values, tables' ones included,
+  /// are joined by line breaks, blank ones are dropped.
+  pub(crate) fn to_runs(&self) -> String {
+    let run = format!(r#"<w:r><w:t xml:space="preserve">{}</w:t></w:r>"#, self.xml);
+    let xml = format!("<w:p>{run}</w:p>");
+    let Ok(nodes) = tree::read(xml.as_bytes()) else { return run };
+    let input = xml.as_bytes();
+    let is_paragraph =
+      |node: &Node| node.name == b"w:p" && !ancestors(&nodes, node).any(|it| it.name == b"w:p");
+    if nodes.iter().filter(|it| is_paragraph(it)).count() == 1 {
+      return run;
+    }
+
+    let paragraphs = nodes.iter().filter(|it| is_paragraph(it) && !is_blank(&nodes, it));
+    let paragraphs = paragraphs.map(|paragraph| {
+      let children = paragraph.children.iter().map(|&it| &nodes[it]);
+      let children = children.filter(|it| it.name != b"w:pPr");
+      children.map(|it| String::from_utf8_lossy(&input[it.range.clone()])).collect::<String>()
+    });
+    crate::iter_tools::join(paragraphs, "<w:r><w:br/></w:r>")
   }
 
   /// Replaces a placeholder with the text. Each `\n` or `\r\n` symbol forms a new line in the document.
   pub fn from_text(text: &str) -> Self {
     let lines = text.lines().map(quick_xml::escape::escape);
-    Self(crate::iter_tools::join(lines, "</w:t><w:br/><w:t>"))
+    Self::from_xml(crate::iter_tools::join(lines, "</w:t><w:br/><w:t>"))
+  }
+
+  /// Replaces a placeholder with differently formatted spans of text.
+  ///
+  /// ```rust
+  /// # use docx_template::{Replacements, Span, Value};
+  /// Replacements::from_slice(vec![Value::rich()
+  ///   .text("Dear ")
+  ///   .span(Span::new("Ann").bold().color("C00000"))
+  ///   .text(",")
+  ///   .into()]);
+  /// ```
+  pub fn rich() -> RichText {
+    RichText::default()
+  }
+
+  /// Replaces a placeholder with a clickable link, formatted like the placeholder
+  /// but colored and underlined.
+  ///
+  /// The `url` is registered as a relationship of the part the value is put into.
+  /// A `#name` url points to the bookmark `name` inside the document.
+  ///
+  /// ```rust
+  /// # use docx_template::{Replacements, Value};
+  /// Replacements::from_slice(vec![
+  ///   Value::hyperlink("Track the order", "https://example.com/track?id=42"),
+  ///   Value::hyperlink("see the appendix", "#appendix"),
+  /// ]);
+  /// ```
+  pub fn hyperlink(text: &str, url: &str) -> Self {
+    let (tag, relationship) = hyperlink_tag(url);
+    let run = Span::new(text).underline().color("0563C1").to_xml();
+    let xml = format!(r#"</w:t></w:r>{tag}{run}</w:hyperlink><w:r><w:t xml:space="preserve">"#);
+    Self::with_relationships(xml, relationship.into_iter().collect())
+  }
+
+  /// Replaces a placeholder with paragraphs converted from Markdown.
+  ///
+  /// Supports `*emphasis*`, `**strong**`, `` `code` ``, `[links](https://…)`, `# headings`,
+  /// `- bulleted` and `1. numbered` lists. Headings and lists are given the built-in styles,
+  /// see [Value::from_markdown_with_styles] to use the styles of a particular document.
+  ///
+  /// ```rust
+  /// # use docx_template::{Replacements, Value};
+  /// Replacements::from_slice(vec![Value::from_markdown("## Notes\n\n- **Paid** in full")]);
+  /// ```
+  pub fn from_markdown(markdown: &str) -> Self {
+    markdown::to_value(markdown, &ParagraphStyles::default())
+  }
+
+  /// Replaces a placeholder with paragraphs converted from Markdown, see [ParagraphStyles].
+  ///
+  /// ```rust
+  /// # use docx_template::{DocxFile, Value};
+  /// let styles = DocxFile::from_path("examples/template/input.docx")?.paragraph_styles()?;
+  /// Value::from_markdown_with_styles("# Title\n\nText", &styles);
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn from_markdown_with_styles(markdown: &str, styles: &ParagraphStyles) -> Self {
+    markdown::to_value(markdown, styles)
+  }
+
+  /// Replaces a placeholder with runs, paragraphs and tables converted from HTML.
+  ///
+  /// Supports `<p>`, `<h1>`…`<h6>`, `<b>`, `<i>`, `<u>`, `<s>`, `<code>`, `<br>`, `<a href>`,
+  /// `<ul>`, `<ol>`, `<li>`, `<table>`, and colors set by `style` attributes. Unknown tags are
+  /// ignored, but their content is kept. Links to other sites are registered as relationships
+  /// of the part the value is put into, `#anchor` links point to bookmarks.
+  ///
+  /// ```rust
+  /// # use docx_template::{Replacements, Value};
+  /// Replacements::from_slice(vec![Value::from_html(
+  ///   r#"<p>See <a href="https://example.com"><b>the site</b></a></p><ul><li>one<li>two</ul>"#,
+  /// )]);
+  /// ```
+  pub fn from_html(html: &str) -> Self {
+    html::to_value(html, &ParagraphStyles::default())
+  }
+
+  /// Replaces a placeholder with runs, paragraphs and tables converted from HTML, see [ParagraphStyles].
+  pub fn from_html_with_styles(html: &str, styles: &ParagraphStyles) -> Self {
+    html::to_value(html, styles)
+  }
+
+  /// Replaces a placeholder with an inline picture of `width` × `height` pixels.
+  ///
+  /// The image is added to `word/media/` and registered as a relationship of the part
+  /// the value is put into. PNG, JPEG, GIF, BMP and TIFF are recognized by their signatures,
+  /// `None` is returned for other formats, as Word can't display them.
+  ///
+  /// ```rust
+  /// # use docx_template::{Replacements, Value};
+  /// let logo = std::fs::read("examples/image-replacement/cat.jpg")?;
+  /// Replacements::from_slice(vec![Value::image(logo, 120, 80).unwrap()]);
+  /// # Ok::<(), std::io::Error>(())
+  /// ```
+  pub fn image(bytes: impl Into<Arc<[u8]>>, width: u32, height: u32) -> Option<Self> {
+    let bytes = bytes.into();
+    let format = ImageFormat::detect(&bytes)?;
+
+    let relationship = Relationship::new(Target::Media {
+      extension: format.extension().to_owned(),
+      content_type: format.content_type().to_owned(),
+      bytes,
+    });
+    let (cx, cy) = (u64::from(width) * EMU_PER_PIXEL, u64::from(height) * EMU_PER_PIXEL);
+    let drawing = image::drawing(&relationship.marker(), image::next_drawing_id(), cx, cy);
+
+    // the picture is put into the placeholder's run, so it follows the run's formatting
+    let xml = format!(r#"</w:t>{drawing}<w:t xml:space="preserve">"#);
+    Some(Self::with_relationships(xml, vec![relationship]))
   }
 
   #[cfg(feature = "docx-rust")]
   /// Replaces a placeholder with the markup node. Tables and images can be inserted this way.
+  /// The text following the node keeps the placeholder's formatting.
   ///
   /// Be careful with reference ids, as they must not intersect with those already in the doc.
   ///
   /// Some markup nodes require storing _index_ information in a separate file of a `.docx` archive.
-  /// Like images or comments. So it's easier to replace an already existing image, than to insert one.
+  /// Like images or comments. Use [Value::image] to insert an image.
   ///
   /// If you see how this can be simplified, feel free to [open an issue](https://github.com/xamgore/docx-template/issues/new).
   ///
@@ -52,11 +226,12 @@ impl Value {
         format!("</w:t>{node}<w:t>")
       }
     };
-    Self(xml)
+    Self::from_xml(xml)
   }
 
   #[cfg(feature = "docx-rs")]
   /// Replaces a placeholder with the markup node. Tables and images can be inserted this way.
+  /// The text following the node keeps the placeholder's formatting.
   ///
   /// Be careful with reference ids, as they must not intersect with those already in the doc.
   /// `docx_rs` has several internal counters that are incremented each time a paragraph
@@ -64,7 +239,7 @@ impl Value {
   /// setting all the ids fields yourself.
   ///
   /// Some markup nodes require storing _index_ information in a separate file of a `.docx` archive.
-  /// Like images or comments. So it's easier to replace an already existing image, than to insert one.
+  /// Like images or comments. Use [Value::image] to insert an image.
   ///
   /// If you see how this can be simplified, feel free to [open an issue](https://github.com/xamgore/docx-template/issues/new).
   ///
@@ -91,10 +266,30 @@ impl Value {
         format!("</w:t>{node}<w:t>")
       }
     };
-    Self(xml)
+    Self::from_xml(xml)
   }
 }
 
+/// Ancestors of the node, from its parent up.
+fn ancestors<'n>(nodes: &'n [Node], node: &Node) -> impl Iterator<Item = &'n Node> {
+  std::iter::successors(node.parent.map(|it| &nodes[it]), |it| it.parent.map(|it| &nodes[it]))
+}
+
+/// Returns true if the paragraph holds neither text, nor other elements besides runs and properties.
+fn is_blank(nodes: &[Node], paragraph: &Node) -> bool {
+  let inner = nodes.iter().filter(|it| {
+    paragraph.content.start <= it.range.start && it.range.end <= paragraph.content.end
+  });
+  let is_properties = |node: &Node| node.name.ends_with(b"Pr");
+  inner.filter(|it| !is_properties(it) && !ancestors(nodes, it).any(is_properties)).all(|it| {
+    match it.name.as_slice() {
+      b"w:r" => true,
+      b"w:t" => it.content.is_empty(),
+      _ => false,
+    }
+  })
+}
+
 impl From<&str> for Value {
   fn from(value: &str) -> Self {
     Self::from_text(value)
@@ -127,10 +322,11 @@ impl From<&serde_json::Value> for Value {
   fn from(value: &serde_json::Value) -> Self {
     match value {
       serde_json::Value::Null => Value::from_xml(String::new()),
+      serde_json::Value::Bool(v) => Value::from_text(&v.to_string()),
       serde_json::Value::String(v) => Value::from_text(v.as_str()),
       serde_json::Value::Number(v) => Value::from_text(&v.to_string()),
-      _ if cfg!(debug_assertions) => unimplemented!(),
-      _ => Value::from_xml(String::new()),
+      // collections are rendered by sections, see `DocxFile::into_template`
+      serde_json::Value::Array(_) | serde_json::Value::Object(_) => Value::from_xml(String::new()),
     }
   }
 }
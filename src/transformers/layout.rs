@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use quick_xml::events::Event;
+
+/// A bird's-eye view of a WordprocessingML stream: paragraphs, their texts and table rows.
+///
+/// All the ranges are absolute byte offsets over the input stream.
+///
+/// Unlike [FindAndReplace](crate::transformers::find_and_replace::FindAndReplace), which rewrites
+/// the stream paragraph by paragraph, the layout is read upfront: sections need to relate
+/// paragraphs to their parents and table rows, which may end far after a paragraph is closed.
+#[derive(Debug, Default, Clone)]
+pub struct Layout {
+  /// Paragraphs in the order of their closing tags.
+  pub paragraphs: Vec<Paragraph>,
+  /// Table rows `<w:tr>` indexed by their start offset.
+  pub rows: HashMap<usize, Row>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Paragraph {
+  /// Bytes of `<w:p>…</w:p>`, tags included.
+  pub range: Range<usize>,
+  /// Inner contents of `<w:t>` tags residing in the paragraph's runs.
+  pub spans: Vec<Range<usize>>,
+  /// Start offset of the parent element, paragraphs having the same parent are siblings.
+  pub parent: usize,
+  /// Start offset of the innermost table row the paragraph resides in.
+  pub row: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Row {
+  /// Bytes of `<w:tr>…</w:tr>`, tags included.
+  pub range: Range<usize>,
+  /// Start offset of the parent table, rows having the same parent are siblings.
+  pub parent: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tag {
+  Paragraph,
+  Run,
+  Row,
+  Other,
+}
+
+impl From<&[u8]> for Tag {
+  fn from(name: &[u8]) -> Self {
+    match name {
+      b"w:p" => Self::Paragraph,
+      b"w:r" => Self::Run,
+      b"w:tr" => Self::Row,
+      _ => Self::Other,
+    }
+  }
+}
+
+impl Layout {
+  /// Reads the stream once, accumulating paragraphs and table rows met.
+  ///
+  /// The input may be a fragment of a document, like a sequence of table rows.
+  pub fn read(input: &[u8]) -> Result<Self, quick_xml::Error> {
+    let mut reader = quick_xml::Reader::from_reader(input);
+    reader.config_mut().check_end_names = true;
+
+    let mut layout = Layout::default();
+    let mut stack = Vec::<(Tag, usize)>::new();
+    let mut paragraphs = Vec::<Paragraph>::new();
+
+    loop {
+      let start = reader.buffer_position() as usize;
+      match reader.read_event()? {
+        Event::Start(tag) => {
+          let kind = Tag::from(tag.name().as_ref());
+
+          if tag.name().as_ref() == b"w:t" && Self::is_in_run(&stack) {
+            let span = reader.read_to_end(tag.name())?;
+            if let Some(paragraph) = paragraphs.last_mut() {
+              paragraph.spans.push(span.start as usize..span.end as usize);
+            }
+            continue;
+          }
+
+          if kind == Tag::Paragraph {
+            paragraphs.push(Paragraph {
+              range: start..start,
+              spans: Vec::new(),
+              parent: Self::parent(&stack),
+              row: stack.iter().rev().find(|(tag, _)| *tag == Tag::Row).map(|&(_, start)| start),
+            });
+          }
+
+          stack.push((kind, start));
+        }
+        Event::End(_) => {
+          let Some((kind, start)) = stack.pop() else { continue };
+          let end = reader.buffer_position() as usize;
+
+          match kind {
+            Tag::Paragraph => {
+              if let Some(mut paragraph) = paragraphs.pop() {
+                paragraph.range = start..end;
+                layout.paragraphs.push(paragraph);
+              }
+            }
+            Tag::Row => {
+              layout.rows.insert(start, Row { range: start..end, parent: Self::parent(&stack) });
+            }
+            Tag::Run | Tag::Other => {}
+          }
+        }
+        Event::Eof => break,
+        _ => {}
+      }
+    }
+
+    Ok(layout)
+  }
+
+  fn parent(stack: &[(Tag, usize)]) -> usize {
+    stack.last().map(|&(_, start)| start).unwrap_or_default()
+  }
+
+  /// `<w:t>` is taken into account only if it's a part of a run, which is a part of a paragraph.
+  fn is_in_run(stack: &[(Tag, usize)]) -> bool {
+    let run = stack.iter().rposition(|(tag, _)| *tag == Tag::Run);
+    let paragraph = stack.iter().rposition(|(tag, _)| *tag == Tag::Paragraph);
+    matches!((paragraph, run), (Some(p), Some(r)) if p < r)
+  }
+}
+
+impl Paragraph {
+  /// Concatenated text of all the spans, as it's seen by a reader.
+  pub fn text(&self, input: &[u8]) -> Vec<u8> {
+    self.spans.iter().flat_map(|span| &input[span.clone()]).copied().collect()
+  }
+
+  /// Converts a range over the [text](Paragraph::text) into absolute ranges over the input.
+  ///
+  /// A single range of text may reside in several spans, that's why a list is returned.
+  pub fn locate(&self, text: Range<usize>) -> Vec<Range<usize>> {
+    let mut offset = 0;
+    let mut pieces = Vec::new();
+
+    for span in &self.spans {
+      let (start, end) = (text.start.max(offset), text.end.min(offset + span.len()));
+      if start < end {
+        pieces.push((span.start + start - offset)..(span.start + end - offset));
+      }
+      offset += span.len();
+    }
+
+    pieces
+  }
+}
+
+/// Finds `{…}` tokens in the text, returns their ranges, brackets included.
+pub fn tokens(text: &[u8], open: &[u8], close: &[u8]) -> Vec<Range<usize>> {
+  let mut tokens = Vec::new();
+  let mut from = 0;
+
+  while let Some(start) = find(text, open, from) {
+    let Some(end) = find(text, close, start + open.len()) else { break };
+
+    // in `{a {b}` the token starts from the last opening bracket
+    match find(&text[..end], open, start + open.len()) {
+      Some(inner) => from = inner,
+      None => {
+        // empty `{}` tokens are not a thing
+        if start + open.len() < end {
+          tokens.push(start..(end + close.len()));
+        }
+        from = end + close.len();
+      }
+    }
+  }
+
+  tokens
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+  // brackets may be empty, there is nothing to look for then
+  if needle.is_empty() {
+    return None;
+  }
+  haystack
+    .get(from..)?
+    .windows(needle.len())
+    .position(|window| window == needle)
+    .map(|pos| from + pos)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn collects_paragraphs_and_rows() {
+    let xml =
+      br#"<w:tbl><w:tr><w:tc><w:p><w:r><w:t>a</w:t><w:t>b</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"#;
+    let layout = Layout::read(xml).unwrap();
+
+    let paragraph = &layout.paragraphs[0];
+    assert_eq!(paragraph.text(xml), b"ab");
+    assert_eq!(&xml[paragraph.range.clone()], br#"<w:p><w:r><w:t>a</w:t><w:t>b</w:t></w:r></w:p>"#);
+
+    let row = &layout.rows[&paragraph.row.unwrap()];
+    assert_eq!(row.parent, 0);
+    assert!(
+      xml[row.range.clone()].starts_with(b"<w:tr>") && xml[row.range.clone()].ends_with(b"</w:tr>")
+    );
+  }
+
+  #[test]
+  fn locates_text_split_between_spans() {
+    let xml = br#"<w:p><w:r><w:t>ab</w:t></w:r><w:r><w:t>cd</w:t></w:r></w:p>"#;
+    let layout = Layout::read(xml).unwrap();
+
+    let pieces = layout.paragraphs[0].locate(1..3);
+    assert_eq!(pieces.iter().map(|r| &xml[r.clone()]).collect::<Vec<_>>(), [b"b", b"c"]);
+  }
+
+  #[test]
+  fn finds_tokens() {
+    let text = b"{a {b} {} {c d}{e";
+    let found = tokens(text, b"{", b"}").into_iter().map(|r| &text[r]).collect::<Vec<_>>();
+    assert_eq!(found, [&b"{b}"[..], b"{c d}"]);
+  }
+
+  #[test]
+  fn finds_no_tokens_without_brackets() {
+    assert!(tokens(b"{a}", b"", b"").is_empty());
+    assert!(tokens(b"{a}", b"{", b"").is_empty());
+  }
+}
@@ -2,15 +2,27 @@ use crate::CantRenderError;
 use std::io;
 use thiserror::Error;
 
+pub mod bookmarks;
+pub mod checkboxes;
+pub mod content_controls;
+pub mod drawings;
 pub mod erase_commented;
+pub mod fields;
 pub mod find_and_replace;
+pub mod layout;
+#[cfg(feature = "serde")]
+pub mod sections;
+mod tree;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum TransformerError {
   #[error(transparent)]
   WriteIoErr(#[from] io::Error),
   #[error(transparent)]
   ReadXmlErr(#[from] quick_xml::Error),
+  #[error("section {0:?} is not closed properly")]
+  UnbalancedSectionErr(String),
 }
 
 impl From<TransformerError> for CantRenderError {
@@ -20,6 +32,7 @@ impl From<TransformerError> for CantRenderError {
       TransformerError::ReadXmlErr(err) => Self::from(err),
       // as data is written directly to a zip archive, it's a ZipError
       TransformerError::WriteIoErr(err) => Self::ZipErr(err.into()),
+      TransformerError::UnbalancedSectionErr(name) => Self::UnbalancedSectionErr(name),
     }
   }
 }
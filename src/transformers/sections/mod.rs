@@ -0,0 +1,511 @@
+use std::collections::HashSet;
+use std::io;
+use std::ops::Range;
+
+use serde_json::Value as JsonValue;
+
+use crate::relationships::relationship_targets;
+use crate::transformers::checkboxes::Checkboxes;
+use crate::transformers::find_and_replace::paths;
+use crate::transformers::find_and_replace::{FindAndReplace, Placeholders, Replacements};
+use crate::transformers::layout::{tokens, Layout};
+use crate::transformers::tree::{apply_edits, Edit};
+use crate::transformers::TransformerError;
+use crate::Filters;
+use crate::Value;
+
+#[cfg(test)]
+mod tests;
+
+/// Fills in `{placeholders}`, repeats or hides parts of a document depending on the json data.
+///
+/// Placeholders are paths to the data fields, optionally followed by [filters](Filters),
+/// like `{client.name | upper}`.
+///
+/// A section is opened by the `{#items}` marker and closed by the `{/items}` one.
+/// When markers reside in sibling paragraphs, everything between them is cloned
+/// per element of the `items` array, while the marker paragraphs are removed.
+/// If a marker paragraph has some other text, only the marker is erased from it.
+///
+/// ```xml
+/// <w:p><w:r><w:t>{#items}</w:t></w:r></w:p>
+/// <w:p><w:r><w:t>{name}</w:t></w:r></w:p>
+/// <w:p><w:r><w:t>{/items}</w:t></w:r></w:p>
+/// ```
+///
+/// Otherwise, when both markers reside in rows of the same table, the rows from the first marker
+/// to the last one are cloned, markers are erased. Markers placed in sibling paragraphs
+/// of a table cell along with other text repeat the row as well.
+///
+/// ```xml
+/// <w:tr><w:tc><w:p><w:r><w:t>{#items}{name}</w:t></w:r></w:p></w:tc>
+///       <w:tc><w:p><w:r><w:t>{price}{/items}</w:t></w:r></w:p></w:tc></w:tr>
+/// ```
+///
+/// Markers residing in the same paragraph outside of a table clone the runs between them.
+/// Markers which can't enclose a section, like the ones residing in rows of different tables,
+/// are left in the document as is.
+///
+/// Sections can be nested. Placeholders of a clone are looked up among the element's fields first,
+/// then among the fields of the enclosing objects. `{.}` stands for the element itself.
+/// Both section names and placeholders may be paths, like `{#order.items}` or `{address.city}`.
+/// A missing or `null` value produces no clones, a non-array value produces a single one.
+///
+/// Conditional sections `{?flag}…{/flag}` keep their content only if the `flag` value is truthy,
+/// inverted ones `{^flag}…{/flag}` only if it's falsy. `null`, `false`, `0`, `""`, `[]` and `{}`
+/// are falsy values. Unlike the repeated sections, conditional ones can hide runs
+/// of a single paragraph even inside a table.
+#[derive(Debug, Clone)]
+pub struct Sections {
+  pub open_bracket: String,
+  pub close_bracket: String,
+  pub data: JsonValue,
+  pub filters: Filters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkerKind {
+  /// `{#name}`
+  Loop,
+  /// `{?name}`
+  Condition,
+  /// `{^name}`
+  InvertedCondition,
+  /// `{/name}`
+  Close,
+}
+
+#[derive(Debug, Clone)]
+struct Marker {
+  kind: MarkerKind,
+  name: String,
+  /// Index of the paragraph in [Layout::paragraphs].
+  paragraph: usize,
+  /// Range over the paragraph's [text](crate::transformers::layout::Paragraph::text).
+  text: Range<usize>,
+  /// Absolute ranges of the marker's text, which can be split between several `<w:t>` spans.
+  pieces: Vec<Range<usize>>,
+  /// The paragraph holds nothing but the marker.
+  is_bare: bool,
+}
+
+/// Data values looked up while rendering, see [Sections::unused_keys].
+#[derive(Debug, Default)]
+pub struct Usage {
+  /// Paths of the values put into the document, their fields are considered used as well.
+  values: HashSet<String>,
+  /// Paths of the values sections are opened for.
+  sections: HashSet<String>,
+}
+
+/// A data value along with its path from the root, like `items[0]`, sections are rendered in.
+#[derive(Debug, Clone)]
+struct Frame<'d> {
+  path: String,
+  value: &'d JsonValue,
+}
+
+/// Bytes of the document a section occupies.
+struct Unit {
+  /// Bytes to be removed from the output.
+  replaced: Range<usize>,
+  /// Bytes to be cloned per each element.
+  repeated: Range<usize>,
+  /// The repeated bytes start and end inside `<w:t>` tags of the same paragraph.
+  is_inline: bool,
+}
+
+impl Sections {
+  /// Renders the stream, keeping track of the data values looked up.
+  pub fn transform_stream<In: AsRef<[u8]>, Out: io::Write>(
+    &self,
+    input: In,
+    mut output: Out,
+    usage: &mut Usage,
+  ) -> Result<Out, TransformerError> {
+    let rendered = self.render(input.as_ref(), &[Frame::root(&self.data)], usage)?;
+    output.write_all(&rendered)?;
+    Ok(output)
+  }
+
+  /// Value of the data field at the `path`, like `client.name`, for fields outside of placeholders.
+  pub fn value(&self, path: &str, usage: &mut Usage) -> Option<Value> {
+    lookup(&[Frame::root(&self.data)], path, &mut usage.values).map(|it| Value::from(it.value))
+  }
+
+  /// Returns true if the data holds booleans, which may tick checkboxes.
+  fn has_flags(&self) -> bool {
+    fn any_boolean(value: &JsonValue) -> bool {
+      match value {
+        JsonValue::Bool(_) => true,
+        JsonValue::Array(items) => items.iter().any(any_boolean),
+        JsonValue::Object(fields) => fields.values().any(any_boolean),
+        _ => false,
+      }
+    }
+    any_boolean(&self.data)
+  }
+
+  /// Paths of the data fields which were never looked up, neither directly, nor via their
+  /// parents or children. Only the outermost unused fields are listed.
+  pub fn unused_keys(&self, usage: &Usage) -> Vec<String> {
+    let fields = paths::flatten(&self.data);
+    // looked up values, along with the ones containing them
+    let mut reached = HashSet::new();
+    for path in usage.values.iter().chain(&usage.sections) {
+      reached.insert(path.as_str());
+      reached.extend(parents(path));
+    }
+
+    let (mut unused, mut reported) = (Vec::new(), HashSet::new());
+    for (path, _) in &fields {
+      let is_touched =
+        reached.contains(path.as_str()) || parents(path).any(|it| usage.values.contains(it));
+      if !is_touched && !parents(path).any(|it| reported.contains(it)) {
+        reported.insert(path.as_str());
+        unused.push(path.clone());
+      }
+    }
+    unused
+  }
+
+  /// Expands top-level sections of the `input`, nested ones are expanded along with their clones.
+  fn expand(
+    &self,
+    input: &[u8],
+    scope: &[Frame],
+    usage: &mut Usage,
+  ) -> Result<Vec<u8>, TransformerError> {
+    let layout = Layout::read(input)?;
+    let markers = self.markers(input, &layout);
+    let ticks = self.tick(input, scope, usage)?;
+
+    // the list of byte ranges to be substituted in the input
+    let mut edits = Vec::<(Range<usize>, Vec<u8>)>::new();
+    let mut units = Vec::<Range<usize>>::new();
+    let mut edited = 0;
+    let mut opened = Vec::<&Marker>::new();
+
+    for close in &markers {
+      if close.kind != MarkerKind::Close {
+        opened.push(close);
+        continue;
+      }
+
+      let open = match opened.pop() {
+        Some(open) if open.name == close.name => open,
+        _ => return Err(TransformerError::UnbalancedSectionErr(close.name.clone())),
+      };
+
+      if !opened.is_empty() {
+        continue;
+      }
+
+      let unit = match Self::unit(&layout, open, close) {
+        // sections must not share a table row
+        Some(unit) if edited <= unit.replaced.start => unit,
+        Some(_) => return Err(TransformerError::UnbalancedSectionErr(open.name.clone())),
+        // markers are left in place, to be reported as unresolved placeholders
+        None => continue,
+      };
+
+      // markers of table rows are a part of the repeated markup, inline ones are outside of it
+      let (inner, outer) = open
+        .pieces
+        .iter()
+        .chain(&close.pieces)
+        .filter(|it| !unit.replaced.contains(&it.start) || unit.repeated.contains(&it.start))
+        .partition::<Vec<_>, _>(|it| unit.repeated.contains(&it.start));
+
+      let mut markup = erase(&input[unit.repeated.clone()], unit.repeated.start, inner);
+      if unit.is_inline {
+        // lets the fragment be parsed as a standalone paragraph
+        markup = [INLINE_PREFIX, &markup, INLINE_SUFFIX].concat();
+      }
+
+      let section = lookup(scope, &open.name, &mut usage.sections);
+      let value = section.as_ref().map(|it| it.value);
+      let rendered = match open.kind {
+        MarkerKind::Loop => {
+          let mut copies = Vec::new();
+          for (idx, element) in elements(value).iter().enumerate() {
+            let path = match (value, &section) {
+              (Some(JsonValue::Array(_)), Some(section)) => format!("{}[{idx}]", section.path),
+              (_, section) => section.as_ref().map(|it| it.path.clone()).unwrap_or_default(),
+            };
+            let scope = [scope, &[Frame { path, value: element }]].concat();
+            let filled = self.render(&markup, &scope, usage)?;
+            copies.extend_from_slice(Self::unwrap(&filled, unit.is_inline));
+          }
+          Some(copies)
+        }
+        MarkerKind::Condition | MarkerKind::InvertedCondition => {
+          if is_truthy(value) == (open.kind == MarkerKind::Condition) {
+            // placeholders are left to the enclosing scope
+            let expanded = self.expand(&markup, scope, usage)?;
+            Some(Self::unwrap(&expanded, unit.is_inline).to_vec())
+          } else if unit.is_inline {
+            // erase the text only, so the runs following the section keep their formatting
+            let hidden = layout.paragraphs[open.paragraph].locate(open.text.end..close.text.start);
+            edits.extend(hidden.into_iter().map(|piece| (piece, Vec::new())));
+            None
+          } else {
+            Some(Vec::new())
+          }
+        }
+        MarkerKind::Close => unreachable!(),
+      };
+
+      edited = close.pieces.iter().map(|it| it.end).fold(unit.replaced.end, usize::max);
+      units.push(unit.replaced.clone());
+      edits.extend(outer.into_iter().map(|piece| (piece.clone(), Vec::new())));
+      edits.extend(rendered.map(|rendered| (unit.replaced, rendered)));
+    }
+
+    if let Some(open) = opened.first() {
+      return Err(TransformerError::UnbalancedSectionErr(open.name.clone()));
+    }
+
+    // checkboxes of the sections are ticked along with their clones
+    let ticks =
+      ticks.into_iter().filter(|(range, _)| !units.iter().any(|it| it.contains(&range.start)));
+    edits.extend(ticks);
+
+    Ok(apply_edits(input, edits, Vec::with_capacity(input.len()))?)
+  }
+
+  /// Edits ticking checkboxes by the boolean fields of the scope.
+  fn tick(
+    &self,
+    input: &[u8],
+    scope: &[Frame],
+    usage: &mut Usage,
+  ) -> Result<Vec<Edit>, TransformerError> {
+    if !Checkboxes::are_met(input) || !self.has_flags() {
+      return Ok(Vec::new());
+    }
+    let checkboxes =
+      Checkboxes { open_bracket: &self.open_bracket, close_bracket: &self.close_bracket };
+    checkboxes.edits(input, |name| {
+      let mut used = HashSet::new();
+      let flag = lookup(scope, name, &mut used)?.value.as_bool()?;
+      usage.values.extend(used);
+      Some(flag)
+    })
+  }
+
+  /// Fills in a clone of a section: expands the nested sections, then replaces placeholders.
+  fn render(
+    &self,
+    input: &[u8],
+    scope: &[Frame],
+    usage: &mut Usage,
+  ) -> Result<Vec<u8>, TransformerError> {
+    let expanded = self.expand(input, scope, usage)?;
+    let layout = Layout::read(&expanded)?;
+    let texts = layout.paragraphs.iter().map(|paragraph| paragraph.text(&expanded));
+    self.resolve(texts, scope, usage).transform_stream(&expanded, Vec::new())
+  }
+
+  /// Replaces placeholders in link targets of a `.rels` file.
+  pub fn transform_relationships(
+    &self,
+    input: &[u8],
+    usage: &mut Usage,
+  ) -> Result<Vec<u8>, TransformerError> {
+    let targets = relationship_targets(input).into_values().map(String::into_bytes);
+    self.resolve(targets, &[Frame::root(&self.data)], usage).transform_relationships(input)
+  }
+
+  /// Placeholders met in the texts along with their values.
+  ///
+  /// Only the placeholders met are resolved, unknown ones are left to the enclosing scope.
+  fn resolve(
+    &self,
+    texts: impl IntoIterator<Item = Vec<u8>>,
+    scope: &[Frame],
+    usage: &mut Usage,
+  ) -> FindAndReplace<'static> {
+    let (open, close) = (self.open_bracket.as_bytes(), self.close_bracket.as_bytes());
+    let mut patterns = Vec::<Vec<u8>>::new();
+    let mut values = Vec::<Value>::new();
+
+    for text in texts {
+      for token in tokens(&text, open, close) {
+        let pattern = &text[token.clone()];
+        if patterns.iter().any(|it| it == pattern) {
+          continue;
+        }
+
+        let expression =
+          String::from_utf8_lossy(&pattern[open.len()..(pattern.len() - close.len())]);
+        let expression = quick_xml::escape::unescape(&expression).unwrap_or(expression.clone());
+        if let Some(value) = self
+          .filters
+          .evaluate(&expression, |path| lookup(scope, path, &mut usage.values).map(|it| it.value))
+        {
+          patterns.push(pattern.to_vec());
+          values.push(Value::from(value));
+        }
+      }
+    }
+
+    let placeholders = Placeholders::from_iter(patterns);
+    let replacements = Replacements::from_slice(values);
+    FindAndReplace { placeholders, replacements }
+  }
+
+  /// Strips the wrapping paragraph of an inline fragment.
+  fn unwrap(markup: &[u8], is_inline: bool) -> &[u8] {
+    match is_inline {
+      true => &markup[INLINE_PREFIX.len()..(markup.len() - INLINE_SUFFIX.len())],
+      false => markup,
+    }
+  }
+
+  /// Finds `{#name}`, `{?name}`, `{^name}` and `{/name}` markers in paragraphs,
+  /// returns them in the document order.
+  fn markers(&self, input: &[u8], layout: &Layout) -> Vec<Marker> {
+    let (open, close) = (self.open_bracket.as_bytes(), self.close_bracket.as_bytes());
+    let mut markers = Vec::new();
+
+    for (idx, paragraph) in layout.paragraphs.iter().enumerate() {
+      let text = paragraph.text(input);
+
+      for token in tokens(&text, open, close) {
+        let kind = match text[token.start + open.len()] {
+          b'#' => MarkerKind::Loop,
+          b'?' => MarkerKind::Condition,
+          b'^' => MarkerKind::InvertedCondition,
+          b'/' => MarkerKind::Close,
+          _ => continue,
+        };
+
+        let name = &text[(token.start + open.len() + 1)..(token.end - close.len())];
+        let mut rest = text[..token.start].iter().chain(&text[token.end..]);
+        markers.push(Marker {
+          kind,
+          name: String::from_utf8_lossy(name).trim().to_owned(),
+          paragraph: idx,
+          pieces: paragraph.locate(token.clone()),
+          text: token,
+          is_bare: rest.all(u8::is_ascii_whitespace),
+        });
+      }
+    }
+
+    markers.sort_by_key(|marker| marker.pieces.first().map(|piece| piece.start));
+    markers
+  }
+
+  /// Returns bytes of the document to be repeated, if markers are placed correctly.
+  fn unit(layout: &Layout, open: &Marker, close: &Marker) -> Option<Unit> {
+    let (first, last) = (&layout.paragraphs[open.paragraph], &layout.paragraphs[close.paragraph]);
+
+    // a loop inside a single table cell repeats the whole row
+    let is_row_loop = open.kind == MarkerKind::Loop && first.row.is_some();
+    if open.paragraph == close.paragraph && !is_row_loop {
+      let content = open.pieces.last()?.end..close.pieces.first()?.start;
+      return Some(Unit { replaced: content.clone(), repeated: content, is_inline: true });
+    }
+
+    // marker paragraphs having other text are kept, inside a table such markers repeat the row
+    let are_bare = open.is_bare && close.is_bare;
+    if first.parent == last.parent
+      && first.range.end <= last.range.start
+      && (are_bare || first.row.is_none())
+    {
+      let start = if open.is_bare { first.range.start } else { first.range.end };
+      let end = if close.is_bare { last.range.end } else { last.range.start };
+      return Some(Unit {
+        replaced: start..end,
+        repeated: first.range.end..last.range.start,
+        is_inline: false,
+      });
+    }
+
+    let first = layout.rows.get(&first.row?)?;
+    let last = layout.rows.get(&last.row?)?;
+    let are_siblings = first.parent == last.parent && first.range.start <= last.range.start;
+    let rows = first.range.start..last.range.end;
+    are_siblings.then(|| Unit { replaced: rows.clone(), repeated: rows, is_inline: false })
+  }
+}
+
+const INLINE_PREFIX: &[u8] = b"<w:p><w:r><w:t>";
+const INLINE_SUFFIX: &[u8] = b"</w:t></w:r></w:p>";
+
+impl<'d> Frame<'d> {
+  fn root(value: &'d JsonValue) -> Self {
+    Self { path: String::new(), value }
+  }
+
+  /// Path of the value's field, like `items[0].sku`.
+  fn join(&self, name: &str) -> String {
+    match self.path.is_empty() || name.starts_with('[') {
+      true => format!("{}{name}", self.path),
+      false => format!("{}.{name}", self.path),
+    }
+  }
+}
+
+/// Looks up the value by its name or path, starting from the innermost object.
+/// The value's path is put into the `used` set.
+fn lookup<'d>(scope: &[Frame<'d>], name: &str, used: &mut HashSet<String>) -> Option<Frame<'d>> {
+  let found = match name {
+    "." => scope.last().cloned(),
+    _ => scope.iter().rev().find_map(|frame| {
+      let value = paths::resolve(frame.value, name)?;
+      Some(Frame { path: frame.join(name), value })
+    }),
+  };
+  if let Some(ref found) = found {
+    used.insert(found.path.clone());
+  }
+  found
+}
+
+/// Elements to clone a section for.
+/// Paths of the values containing the one at `path`, `items[0]` and `items` for `items[0].sku`.
+fn parents(path: &str) -> impl Iterator<Item = &str> {
+  let separators = path.match_indices(['.', '[']).map(|(idx, _)| &path[..idx]);
+  separators.filter(|it| !it.is_empty())
+}
+
+fn elements(value: Option<&JsonValue>) -> &[JsonValue] {
+  match value {
+    None | Some(JsonValue::Null) => &[],
+    Some(JsonValue::Array(items)) => items,
+    Some(other) => std::slice::from_ref(other),
+  }
+}
+
+fn is_truthy(value: Option<&JsonValue>) -> bool {
+  match value {
+    None | Some(JsonValue::Null) => false,
+    Some(JsonValue::Bool(flag)) => *flag,
+    Some(JsonValue::Number(number)) => number.as_f64() != Some(0.0),
+    Some(JsonValue::String(string)) => !string.is_empty(),
+    Some(JsonValue::Array(items)) => !items.is_empty(),
+    Some(JsonValue::Object(fields)) => !fields.is_empty(),
+  }
+}
+
+/// Copies the `input`, skipping the `pieces` given relative to the `offset`.
+fn erase<'a>(
+  input: &[u8],
+  offset: usize,
+  pieces: impl IntoIterator<Item = &'a Range<usize>>,
+) -> Vec<u8> {
+  let mut pieces = pieces.into_iter().collect::<Vec<_>>();
+  pieces.sort_by_key(|piece| piece.start);
+
+  let mut output = Vec::with_capacity(input.len());
+  let mut reported = 0;
+  for piece in pieces {
+    output.extend_from_slice(&input[reported..(piece.start - offset)]);
+    reported = piece.end - offset;
+  }
+  output.extend_from_slice(&input[reported..]);
+  output
+}
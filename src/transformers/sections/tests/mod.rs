@@ -0,0 +1,428 @@
+use indoc::indoc;
+use serde_json::json;
+
+use super::*;
+
+fn sections(data: JsonValue) -> Sections {
+  Sections {
+    open_bracket: "{".into(),
+    close_bracket: "}".into(),
+    data,
+    filters: Default::default(),
+  }
+}
+
+fn run(data: JsonValue, input: &str) -> String {
+  let buf = sections(data).transform_stream(input, Vec::new(), &mut Usage::default()).unwrap();
+  String::from_utf8(buf).unwrap()
+}
+
+mod rows {
+  use super::*;
+
+  #[test]
+  fn repeats_row_per_array_element() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "items": [{ "name": "A", "qty": 1 }, { "name": "B", "qty": 2 }] }),
+        indoc! {r#"
+          <w:tbl>
+            <w:tr><w:tc><w:p><w:r><w:t>Name</w:t></w:r></w:p></w:tc></w:tr>
+            <w:tr><w:tc><w:p><w:r><w:t>{#items}{name}</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>{qty}{/items}</w:t></w:r></w:p></w:tc></w:tr>
+          </w:tbl>
+        "#},
+      ),
+      @r###"
+      <w:tbl>
+        <w:tr><w:tc><w:p><w:r><w:t>Name</w:t></w:r></w:p></w:tc></w:tr>
+        <w:tr><w:tc><w:p><w:r><w:t>A</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>1</w:t></w:r></w:p></w:tc></w:tr><w:tr><w:tc><w:p><w:r><w:t>B</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>2</w:t></w:r></w:p></w:tc></w:tr>
+      </w:tbl>
+      "###,
+    );
+  }
+
+  #[test]
+  fn repeats_several_rows() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "items": [{ "name": "A" }, { "name": "B" }] }),
+        indoc! {r#"
+          <w:tbl>
+            <w:tr><w:tc><w:p><w:r><w:t>{#items}{name}</w:t></w:r></w:p></w:tc></w:tr>
+            <w:tr><w:tc><w:p><w:r><w:t>—{/items}</w:t></w:r></w:p></w:tc></w:tr>
+          </w:tbl>
+        "#},
+      ),
+      @r###"
+      <w:tbl>
+        <w:tr><w:tc><w:p><w:r><w:t>A</w:t></w:r></w:p></w:tc></w:tr>
+        <w:tr><w:tc><w:p><w:r><w:t>—</w:t></w:r></w:p></w:tc></w:tr><w:tr><w:tc><w:p><w:r><w:t>B</w:t></w:r></w:p></w:tc></w:tr>
+        <w:tr><w:tc><w:p><w:r><w:t>—</w:t></w:r></w:p></w:tc></w:tr>
+      </w:tbl>
+      "###,
+    );
+  }
+
+  #[test]
+  fn markers_split_between_runs() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "items": ["x", "y"] }),
+        r#"<w:tr><w:tc><w:p><w:r><w:t>{#it</w:t></w:r><w:r><w:t>ems}{.}{/items</w:t></w:r><w:r><w:t>}</w:t></w:r></w:p></w:tc></w:tr>"#,
+      ),
+      @r###"<w:tr><w:tc><w:p><w:r><w:t></w:t></w:r><w:r><w:t>x</w:t></w:r><w:r><w:t></w:t></w:r></w:p></w:tc></w:tr><w:tr><w:tc><w:p><w:r><w:t></w:t></w:r><w:r><w:t>y</w:t></w:r><w:r><w:t></w:t></w:r></w:p></w:tc></w:tr>"###,
+    );
+  }
+
+  #[test]
+  fn falls_back_to_outer_fields() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "currency": "$", "items": [{ "price": 1 }, { "price": 2, "currency": "€" }] }),
+        r#"<w:tr><w:tc><w:p><w:r><w:t>{#items}{price}{currency}{/items}</w:t></w:r></w:p></w:tc></w:tr>"#,
+      ),
+      @r###"<w:tr><w:tc><w:p><w:r><w:t>1$</w:t></w:r></w:p></w:tc></w:tr><w:tr><w:tc><w:p><w:r><w:t>2€</w:t></w:r></w:p></w:tc></w:tr>"###,
+    );
+  }
+
+  #[test]
+  fn removes_rows_of_missing_array() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "items": [] }),
+        r#"<w:tbl><w:tr><w:tc><w:p><w:r><w:t>{#items}{name}{/items}</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"#,
+      ),
+      @r###"<w:tbl></w:tbl>"###,
+    );
+  }
+
+  #[test]
+  fn repeats_row_of_marker_paragraphs_with_text() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "items": [{ "name": "A", "qty": 1 }, { "name": "B", "qty": 2 }] }),
+        r#"<w:tr><w:tc><w:p><w:r><w:t>{#items}{name}</w:t></w:r></w:p><w:p><w:r><w:t>{qty}{/items}</w:t></w:r></w:p></w:tc></w:tr>"#,
+      ),
+      @r###"<w:tr><w:tc><w:p><w:r><w:t>A</w:t></w:r></w:p><w:p><w:r><w:t>1</w:t></w:r></w:p></w:tc></w:tr><w:tr><w:tc><w:p><w:r><w:t>B</w:t></w:r></w:p><w:p><w:r><w:t>2</w:t></w:r></w:p></w:tc></w:tr>"###,
+    );
+  }
+
+  #[test]
+  fn leaves_markers_of_unrelated_rows() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "items": [1, 2], "total": 3 }),
+        r#"<w:tbl><w:tr><w:tc><w:p><w:r><w:t>{#items}{total}</w:t></w:r></w:p></w:tc></w:tr></w:tbl><w:tbl><w:tr><w:tc><w:p><w:r><w:t>{/items}</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"#,
+      ),
+      @r###"<w:tbl><w:tr><w:tc><w:p><w:r><w:t>{#items}3</w:t></w:r></w:p></w:tc></w:tr></w:tbl><w:tbl><w:tr><w:tc><w:p><w:r><w:t>{/items}</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"###,
+    );
+  }
+
+  #[test]
+  fn fails_on_unbalanced_markers() {
+    let input = r#"<w:tr><w:tc><w:p><w:r><w:t>{#items}{/other}</w:t></w:r></w:p></w:tc></w:tr>"#;
+    let result = sections(json!({})).transform_stream(input, Vec::new(), &mut Usage::default());
+    assert!(matches!(result, Err(TransformerError::UnbalancedSectionErr(name)) if name == "other"));
+  }
+}
+
+mod paragraphs {
+  use super::*;
+
+  #[test]
+  fn repeats_paragraphs_between_markers() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "people": [{ "name": "Ann" }, { "name": "Bob" }] }),
+        indoc! {r#"
+          <w:p><w:r><w:t>Before</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{#people}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>Dear {name},</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{/people}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>After</w:t></w:r></w:p>
+        "#},
+      ),
+      @r###"
+      <w:p><w:r><w:t>Before</w:t></w:r></w:p>
+
+      <w:p><w:r><w:t>Dear Ann,</w:t></w:r></w:p>
+
+      <w:p><w:r><w:t>Dear Bob,</w:t></w:r></w:p>
+
+      <w:p><w:r><w:t>After</w:t></w:r></w:p>
+      "###,
+    );
+  }
+
+  #[test]
+  fn keeps_text_of_marker_paragraphs() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "people": [{ "name": "Ann" }, { "name": "Bob" }] }),
+        indoc! {r#"
+          <w:p><w:r><w:t>Team: {#people}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{name}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{/people}</w:t></w:r></w:p>
+        "#},
+      ),
+      @r###"
+      <w:p><w:r><w:t>Team: </w:t></w:r></w:p>
+      <w:p><w:r><w:t>Ann</w:t></w:r></w:p>
+
+      <w:p><w:r><w:t>Bob</w:t></w:r></w:p>
+      "###,
+    );
+  }
+
+  #[test]
+  fn expands_nested_sections() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "people": [{ "name": "Ann", "phones": ["1", "2"] }, { "name": "Bob", "phones": [] }] }),
+        indoc! {r#"
+          <w:p><w:r><w:t>{#people}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{name}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{#phones}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{name}: {.}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{/phones}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{/people}</w:t></w:r></w:p>
+        "#},
+      ),
+      @r###"
+      <w:p><w:r><w:t>Ann</w:t></w:r></w:p>
+
+      <w:p><w:r><w:t>Ann: 1</w:t></w:r></w:p>
+
+      <w:p><w:r><w:t>Ann: 2</w:t></w:r></w:p>
+
+
+      <w:p><w:r><w:t>Bob</w:t></w:r></w:p>
+
+
+      "###,
+    );
+  }
+
+  #[test]
+  fn repeats_paragraphs_inside_table_cell() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "lines": ["a", "b"] }),
+        r#"<w:tc><w:p><w:r><w:t>{#lines}</w:t></w:r></w:p><w:p><w:r><w:t>{.}</w:t></w:r></w:p><w:p><w:r><w:t>{/lines}</w:t></w:r></w:p></w:tc>"#,
+      ),
+      @r###"<w:tc><w:p><w:r><w:t>a</w:t></w:r></w:p><w:p><w:r><w:t>b</w:t></w:r></w:p></w:tc>"###,
+    );
+  }
+
+  #[test]
+  fn leaves_markers_of_different_levels() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "a": [1] }),
+        r#"<w:p><w:r><w:t>{#a}</w:t></w:r></w:p><w:tc><w:p><w:r><w:t>{/a}</w:t></w:r></w:p></w:tc>"#,
+      ),
+      @r###"<w:p><w:r><w:t>{#a}</w:t></w:r></w:p><w:tc><w:p><w:r><w:t>{/a}</w:t></w:r></w:p></w:tc>"###,
+    );
+  }
+}
+
+mod conditions {
+  use super::*;
+
+  #[test]
+  fn keeps_paragraphs_if_truthy() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "vip": true, "debtor": 0 }),
+        indoc! {r#"
+          <w:p><w:r><w:t>{?vip}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>Priority support</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{/vip}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{?debtor}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>Pay your debts</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{/debtor}</w:t></w:r></w:p>
+        "#},
+      ),
+      @r###"
+
+      <w:p><w:r><w:t>Priority support</w:t></w:r></w:p>
+
+
+      "###,
+    );
+  }
+
+  #[test]
+  fn inverted_keeps_paragraphs_if_falsy() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "items": [] }),
+        r#"<w:p><w:r><w:t>{^items}</w:t></w:r></w:p><w:p><w:r><w:t>Nothing to show</w:t></w:r></w:p><w:p><w:r><w:t>{/items}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:r><w:t>Nothing to show</w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn hides_runs_of_single_paragraph() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "discount": null, "vat": "20%" }),
+        r#"<w:p><w:r><w:t>Total{?discount} with </w:t></w:r><w:r><w:b/><w:t>discount{/discount}{?vat}, VAT {vat}{/vat}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:r><w:t>Total</w:t></w:r><w:r><w:b/><w:t>, VAT 20%</w:t></w:r></w:p>"###,
+    );
+  }
+
+  #[test]
+  fn hides_runs_inside_table_cell() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "note": false }),
+        r#"<w:tr><w:tc><w:p><w:r><w:t>a{^note}b{/note}</w:t></w:r></w:p></w:tc></w:tr>"#,
+      ),
+      @r###"<w:tr><w:tc><w:p><w:r><w:t>ab</w:t></w:r></w:p></w:tc></w:tr>"###,
+    );
+  }
+
+  #[test]
+  fn hides_table_rows() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "shipping": "" }),
+        r#"<w:tbl><w:tr><w:tc><w:p><w:r><w:t>{?shipping}Shipping</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>{shipping}{/shipping}</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"#,
+      ),
+      @r###"<w:tbl></w:tbl>"###,
+    );
+  }
+
+  #[test]
+  fn nests_into_loops() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "items": [{ "name": "A", "sale": true }, { "name": "B", "sale": false }] }),
+        r#"<w:p><w:r><w:t>{#items}{name}{?sale} (sale){/sale}; {/items}</w:t></w:r></w:p>"#,
+      ),
+      @r###"<w:p><w:r><w:t>A (sale); B; </w:t></w:r></w:p>"###,
+    );
+  }
+}
+
+mod paths {
+  use super::*;
+
+  #[test]
+  fn resolves_nested_names() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "order": { "items": [{ "sku": "A1", "origin": { "city": "Oslo" } }] } }),
+        r#"<w:tr><w:tc><w:p><w:r><w:t>{#order.items}{sku} from { origin.city }{/order.items}</w:t></w:r></w:p></w:tc></w:tr>"#,
+      ),
+      @r###"<w:tr><w:tc><w:p><w:r><w:t>A1 from Oslo</w:t></w:r></w:p></w:tc></w:tr>"###,
+    );
+  }
+}
+
+mod filters {
+  use super::*;
+
+  #[test]
+  fn formats_placeholders() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "client": "Ann", "items": [{ "price": 1234.5 }] }),
+        r#"<w:p><w:r><w:t>{client | upper}: {note | default:&quot;—&quot;}</w:t></w:r></w:p><w:tr><w:tc><w:p><w:r><w:t>{#items}{price | number:2,&apos; &apos;}{/items}</w:t></w:r></w:p></w:tc></w:tr>"#,
+      ),
+      @r###"<w:p><w:r><w:t>ANN: —</w:t></w:r></w:p><w:tr><w:tc><w:p><w:r><w:t>1 234.50</w:t></w:r></w:p></w:tc></w:tr>"###,
+    );
+  }
+
+  #[test]
+  fn uses_custom_filters() {
+    let mut sections = sections(json!({ "price": 5 }));
+    sections
+      .filters
+      .register("currency", |value, args| json!(format!("{value} {}", args[0].as_str().unwrap())));
+    let buf = sections.transform_stream(
+      r#"<w:p><w:r><w:t>{price | currency:EUR}</w:t></w:r></w:p>"#,
+      Vec::new(),
+      &mut Usage::default(),
+    );
+    insta::assert_snapshot!(String::from_utf8(buf.unwrap()).unwrap(), @r###"<w:p><w:r><w:t>5 EUR</w:t></w:r></w:p>"###);
+  }
+
+  #[test]
+  fn leaves_unknown_filters_alone() {
+    insta::assert_snapshot!(
+      run(json!({ "a": 1 }), r#"<w:p><w:r><w:t>{a | nope}</w:t></w:r></w:p>"#),
+      @r###"<w:p><w:r><w:t>{a | nope}</w:t></w:r></w:p>"###,
+    );
+  }
+}
+
+mod checkboxes {
+  use super::*;
+
+  #[test]
+  fn ticks_checkboxes_in_section_scopes() {
+    insta::assert_snapshot!(
+      run(
+        json!({ "sent": true, "items": [{ "name": "A", "done": true }, { "name": "B", "done": false }] }),
+        indoc! {r#"
+          <w:p><w:r><w:t>{#items}</w:t></w:r></w:p>
+          <w:p><w:sdt><w:sdtPr><w:tag w:val="done"/><w14:checkbox/></w:sdtPr><w:sdtContent><w:r><w:t>☐</w:t></w:r></w:sdtContent></w:sdt><w:r><w:t> {name}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{/items}</w:t></w:r></w:p>
+          <w:p><w:r><w:t>{sent}</w:t></w:r><w:sdt><w:sdtPr><w14:checkbox/></w:sdtPr><w:sdtContent><w:r><w:t>☐</w:t></w:r></w:sdtContent></w:sdt><w:r><w:t> Sent</w:t></w:r></w:p>
+        "#},
+      ),
+      @r###"
+      <w:p><w:sdt><w:sdtPr><w:tag w:val="done"/><w14:checkbox><w14:checked w14:val="1"/></w14:checkbox></w:sdtPr><w:sdtContent><w:r><w:t>☒</w:t></w:r></w:sdtContent></w:sdt><w:r><w:t> A</w:t></w:r></w:p>
+
+      <w:p><w:sdt><w:sdtPr><w:tag w:val="done"/><w14:checkbox><w14:checked w14:val="0"/></w14:checkbox></w:sdtPr><w:sdtContent><w:r><w:t>☐</w:t></w:r></w:sdtContent></w:sdt><w:r><w:t> B</w:t></w:r></w:p>
+
+      <w:p><w:sdt><w:sdtPr><w14:checkbox><w14:checked w14:val="1"/></w14:checkbox></w:sdtPr><w:sdtContent><w:r><w:t>☒</w:t></w:r></w:sdtContent></w:sdt><w:r><w:t> Sent</w:t></w:r></w:p>
+      "###,
+    );
+  }
+}
+
+mod usage {
+  use super::*;
+
+  #[test]
+  fn reports_outermost_unused_keys() {
+    let sections = sections(json!({
+      "client": { "name": "Ann", "phone": "123" },
+      "items": [{ "sku": "A1", "qty": 1 }],
+      "notes": { "a": 1, "b": 2 },
+    }));
+
+    let mut usage = Usage::default();
+    let input = r#"<w:p><w:r><w:t>{client.name} {#items}{sku}{/items}</w:t></w:r></w:p>"#;
+    sections.transform_stream(input, Vec::new(), &mut usage).unwrap();
+
+    assert_eq!(sections.unused_keys(&usage), ["client.phone", "items[0].qty", "notes"]);
+  }
+
+  #[test]
+  fn tracks_paths_of_section_scopes() {
+    let sections = sections(json!({
+      "client": { "name": "Ann", "phone": "123" },
+      "tags": ["a", "b"],
+      "items": [{ "sku": "A1" }, { "sku": "B2" }],
+    }));
+
+    let mut usage = Usage::default();
+    let input = r#"<w:p><w:r><w:t>{#client}{name}{/client} {#tags}{.}{/tags} {items[1].sku}</w:t></w:r></w:p>"#;
+    sections.transform_stream(input, Vec::new(), &mut usage).unwrap();
+
+    assert_eq!(sections.unused_keys(&usage), ["client.phone", "items[0]"]);
+  }
+
+  #[test]
+  fn lists_parents_of_paths() {
+    let paths = parents("items[0][1].sku").collect::<Vec<_>>();
+    assert_eq!(paths, ["items", "items[0]", "items[0][1]"]);
+    assert_eq!(parents("notes").count(), 0);
+  }
+}
@@ -0,0 +1,242 @@
+use std::io;
+use std::ops::Range;
+
+use quick_xml::events::{BytesStart, Event};
+
+/// An element of the document along with its position.
+#[derive(Debug)]
+pub(super) struct Node {
+  pub(super) name: Vec<u8>,
+  /// `w:id` attribute of comment and bookmark markers, and comment references.
+  pub(super) id: Option<String>,
+  /// Bytes of the element, tags included.
+  pub(super) range: Range<usize>,
+  /// Bytes between the opening and the closing tags.
+  pub(super) content: Range<usize>,
+  pub(super) parent: Option<usize>,
+  pub(super) children: Vec<usize>,
+}
+
+impl Node {
+  /// The opening tag of the element.
+  pub(super) fn start_tag<'i>(&self, input: &'i [u8]) -> Option<BytesStart<'i>> {
+    let mut reader = quick_xml::Reader::from_reader(&input[self.range.start..self.content.start]);
+    match reader.read_event() {
+      Ok(Event::Start(tag) | Event::Empty(tag)) => Some(tag),
+      _ => None,
+    }
+  }
+
+  /// Value of the attribute of the opening tag.
+  pub(super) fn attribute(&self, input: &[u8], name: &str) -> Option<String> {
+    let tag = self.start_tag(input)?;
+    let value = tag.try_get_attribute(name).ok()??;
+    value.unescape_value().ok().map(|it| it.into_owned())
+  }
+}
+
+/// A replacement of the input's bytes.
+pub(super) type Edit = (Range<usize>, Vec<u8>);
+
+/// Builds a tree of the elements, the root is the first node.
+pub(super) fn read(input: &[u8]) -> Result<Vec<Node>, quick_xml::Error> {
+  let mut reader = quick_xml::Reader::from_reader(input);
+  reader.config_mut().check_end_names = true;
+  let mut nodes = Vec::<Node>::new();
+  let mut stack = Vec::<usize>::new();
+
+  loop {
+    let position = reader.buffer_position() as usize;
+    let event = reader.read_event()?;
+    let end = reader.buffer_position() as usize;
+    match event {
+      Event::Start(ref tag) | Event::Empty(ref tag) => {
+        let idx = nodes.len();
+        let parent = stack.last().copied();
+        nodes.push(Node {
+          name: tag.name().as_ref().to_vec(),
+          id: marker_id(tag),
+          range: position..end,
+          content: end..end,
+          parent,
+          children: Vec::new(),
+        });
+        if let Some(parent) = parent {
+          nodes[parent].children.push(idx);
+        }
+        if let Event::Start(_) = event {
+          stack.push(idx);
+        }
+      }
+      Event::End(_) => {
+        if let Some(idx) = stack.pop() {
+          nodes[idx].range.end = end;
+          nodes[idx].content.end = position;
+        }
+      }
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+
+  Ok(nodes)
+}
+
+/// Indices of the node's ancestors, from the root down to the node itself.
+pub(super) fn path(nodes: &[Node], idx: usize) -> Vec<usize> {
+  let mut path = vec![idx];
+  while let Some(parent) = nodes[*path.last().unwrap_or(&idx)].parent {
+    path.push(parent);
+  }
+  path.reverse();
+  path
+}
+
+/// Byte ranges to be removed between the last nodes of the paths, which are removed too
+/// if `inclusive`.
+///
+/// Elements the range starts in keep their heads, ones it ends in keep their properties
+/// and tails, siblings in between are removed entirely.
+pub(super) fn cut_between(
+  nodes: &[Node],
+  from: &[usize],
+  to: &[usize],
+  inclusive: bool,
+) -> Vec<Range<usize>> {
+  let common = from.iter().zip(to).take_while(|(a, b)| a == b).count();
+  // the innermost element holding both ends
+  if common == from.len() || common == to.len() {
+    return match inclusive {
+      true => vec![nodes[*from.last().unwrap_or(&0)].range.clone()],
+      false => Vec::new(),
+    };
+  }
+
+  let mut cuts = Vec::new();
+  for level in common..from.len() - 1 {
+    let (node, child) = (&nodes[from[level]], &nodes[from[level + 1]]);
+    let since = match inclusive && level + 2 == from.len() {
+      true => child.range.start,
+      false => child.range.end,
+    };
+    cuts.push(since..node.content.end);
+  }
+  for level in common..to.len() - 1 {
+    let (node, child) = (from_properties(nodes, to[level]), &nodes[to[level + 1]]);
+    let until = match inclusive && level + 2 == to.len() {
+      true => child.range.end,
+      false => child.range.start,
+    };
+    cuts.push(node..until);
+  }
+  let (first, last) = (&nodes[from[common]], &nodes[to[common]]);
+  let since = match inclusive && common + 1 == from.len() {
+    true => first.range.start,
+    false => first.range.end,
+  };
+  let until = match inclusive && common + 1 == to.len() {
+    true => last.range.end,
+    false => last.range.start,
+  };
+  cuts.push(since..until);
+
+  cuts
+}
+
+/// Start of the element's content, past the properties like `<w:pPr>` or `<w:tblGrid>`.
+fn from_properties(nodes: &[Node], idx: usize) -> usize {
+  let node = &nodes[idx];
+  let properties = node.children.iter().map(|&it| &nodes[it]);
+  let properties = properties.take_while(|it| it.name.ends_with(b"Pr") || it.name == b"w:tblGrid");
+  properties.last().map_or(node.content.start, |it| it.range.end)
+}
+
+/// The first child of the node having the name.
+pub(super) fn child(nodes: &[Node], node: usize, name: &[u8]) -> Option<usize> {
+  nodes[node].children.iter().copied().find(|&it| nodes[it].name == name)
+}
+
+/// The opening tag of the node, with the attribute set to the value.
+pub(super) fn set_attribute(input: &[u8], node: &Node, name: &str, value: &str) -> Option<Edit> {
+  let tag = node.start_tag(input)?;
+  let mut edited = BytesStart::new(String::from_utf8_lossy(tag.name().as_ref()).into_owned());
+  edited
+    .extend_attributes(tag.attributes().flatten().filter(|it| it.key.as_ref() != name.as_bytes()));
+  edited.push_attribute((name, value));
+
+  let range = node.range.start..node.content.start;
+  let closing = match input[range.clone()].ends_with(b"/>") {
+    true => "/>",
+    false => ">",
+  };
+  Some((range, format!("<{}{closing}", String::from_utf8_lossy(&edited)).into_bytes()))
+}
+
+/// Writes the input with the edits applied in the order of their starts.
+///
+/// Edits starting at the same position keep their order. Ones overlapping an applied edit,
+/// whether nested in it or sticking out of it, are skipped entirely.
+pub(super) fn apply_edits<Out: io::Write>(
+  input: &[u8],
+  mut edits: Vec<Edit>,
+  mut output: Out,
+) -> io::Result<Out> {
+  edits.sort_by_key(|(range, _)| range.start);
+  let mut reported = 0;
+  for (range, replacement) in edits {
+    if range.start < reported {
+      continue;
+    }
+    output.write_all(&input[reported..range.start])?;
+    output.write_all(&replacement)?;
+    reported = range.end;
+  }
+  output.write_all(&input[reported..])?;
+  Ok(output)
+}
+
+/// `w:id` of comment and bookmark markers, and comment references.
+fn marker_id(tag: &BytesStart) -> Option<String> {
+  if !matches!(
+    tag.name().as_ref(),
+    b"w:commentRangeStart"
+      | b"w:commentRangeEnd"
+      | b"w:commentReference"
+      | b"w:bookmarkStart"
+      | b"w:bookmarkEnd"
+  ) {
+    return None;
+  }
+  let value = tag.try_get_attribute("w:id").ok()??;
+  value.unescape_value().ok().map(|it| it.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn apply(input: &str, edits: &[(Range<usize>, &str)]) -> String {
+    let edits = edits.iter().map(|(range, it)| (range.clone(), it.as_bytes().to_vec())).collect();
+    String::from_utf8(apply_edits(input.as_bytes(), edits, Vec::new()).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn applies_edits_in_order_of_starts() {
+    assert_eq!(apply("abcdef", &[(4..5, "E"), (0..1, "A")]), "AbcdEf");
+  }
+
+  #[test]
+  fn applies_adjacent_edits() {
+    assert_eq!(apply("abcdef", &[(1..3, "X"), (3..3, "+"), (3..5, "Y")]), "aX+Yf");
+  }
+
+  #[test]
+  fn skips_nested_edits() {
+    assert_eq!(apply("abcdef", &[(1..5, "X"), (2..3, "Y"), (3..3, "+")]), "aXf");
+  }
+
+  #[test]
+  fn skips_overlapping_edits() {
+    assert_eq!(apply("abcdef", &[(1..3, "X"), (2..5, "Y")]), "aXdef");
+  }
+}
@@ -1,20 +1,20 @@
 use std::io::Read;
 use zip::read::ZipFile;
-use zip::write::{FileOptions, SimpleFileOptions};
+use zip::write::SimpleFileOptions;
 use zip::DateTime;
 
 pub trait ZipFileExt
 where
   Self: Sized,
 {
-  fn to_options(&self) -> FileOptions<()>;
+  fn to_options(&self) -> SimpleFileOptions;
 }
 
 impl<'a, R: Read> ZipFileExt for ZipFile<'a, R> {
   /// `zip` package does not provide a way to copy a file header from another archive,
   /// that's why we do it manually. Implementation is based on
   /// [ZipWriter::raw_copy_file_rename](zip::ZipWriter::raw_copy_file_rename) method.
-  fn to_options(&self) -> FileOptions<()> {
+  fn to_options(&self) -> SimpleFileOptions {
     const ZIP64_BYTES_THR: u64 = u32::MAX as u64;
     const S_IFREG: u32 = 0o0100000;
 
@@ -0,0 +1,268 @@
+use std::error::Error;
+use std::io::{Cursor, Read};
+
+use insta::assert_snapshot;
+use serde_json::json;
+use zip::ZipArchive;
+
+use docx_template::{
+  CantRenderError, DocxFile, DocxTemplate, Placeholders, RenderMode, Replacements, Value,
+};
+
+/// Content of the archive's file, if it exists.
+fn part(docx: &[u8], name: &str) -> Option<String> {
+  let mut archive = ZipArchive::new(Cursor::new(docx)).ok()?;
+  let mut file = archive.by_name(name).ok()?;
+  let mut content = String::new();
+  file.read_to_string(&mut content).ok()?;
+  Some(content)
+}
+
+/// Paragraphs of the main part, without the namespaces and section properties.
+fn body(docx: &[u8]) -> String {
+  let document = part(docx, "word/document.xml").unwrap_or_default();
+  let start = document.find("<w:body>").map_or(0, |it| it + "<w:body>".len());
+  let end = document.find("<w:sectPr").unwrap_or(document.len());
+  document[start..end].to_owned()
+}
+
+#[test]
+fn converts_markdown_by_styles_of_the_document() -> Result<(), Box<dyn Error>> {
+  let mut docx = DocxFile::from_path("features/lists.docx")?;
+  let styles = docx.paragraph_styles()?;
+  let notes = Value::from_markdown_with_styles("# Fixes\n\n- **Paid** in full\n- Shipped", &styles);
+
+  let output = DocxTemplate::new(
+    docx,
+    Placeholders::from_iter(["{notes}"]),
+    Replacements::from_slice(vec![notes]),
+  )
+  .render()?;
+
+  assert_snapshot!(body(&output), @r###"<w:p><w:pPr><w:pStyle w:val="1"/></w:pPr><w:r><w:t>Release notes</w:t></w:r></w:p><w:p><w:r><w:rPr><w:i/></w:rPr><w:t></w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="1"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">Fixes</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="a3"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:rPr><w:b/><w:bCs/><w:i/></w:rPr><w:t xml:space="preserve">Paid</w:t></w:r><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve"> in full</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="a3"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">Shipped</w:t></w:r></w:p><w:p><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve"></w:t></w:r></w:p>"###);
+  Ok(())
+}
+
+#[test]
+fn converts_html_by_styles_of_the_document() -> Result<(), Box<dyn Error>> {
+  let mut docx = DocxFile::from_path("features/lists.docx")?;
+  let styles = docx.paragraph_styles()?;
+  let notes = Value::from_html_with_styles(
+    r#"<ol><li>Paid</li><li>See <a href="https://example.com/?a&amp;b">the site</a></li></ol>"#,
+    &styles,
+  );
+
+  let output = DocxTemplate::new(
+    docx,
+    Placeholders::from_iter(["{notes}"]),
+    Replacements::from_slice(vec![notes]),
+  )
+  .render()?;
+
+  assert_snapshot!(body(&output), @r###"<w:p><w:pPr><w:pStyle w:val="1"/></w:pPr><w:r><w:t>Release notes</w:t></w:r></w:p><w:p><w:r><w:rPr><w:i/></w:rPr><w:t></w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="a4"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="2"/></w:numPr></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">Paid</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="a4"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="2"/></w:numPr></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">See </w:t></w:r><w:hyperlink r:id="rId3"><w:r><w:rPr><w:i/><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr><w:t xml:space="preserve">the site</w:t></w:r></w:hyperlink></w:p><w:p><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve"></w:t></w:r></w:p>"###);
+  let rels = part(&output, "word/_rels/document.xml.rels").unwrap_or_default();
+  assert!(
+    rels.contains(r#"<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com/?a&amp;b" TargetMode="External"/>"#),
+    "{rels}"
+  );
+  Ok(())
+}
+
+#[test]
+fn adds_media_of_images() -> Result<(), Box<dyn Error>> {
+  let logo = std::fs::read("features/cat_min.jpg")?;
+  let output = DocxTemplate::new(
+    DocxFile::from_path("features/lists.docx")?,
+    Placeholders::from_iter(["{notes}"]),
+    Replacements::from_slice(vec![Value::image(logo.clone(), 120, 80).unwrap()]),
+  )
+  .render()?;
+
+  let mut archive = ZipArchive::new(Cursor::new(&output))?;
+  let mut media = Vec::new();
+  archive.by_name("word/media/image1.jpeg")?.read_to_end(&mut media)?;
+  assert_eq!(media, logo);
+
+  let rels = part(&output, "word/_rels/document.xml.rels").unwrap_or_default();
+  assert!(
+    rels.contains(r#"<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/image" Target="media/image1.jpeg"/>"#),
+    "{rels}"
+  );
+  assert!(body(&output).contains(r#"<a:blip r:embed="rId3""#), "{}", body(&output));
+
+  let content_types = part(&output, "[Content_Types].xml").unwrap_or_default();
+  assert!(
+    content_types.contains(r#"<Default Extension="jpeg" ContentType="image/jpeg"/>"#),
+    "{content_types}"
+  );
+  Ok(())
+}
+
+#[test]
+fn links_hyperlinks_via_part_relationships() -> Result<(), Box<dyn Error>> {
+  let output = DocxTemplate::new(
+    DocxFile::from_path("features/links.docx")?,
+    Placeholders::from_iter(["{link}"]),
+    Replacements::from_slice(vec![Value::hyperlink(
+      "KX-1042",
+      "https://track.example.com/?id=KX-1042&lang=en",
+    )]),
+  )
+  .render()?;
+
+  assert_snapshot!(body(&output), @r###"<w:p><w:r><w:t xml:space="preserve">Tracking: </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t></w:t></w:r><w:hyperlink r:id="rId3"><w:r><w:rPr><w:b/><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr><w:t xml:space="preserve">KX-1042</w:t></w:r></w:hyperlink><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve"></w:t></w:r></w:p><w:p><w:r><w:t xml:space="preserve">See the </w:t></w:r><w:hyperlink r:id="rId2" w:history="1"><w:r><w:rPr><w:u w:val="single"/></w:rPr><w:t>order</w:t></w:r></w:hyperlink><w:r><w:t xml:space="preserve"> online.</w:t></w:r></w:p>"###);
+  let rels = part(&output, "word/_rels/document.xml.rels").unwrap_or_default();
+  assert!(
+    rels.contains(r#"<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://track.example.com/?id=KX-1042&amp;lang=en" TargetMode="External"/>"#),
+    "{rels}"
+  );
+  Ok(())
+}
+
+#[test]
+fn replaces_placeholders_in_notes() -> Result<(), Box<dyn Error>> {
+  let output = DocxTemplate::new(
+    DocxFile::from_path("features/notes.docx")?,
+    Placeholders::from_iter(["{party}", "{law}", "{section}"]),
+    Replacements::from_iter(["Buyer", "Art. 5", "§ 2"]),
+  )
+  .render()?;
+
+  assert_snapshot!(body(&output), @r###"<w:p><w:r><w:t>The Buyer agrees to the terms.</w:t></w:r><w:r><w:footnoteReference w:id="1"/></w:r><w:r><w:endnoteReference w:id="1"/></w:r></w:p>"###);
+  for name in ["word/footnotes.xml", "word/endnotes.xml"] {
+    let notes = part(&output, name).unwrap_or_default();
+    assert!(notes.contains(r#"<w:t xml:space="preserve"> See Art. 5, </w:t></w:r><w:r><w:rPr><w:i/></w:rPr><w:t>§ 2</w:t>"#), "{notes}");
+  }
+  Ok(())
+}
+
+#[test]
+fn numbers_list_items_by_numbering_of_the_document() -> Result<(), Box<dyn Error>> {
+  let mut docx = DocxFile::from_path("features/lists.docx")?;
+  let styles = docx.paragraph_styles()?;
+  assert_eq!(styles.bullet_numbering.as_deref(), Some("1"));
+  assert_eq!(styles.numbered_numbering.as_deref(), Some("2"));
+
+  let notes =
+    Value::from_html_with_styles("<ul><li>Paid<ul><li>in full</li></ul></li></ul>", &styles);
+  let output = DocxTemplate::new(
+    docx,
+    Placeholders::from_iter(["{notes}"]),
+    Replacements::from_slice(vec![notes]),
+  )
+  .render()?;
+
+  assert_snapshot!(body(&output), @r###"<w:p><w:pPr><w:pStyle w:val="1"/></w:pPr><w:r><w:t>Release notes</w:t></w:r></w:p><w:p><w:r><w:rPr><w:i/></w:rPr><w:t></w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="a3"/><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">Paid</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val="a3"/><w:numPr><w:ilvl w:val="1"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve">in full</w:t></w:r></w:p><w:p><w:r><w:rPr><w:i/></w:rPr><w:t xml:space="preserve"></w:t></w:r></w:p>"###);
+  Ok(())
+}
+
+#[test]
+fn replaces_sections_placeholders_in_link_targets() -> Result<(), Box<dyn Error>> {
+  let output = DocxFile::from_path("features/links.docx")?
+    .into_template(json!({ "order": { "id": "kx-1042" }, "link": "KX-1042" }))?
+    .replace_in_link_targets(true)
+    .render_mode(RenderMode::Strict)
+    .render()?;
+
+  let rels = part(&output, "word/_rels/document.xml.rels").unwrap_or_default();
+  assert!(
+    rels.contains(r#"Target="https://portal.example.com/orders/KX-1042?tab=items&amp;sort=sku""#),
+    "{rels}"
+  );
+  Ok(())
+}
+
+#[test]
+fn creates_missing_custom_properties() -> Result<(), Box<dyn Error>> {
+  let output = DocxFile::from_path("features/lists.docx")?
+    .into_template(json!({}))?
+    .set_custom_property("Customer", "Acme & Co")
+    .render()?;
+
+  let custom = part(&output, "docProps/custom.xml").unwrap_or_default();
+  assert!(custom.contains(r#"name="Customer"><vt:lpwstr>Acme &amp; Co</vt:lpwstr>"#), "{custom}");
+  let rels = part(&output, "_rels/.rels").unwrap_or_default();
+  assert!(
+    rels.contains(r#"Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/custom-properties" Target="docProps/custom.xml"/>"#),
+    "{rels}"
+  );
+  let content_types = part(&output, "[Content_Types].xml").unwrap_or_default();
+  assert!(
+    content_types.contains(r#"<Override PartName="/docProps/custom.xml" ContentType="application/vnd.openxmlformats-officedocument.custom-properties+xml"/>"#),
+    "{content_types}"
+  );
+  Ok(())
+}
+
+#[test]
+fn rejects_non_finite_numbers_of_custom_properties() -> Result<(), Box<dyn Error>> {
+  let result = DocxFile::from_path("features/lists.docx")?
+    .into_template(json!({}))?
+    .set_custom_property("Ratio", f64::NAN)
+    .render();
+
+  let Err(CantRenderError::NonFiniteNumberErr(name)) = result else { panic!("{result:?}") };
+  assert_eq!(name, "Ratio");
+  Ok(())
+}
+
+#[test]
+fn reports_merge_fields_without_values() -> Result<(), Box<dyn Error>> {
+  let result = DocxFile::from_path("features/fields.docx")?
+    .into_template(json!({}))?
+    .fill_fields(true)
+    .render_mode(RenderMode::Strict)
+    .render();
+
+  let Err(CantRenderError::StrictRenderErr(report)) = result else { panic!("{result:?}") };
+  assert_eq!(report.unresolved_placeholders, ["MERGEFIELD Customer"]);
+  Ok(())
+}
+
+#[test]
+fn fills_property_fields_with_custom_properties_first() -> Result<(), Box<dyn Error>> {
+  let output = DocxFile::from_path("features/fields.docx")?
+    .into_template(json!({ "Customer": "Acme", "Project": "Hermes" }))?
+    .set_custom_property("project", "Artemis")
+    .fill_fields(true)
+    .render()?;
+
+  let document = part(&output, "word/document.xml").unwrap_or_default();
+  assert!(document.contains(r#"<w:t xml:space="preserve">Artemis</w:t>"#), "{document}");
+  assert!(!document.contains("Hermes"), "{document}");
+  Ok(())
+}
+
+#[test]
+fn replaces_images_by_alt_texts() -> Result<(), Box<dyn Error>> {
+  let cat = std::fs::read("features/cat_min.jpg")?;
+  let output = DocxFile::from_path("features/comment_and_image.docx")?
+    .into_template(json!({}))?
+    .replace_image_by_alt_text("image1.jpg", &cat)
+    .render()?;
+
+  let mut archive = ZipArchive::new(Cursor::new(&output))?;
+  let names = archive.file_names().filter(|it| *it == "word/media/image1.jpg").count();
+  assert_eq!(names, 1);
+  let mut image = Vec::new();
+  archive.by_name("word/media/image1.jpg")?.read_to_end(&mut image)?;
+  assert_eq!(image, cat);
+  Ok(())
+}
+
+#[test]
+fn reports_placeholders_without_brackets_and_writes_nothing() -> Result<(), Box<dyn Error>> {
+  let mut output = Cursor::new(Vec::new());
+  let result = DocxTemplate::new(
+    DocxFile::from_path("features/lists.docx")?,
+    Placeholders::from_iter(["{notes}", "{missing}"]),
+    Replacements::from_slice(vec![Value::from_text("Paid"), Value::from_text("-")]),
+  )
+  .render_mode(RenderMode::Strict)
+  .render_to(&mut output);
+
+  let Err(CantRenderError::StrictRenderErr(report)) = result else { panic!("{result:?}") };
+  assert_eq!(report.unused_keys, ["missing"]);
+  assert!(output.get_ref().is_empty());
+  Ok(())
+}